@@ -16,17 +16,31 @@ pub const MAX_FPS: usize = 60;
 fn main() {
 
     // return;
+
+    // find the data directory, given with --data-dir or the RUSTY_TETRIS_DATA environment variable on tests
+    let args: Vec<String> = std::env::args().collect();
+    if let Err(err) = init_data_dir(&args) { println!("Error creating the data directory: {}", err) }
+
+    // convert the data files written by older versions before anything reads them
+    migrate_data_files();
+
+    // "export" and "import" run without opening the window
+    if let Some(code) = run_command(&args) { std::process::exit(code) }
+
+    // the window settings of the config file are only read on launch
+    let window = config_tracker::get_window();
+
     let mut app = App::new(AppOptions {
         console_width: CONSOLE_WIDTH,
         console_height: CONSOLE_HEIGHT,
-        screen_width: CONSOLE_WIDTH * 8,
-        screen_height: CONSOLE_HEIGHT * 8,
+        screen_width: CONSOLE_WIDTH * window.scale,
+        screen_height: CONSOLE_HEIGHT * window.scale,
         window_title: "Rusty Tetris by Paulo Granthon".to_owned(),
         font_path: "terminal_8x8.png".to_owned(),
-        vsync: true,
-        fullscreen: false,
+        vsync: window.vsync,
+        fullscreen: window.fullscreen,
         show_cursor: false,
-        resizable: true,
+        resizable: window.resizable,
         intercept_close_request: false,
         max_fps: MAX_FPS,
     });
@@ -0,0 +1,56 @@
+// goals unlocked by playing, kept per profile
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Achievement { FirstTetris, TSpinTriple, BackToBack, Sprint, Rival }
+
+// frames a run has to clear SPRINT_LINES in, for the Sprint achievement
+pub const SPRINT_LINES: u32 = 40;
+pub const SPRINT_FRAMES: u32 = 60 * 60;
+
+impl Achievement {
+
+    // Returns all achievements
+    pub fn all() -> [Achievement; 5] {
+        [Achievement::FirstTetris, Achievement::TSpinTriple, Achievement::BackToBack, Achievement::Sprint, Achievement::Rival]
+    }
+
+    // Returns the name of the achievement
+    pub fn text (&self) -> &'static str {
+        match self {
+            Achievement::FirstTetris => "First Tetris",
+            Achievement::TSpinTriple => "T-Spin Triple",
+            Achievement::BackToBack => "Back to Back",
+            Achievement::Sprint => "Sprinter",
+            Achievement::Rival => "Rival",
+        }
+    }
+
+    // Returns what unlocks the achievement
+    pub fn description (&self) -> &'static str {
+        match self {
+            Achievement::FirstTetris => "Clear 4 rows at once",
+            Achievement::TSpinTriple => "Clear 3 rows with a T-Spin",
+            Achievement::BackToBack => "Chain 10 back to back Tetrises or T-Spins",
+            Achievement::Sprint => "Clear 40 lines in under a minute",
+            Achievement::Rival => "Win 10 versus matches",
+        }
+    }
+
+    // progress needed to unlock the achievement, 1 for the ones unlocked by a single event
+    pub fn goal (&self) -> u32 {
+        match self {
+            Achievement::BackToBack => 10,
+            Achievement::Rival => 10,
+            _ => 1,
+        }
+    }
+
+    // matches the achievement to a unique id for serialization
+    pub fn id (&self) -> u8 {
+        *self as u8
+    }
+
+    // returns the achievement of given serialization id
+    pub fn from_id (id: u8) -> Option<Self> {
+        Self::all().get(id as usize).copied()
+    }
+}
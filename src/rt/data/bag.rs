@@ -1,49 +1,69 @@
 
-use crate::database::{COUNT, random_enum, TetrominoID};
-use crate::Tetromino;
+use crate::database::{random_piece, TetrominoID};
+use rand::{rngs::StdRng, SeedableRng};
 
+#[derive(Clone)]
 pub struct Bag {
-    sequence :[TetrominoID; COUNT],
+    pieces: &'static [TetrominoID],
+    sequence: Vec<TetrominoID>,
     index: usize,
+
+    // the sequence is drawn from a generator seeded by seed, so the same seed always deals the same pieces
+    rng: StdRng,
+    seed: u64,
+    drawn: u32,
 }
 
 impl Bag {
-    fn fill_sequence (sequence: &mut Vec<TetrominoID>) -> Vec<TetrominoID> {
-        while sequence.len() < COUNT {
-            let mut tid = random_enum();
+    fn fill_sequence (&mut self, sequence: &mut Vec<TetrominoID>) -> Vec<TetrominoID> {
+        while sequence.len() < self.pieces.len() {
+            let mut tid = random_piece(self.pieces, &mut self.rng);
             while contains(&sequence, &tid) {
-                tid = random_enum();
+                tid = random_piece(self.pieces, &mut self.rng);
             }
             sequence.push(tid);
         }
         sequence.to_owned()
     }
-    fn sequence_to_array<const L: usize>(sequence: Vec<TetrominoID>) -> [TetrominoID; L] {
-        sequence.try_into().unwrap_or_else(|_| panic!("src\\rusty_tetris\\rt_random.Bag::new -- Error"))
+    pub fn from (pieces: &'static [TetrominoID], sequence: Vec<TetrominoID>, seed: u64) -> Self {
+        let mut bag = Self { pieces, sequence: vec![], index: 0, rng: StdRng::seed_from_u64(seed), seed, drawn: 0 };
+        bag.sequence = bag.fill_sequence(&mut sequence.to_owned());
+        bag
     }
-    pub fn from (sequence: Vec<TetrominoID>) -> Self {
-        Self {
-            sequence: Self::sequence_to_array(Self::fill_sequence(&mut sequence.to_owned())),
-            index: 0 
-        }
+    pub fn new (pieces: &'static [TetrominoID], seed: u64) -> Self {
+        Self::from(pieces, vec![], seed)
     }
-    pub fn new () -> Self {
-        Self::from(vec![])
+    // recreates the bag of given seed after drawing the given number of pieces from it
+    pub fn resume (pieces: &'static [TetrominoID], seed: u64, drawn: u32) -> Self {
+        let mut bag = Self::new(pieces, seed);
+        for _ in 0..drawn { bag.next(); }
+        bag
     }
-    pub fn next(&mut self) -> Tetromino {
+    // returns the seed of the bag and the number of pieces drawn from it
+    pub fn seed (&self) -> (u64, u32) {
+        (self.seed, self.drawn)
+    }
+    pub fn next(&mut self) -> TetrominoID {
         let seq_len = self.sequence.len();
         if self.index >= seq_len - 1 {
-            self.sequence = Self::sequence_to_array(Self::fill_sequence(&mut vec![self.sequence[seq_len - 1]]));
+            let mut carried = vec![self.sequence[seq_len - 1]];
+            self.sequence = self.fill_sequence(&mut carried);
             self.index = 0;
         }
-        let tetromino: Tetromino = self.sequence[self.index].get();
+        let id = self.sequence[self.index];
         self.index += 1;
-        tetromino
+        self.drawn += 1;
+        id
     }
     pub fn peek (&self) -> Option<TetrominoID> {
         if self.index >= self.sequence.len() { return None }
         Some(self.sequence[self.index])
     }
+    // returns the given number of pieces the bag will deal next, without drawing them
+    pub fn peek_n (&self, count: usize) -> Vec<TetrominoID> {
+        let mut bag = self.clone();
+        (0..count).map(|_| bag.next()).collect()
+    }
 
 }
 
@@ -54,6 +74,7 @@ fn contains(sequence: &Vec<TetrominoID>, tid: &TetrominoID) -> bool {
     false
 }
 pub trait HasBag {
-    fn bag_next(&mut self) -> Tetromino;
+    fn bag_next(&mut self) -> TetrominoID;
     fn bag_peek_next(&self) -> Option<TetrominoID>;
+    fn bag_peek(&self, count: usize) -> Vec<TetrominoID>;
 }
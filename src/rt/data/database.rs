@@ -1,18 +1,215 @@
-use crate::tetromino::*;
+use crate::RTColor::{self, *};
+use crate::DEFAULT_PLAYFIELD_WIDTH;
 // use std::slice::Iter;
 use TetrominoID::*;
 
-pub const COUNT: usize = 7;
+// maximum number of blocks of a piece
+pub const MAX_CELLS: usize = 5;
 
-// defines the available Tetrominos
+// size of the biggest bounding box of a piece
+pub const MAX_SIZE: usize = 5;
 
+// positions (x, y) of the blocks of a piece inside it's bounding box.
+// pieces have a variable number of blocks, the unused slots are kept at (0, 0)
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
-pub enum TetrominoID { I, J, L, O, S, Z, T }
+pub struct Cells {
+    blocks: [(i8, i8); MAX_CELLS],
+    len: u8,
+}
+
+impl Cells {
+
+    // creates the cells of a piece with N blocks
+    pub const fn new<const N: usize> (cells: [(i8, i8); N]) -> Self {
+        let mut blocks = [(0, 0); MAX_CELLS];
+        let mut i = 0;
+        while i < N {
+            blocks[i] = cells[i];
+            i += 1;
+        }
+        Self { blocks, len: N as u8 }
+    }
+
+    // creates the cells from a list of blocks, None if there's too many of them
+    pub fn from_slice (cells: &[(i8, i8)]) -> Option<Self> {
+        if cells.len() > MAX_CELLS { return None }
+        let mut blocks = [(0, 0); MAX_CELLS];
+        blocks[..cells.len()].copy_from_slice(cells);
+        Some(Self { blocks, len: cells.len() as u8 })
+    }
+}
+
+// the cells can be used as a slice of blocks
+impl std::ops::Deref for Cells {
+    type Target = [(i8, i8)];
+    fn deref (&self) -> &[(i8, i8)] { &self.blocks[..self.len as usize] }
+}
+
+// rotates the cells inside a bounding box of given size by one step (same as rotating the grid "clockwise")
+pub const fn rotate_cells (cells: Cells, size: i8) -> Cells {
+    let mut result = cells;
+    let mut i = 0;
+    while i < cells.len as usize {
+        result.blocks[i] = (cells.blocks[i].1, size - 1 - cells.blocks[i].0);
+        i += 1;
+    }
+    result
+}
+
+// precomputes the 4 rotation states starting from the spawn state
+pub const fn rotation_states (spawn: Cells, size: i8) -> [Cells; 4] {
+    let r1 = rotate_cells(spawn, size);
+    let r2 = rotate_cells(r1, size);
+    let r3 = rotate_cells(r2, size);
+    [spawn, r1, r2, r3]
+}
+
+// rotation states of each Tetromino on the Super Rotation System, computed at compile time
+const STATES_I: [Cells; 4] = rotation_states(Cells::new([(1, 0), (1, 1), (1, 2), (1, 3)]), 4);
+const STATES_J: [Cells; 4] = rotation_states(Cells::new([(1, 0), (1, 1), (1, 2), (2, 2)]), 3);
+const STATES_L: [Cells; 4] = rotation_states(Cells::new([(0, 2), (1, 0), (1, 1), (1, 2)]), 3);
+const STATES_O: [Cells; 4] = rotation_states(Cells::new([(0, 0), (0, 1), (1, 0), (1, 1)]), 2);
+const STATES_S: [Cells; 4] = rotation_states(Cells::new([(0, 1), (1, 1), (1, 2), (2, 2)]), 3);
+const STATES_Z: [Cells; 4] = rotation_states(Cells::new([(0, 2), (1, 1), (1, 2), (2, 1)]), 3);
+const STATES_T: [Cells; 4] = rotation_states(Cells::new([(0, 1), (1, 0), (1, 1), (2, 1)]), 3);
+
+// rotation states of the 12 pentominoes, rotating around the center of their bounding box
+const STATES_F5: [Cells; 4] = rotation_states(Cells::new([(1, 0), (2, 0), (0, 1), (1, 1), (1, 2)]), 3);
+const STATES_I5: [Cells; 4] = rotation_states(Cells::new([(0, 2), (1, 2), (2, 2), (3, 2), (4, 2)]), 5);
+const STATES_L5: [Cells; 4] = rotation_states(Cells::new([(3, 1), (0, 2), (1, 2), (2, 2), (3, 2)]), 4);
+const STATES_N5: [Cells; 4] = rotation_states(Cells::new([(0, 1), (1, 1), (1, 2), (2, 2), (3, 2)]), 4);
+const STATES_P5: [Cells; 4] = rotation_states(Cells::new([(1, 0), (2, 0), (0, 1), (1, 1), (2, 1)]), 3);
+const STATES_T5: [Cells; 4] = rotation_states(Cells::new([(0, 0), (1, 0), (2, 0), (1, 1), (1, 2)]), 3);
+const STATES_U5: [Cells; 4] = rotation_states(Cells::new([(0, 0), (2, 0), (0, 1), (1, 1), (2, 1)]), 3);
+const STATES_V5: [Cells; 4] = rotation_states(Cells::new([(0, 0), (0, 1), (0, 2), (1, 2), (2, 2)]), 3);
+const STATES_W5: [Cells; 4] = rotation_states(Cells::new([(0, 0), (0, 1), (1, 1), (1, 2), (2, 2)]), 3);
+const STATES_X5: [Cells; 4] = rotation_states(Cells::new([(1, 0), (0, 1), (1, 1), (2, 1), (1, 2)]), 3);
+const STATES_Y5: [Cells; 4] = rotation_states(Cells::new([(2, 1), (0, 2), (1, 2), (2, 2), (3, 2)]), 4);
+const STATES_Z5: [Cells; 4] = rotation_states(Cells::new([(0, 0), (1, 0), (1, 1), (1, 2), (2, 2)]), 3);
+
+// rotation states on the Nintendo Rotation System: pieces spawn flat side up and
+// I, S and Z only have two states, both kept to the right / bottom of the bounding box
+const NES_STATES_I: [Cells; 4] = two_states(Cells::new([(0, 2), (1, 2), (2, 2), (3, 2)]), Cells::new([(2, 0), (2, 1), (2, 2), (2, 3)]));
+const NES_STATES_J: [Cells; 4] = rotation_states(Cells::new([(0, 1), (1, 1), (2, 1), (2, 2)]), 3);
+const NES_STATES_L: [Cells; 4] = rotation_states(Cells::new([(0, 1), (1, 1), (2, 1), (0, 2)]), 3);
+const NES_STATES_S: [Cells; 4] = two_states(Cells::new([(1, 1), (2, 1), (0, 2), (1, 2)]), Cells::new([(1, 0), (1, 1), (2, 1), (2, 2)]));
+const NES_STATES_Z: [Cells; 4] = two_states(Cells::new([(0, 1), (1, 1), (1, 2), (2, 2)]), Cells::new([(2, 0), (1, 1), (2, 1), (1, 2)]));
+const NES_STATES_T: [Cells; 4] = rotation_states(Cells::new([(0, 1), (1, 1), (2, 1), (1, 2)]), 3);
+
+// rotation states on the Arika Rotation System (TGM): pieces spawn flat side up and
+// every state rests on the bottom of the bounding box instead of rotating around it's center
+const ARS_STATES_I: [Cells; 4] = two_states(Cells::new([(0, 1), (1, 1), (2, 1), (3, 1)]), Cells::new([(2, 0), (2, 1), (2, 2), (2, 3)]));
+const ARS_STATES_J: [Cells; 4] = [
+    Cells::new([(0, 1), (1, 1), (2, 1), (2, 2)]),
+    Cells::new([(1, 0), (2, 0), (1, 1), (1, 2)]),
+    Cells::new([(0, 1), (0, 2), (1, 2), (2, 2)]),
+    Cells::new([(1, 0), (1, 1), (0, 2), (1, 2)]),
+];
+const ARS_STATES_L: [Cells; 4] = [
+    Cells::new([(0, 1), (1, 1), (2, 1), (0, 2)]),
+    Cells::new([(1, 0), (1, 1), (1, 2), (2, 2)]),
+    Cells::new([(2, 1), (0, 2), (1, 2), (2, 2)]),
+    Cells::new([(0, 0), (1, 0), (1, 1), (1, 2)]),
+];
+const ARS_STATES_S: [Cells; 4] = two_states(Cells::new([(1, 1), (2, 1), (0, 2), (1, 2)]), Cells::new([(0, 0), (0, 1), (1, 1), (1, 2)]));
+const ARS_STATES_Z: [Cells; 4] = two_states(Cells::new([(0, 1), (1, 1), (1, 2), (2, 2)]), Cells::new([(2, 0), (1, 1), (2, 1), (1, 2)]));
+const ARS_STATES_T: [Cells; 4] = [
+    Cells::new([(0, 1), (1, 1), (2, 1), (1, 2)]),
+    Cells::new([(1, 0), (1, 1), (2, 1), (1, 2)]),
+    Cells::new([(1, 1), (0, 2), (1, 2), (2, 2)]),
+    Cells::new([(1, 0), (0, 1), (1, 1), (1, 2)]),
+];
+
+// states of pieces that only toggle between two orientations
+const fn two_states (a: Cells, b: Cells) -> [Cells; 4] {
+    [a, b, a, b]
+}
+
+// defines the sets of pieces a game can be played with
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PieceSet { Tetrominoes, Pentominoes }
+
+impl PieceSet {
+
+    // Returns all piece sets
+    pub fn all() -> [PieceSet; 2] {
+        [PieceSet::Tetrominoes, PieceSet::Pentominoes]
+    }
+
+    // Returns the name of the piece set
+    pub fn text (&self) -> &'static str {
+        match self {
+            PieceSet::Tetrominoes => "Tetrominoes",
+            PieceSet::Pentominoes => "Pentominoes",
+        }
+    }
+
+    // matches the piece set to a unique id for serialization
+    pub fn id (&self) -> u8 {
+        match self {
+            PieceSet::Tetrominoes => 0,
+            PieceSet::Pentominoes => 1,
+        }
+    }
+
+    // returns the piece set of given serialization id, defaulting to Tetrominoes
+    pub fn from_id (id: u8) -> Self {
+        match id {
+            1 => PieceSet::Pentominoes,
+            _ => PieceSet::Tetrominoes,
+        }
+    }
+
+    // Returns the pieces drawn from the bag
+    pub fn pieces (&self) -> &'static [TetrominoID] {
+        match self {
+            PieceSet::Tetrominoes => &[I, J, L, O, S, Z, T],
+            PieceSet::Pentominoes => &[F5, I5, L5, N5, P5, T5, U5, V5, W5, X5, Y5, Z5],
+        }
+    }
+
+    // Returns the number of columns of the playfield, bigger pieces need a wider board
+    pub fn board_width (&self) -> u8 {
+        match self {
+            PieceSet::Tetrominoes => DEFAULT_PLAYFIELD_WIDTH,
+            PieceSet::Pentominoes => 12,
+        }
+    }
+
+    // Returns the size of the biggest bounding box of the pieces
+    pub fn max_size (&self) -> u8 {
+        self.pieces().iter().map(|id| id.size()).max().unwrap_or(1)
+    }
+}
+
+// defines the available pieces: the 7 Tetrominos followed by the 12 pentominoes
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TetrominoID { I, J, L, O, S, Z, T, F5, I5, L5, N5, P5, T5, U5, V5, W5, X5, Y5, Z5 }
 impl TetrominoID {
 
-    // Returns all Tetrominos
-    pub fn all() -> [TetrominoID; COUNT] {
-        [I, J, L, O, S, Z, T]
+    // Returns all pieces
+    pub fn all() -> &'static [TetrominoID] {
+        &[I, J, L, O, S, Z, T, F5, I5, L5, N5, P5, T5, U5, V5, W5, X5, Y5, Z5]
+    }
+
+    // Returns the position of the piece on all()
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+
+    // Returns the name of the piece, pentominoes are suffixed by their number of blocks
+    pub fn text(&self) -> &'static str {
+        match self {
+            I => "I", J => "J", L => "L", O => "O", S => "S", Z => "Z", T => "T",
+            F5 => "F5", I5 => "I5", L5 => "L5", N5 => "N5", P5 => "P5", T5 => "T5",
+            U5 => "U5", V5 => "V5", W5 => "W5", X5 => "X5", Y5 => "Y5", Z5 => "Z5",
+        }
+    }
+
+    // Returns the Tetromino of given letter
+    pub fn from_text(text: &str) -> Option<TetrominoID> {
+        Self::all().iter().find(|id| id.text() == text).copied()
     }
 
     // Allows the enum iteration
@@ -21,59 +218,96 @@ impl TetrominoID {
     //     TETROMINOS.iter()
     // }
 
-    // Returns the Tetromino of the letter
-    pub fn get(&self) -> Tetromino {
-        use crate::RTColor::*;
+    // Returns the rotation states of the Tetromino on the Super Rotation System
+    pub fn states(&self) -> &'static [Cells; 4] {
+        match self {
+            I => &STATES_I,
+            J => &STATES_J,
+            L => &STATES_L,
+            O => &STATES_O,
+            S => &STATES_S,
+            Z => &STATES_Z,
+            T => &STATES_T,
+            F5 => &STATES_F5,
+            I5 => &STATES_I5,
+            L5 => &STATES_L5,
+            N5 => &STATES_N5,
+            P5 => &STATES_P5,
+            T5 => &STATES_T5,
+            U5 => &STATES_U5,
+            V5 => &STATES_V5,
+            W5 => &STATES_W5,
+            X5 => &STATES_X5,
+            Y5 => &STATES_Y5,
+            Z5 => &STATES_Z5,
+        }
+    }
 
-        // returns a Tetromino corresponding to the enum
+    // Returns the rotation states of the Tetromino on the Nintendo Rotation System
+    pub fn nes_states(&self) -> &'static [Cells; 4] {
         match self {
-            I => Tetromino::from_array(I, [
-                [false, false, false, false],
-                [true,  true,  true,  true],
-                [false, false, false, false],
-                [false, false, false, false],
-            ], Cyan),
-            J => Tetromino::from_array(J, [
-                [false, false, false],
-                [true,  true,  true ],
-                [false, false, true ],
-            ], Blue),
-            L => Tetromino::from_array(L, [
-                [false, false, true ],
-                [true,  true,  true ],
-                [false, false, false],
-            ], Orange),
-            O => Tetromino::from_array(O, [
-                [true, true],
-                [true, true],
-            ], Yellow),
-            S => Tetromino::from_array(S, [
-                [false, true,  false],
-                [false, true,  true ],
-                [false, false, true ],
-            ], Green),
-            Z => Tetromino::from_array(Z, [
-                [false, false, true ],
-                [false, true,  true ],
-                [false, true,  false],
-            ], Magenta),  
-            T => Tetromino::from_array(T, [
-                [false, true, false],
-                [true,  true, false],
-                [false, true, false],
-            ], Red),
-        
+            I => &NES_STATES_I,
+            J => &NES_STATES_J,
+            L => &NES_STATES_L,
+            O => &STATES_O,
+            S => &NES_STATES_S,
+            Z => &NES_STATES_Z,
+            T => &NES_STATES_T,
+            _ => self.states(),
         }
     }
-}
 
-// returns a random Tetromino id
-pub fn random_enum () -> TetrominoID {
-    use rand::Rng;
-    TetrominoID::all()[rand::thread_rng().gen_range(0..COUNT)]
+    // Returns the rotation states of the Tetromino on the Arika Rotation System
+    pub fn ars_states(&self) -> &'static [Cells; 4] {
+        match self {
+            I => &ARS_STATES_I,
+            J => &ARS_STATES_J,
+            L => &ARS_STATES_L,
+            O => &STATES_O,
+            S => &ARS_STATES_S,
+            Z => &ARS_STATES_Z,
+            T => &ARS_STATES_T,
+            _ => self.states(),
+        }
+    }
+
+    // Returns the size of the Tetromino's bounding box
+    pub fn size(&self) -> u8 {
+        match self {
+            I | L5 | N5 | Y5 => 4,
+            I5 => 5,
+            O => 2,
+            _ => 3,
+        }
+    }
+
+    // Returns the number of blocks of the piece
+    pub fn cell_count(&self) -> usize {
+        self.states()[0].len()
+    }
+
+    // Returns the color of the Tetromino
+    pub fn color(&self) -> RTColor {
+        match self {
+            I => Cyan,
+            J => Blue,
+            L => Orange,
+            O => Yellow,
+            S => Green,
+            Z => Magenta,
+            T => Red,
+            F5 | W5 => Green,
+            I5 => Cyan,
+            L5 | Y5 => Orange,
+            N5 | Z5 => Magenta,
+            P5 => Yellow,
+            T5 | X5 => Red,
+            U5 | V5 => Blue,
+        }
+    }
 }
 
-// returns a random Tetromino
-// pub fn random () -> Tetromino {
-//     random_enum().get()
-// }
+// returns a random piece id out of the given pieces, drawn from the given generator
+pub fn random_piece (pieces: &[TetrominoID], rng: &mut impl rand::Rng) -> TetrominoID {
+    pieces[rng.gen_range(0..pieces.len())]
+}
@@ -2,5 +2,9 @@ pub mod rt_color;   pub use rt_color::*;
 pub mod tetromino;  pub use tetromino::*;
 pub mod database;   pub use database::*;
 pub mod collision;  pub use collision::*;
+pub mod playfield;  pub use playfield::*;
 pub mod bag;        pub use bag::*;
-pub mod generics;
\ No newline at end of file
+pub mod rotation_system; pub use rotation_system::*;
+pub mod ruleset;    pub use ruleset::*;
+pub mod preferences; pub use preferences::*;
+pub mod achievement; pub use achievement::*;
@@ -0,0 +1,179 @@
+use crate::{RTColor, PieceSet, DEFAULT_PLAYFIELD_HEIGHT};
+
+// number of rows of the buffer zone above the visible playfield, where pieces spawn
+pub const HIDDEN_ROWS: u8 = 20;
+
+// maximum number of columns, limited by the bits of a row
+pub const MAX_PLAYFIELD_WIDTH: u8 = 32;
+
+// maximum number of visible rows
+pub const MAX_PLAYFIELD_HEIGHT: u8 = 44;
+
+// defines the board sizes a game can be played on
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BoardSize { Standard, FourWide, Tall, Wide }
+
+impl BoardSize {
+
+    // Returns all board sizes
+    pub fn all() -> [BoardSize; 4] {
+        [BoardSize::Standard, BoardSize::FourWide, BoardSize::Tall, BoardSize::Wide]
+    }
+
+    // Returns the name of the board size
+    pub fn text (&self) -> &'static str {
+        match self {
+            BoardSize::Standard => "Standard",
+            BoardSize::FourWide => "4-wide",
+            BoardSize::Tall => "24 rows",
+            BoardSize::Wide => "16-wide",
+        }
+    }
+
+    // matches the board size to a unique id for serialization
+    pub fn id (&self) -> u8 {
+        match self {
+            BoardSize::Standard => 0,
+            BoardSize::FourWide => 1,
+            BoardSize::Wide => 3,
+            BoardSize::Tall => 4,
+        }
+    }
+
+    // returns the board size of given serialization id, defaulting to Standard.
+    // id 2 was the 20 rows board, which is the standard height since the hidden buffer zone
+    pub fn from_id (id: u8) -> Self {
+        match id {
+            1 => BoardSize::FourWide,
+            3 => BoardSize::Wide,
+            4 => BoardSize::Tall,
+            _ => BoardSize::Standard,
+        }
+    }
+
+    // Returns the (columns, visible rows) of the board for the given piece set, never narrower than it's biggest piece
+    pub fn dimensions (&self, piece_set: PieceSet) -> (u8, u8) {
+        let (width, height) = match self {
+            BoardSize::Standard => (piece_set.board_width(), DEFAULT_PLAYFIELD_HEIGHT),
+            BoardSize::FourWide => (4, DEFAULT_PLAYFIELD_HEIGHT),
+            BoardSize::Tall => (piece_set.board_width(), 24),
+            BoardSize::Wide => (16, DEFAULT_PLAYFIELD_HEIGHT),
+        };
+        (width.max(piece_set.max_size()), height)
+    }
+}
+
+// the grid where Tetrominos are stacked, made of the hidden buffer rows on top of the visible ones.
+// occupancy is stored as one bitmask per row (bit x set = block at column x) for collision and line clears,
+// while the color of each block lives on a separate layer that is only read when rendering
+#[derive(Clone)]
+pub struct Playfield {
+    pub rows: Vec<u32>,
+    pub colors: Vec<Vec<Option<RTColor>>>,
+    width: u8,
+    hidden: u8,
+
+    // bitmask of a row with every column filled
+    full_row: u32,
+}
+
+impl Playfield {
+
+    // creates an empty playfield with the given number of columns and visible rows
+    pub fn new (width: u8, height: u8) -> Self {
+        let width = width.clamp(1, MAX_PLAYFIELD_WIDTH);
+        let height = height.clamp(1, MAX_PLAYFIELD_HEIGHT) + HIDDEN_ROWS;
+        Self {
+            rows: vec![0; height as usize],
+            colors: vec![vec![None; height as usize]; width as usize],
+            width,
+            hidden: HIDDEN_ROWS,
+            full_row: (((1u64 << width) - 1) as u32),
+        }
+    }
+
+    // number of columns
+    pub fn width (&self) -> i8 { self.width as i8 }
+
+    // number of rows, including the hidden ones
+    pub fn height (&self) -> i8 { self.rows.len() as i8 }
+
+    // number of rows of the buffer zone, the first visible row comes right after them
+    pub fn hidden_rows (&self) -> i8 { self.hidden as i8 }
+
+    // number of visible rows
+    pub fn visible_height (&self) -> i8 { self.height() - self.hidden_rows() }
+
+    // returns true if the given position is outside the playfield
+    pub fn out_of_bounds (&self, x: i8, y: i8) -> bool {
+        x < 0 || x >= self.width() || y < 0 || y >= self.height()
+    }
+
+    // returns true if there's a block at the given position. Positions outside the playfield are empty
+    pub fn occupied (&self, x: i8, y: i8) -> bool {
+        if self.out_of_bounds(x, y) { return false }
+        self.rows[y as usize] & (1 << x) != 0
+    }
+
+    // places a block of given color at the given position
+    pub fn set (&mut self, x: i8, y: i8, color: RTColor) {
+        if self.out_of_bounds(x, y) { return }
+        self.rows[y as usize] |= 1 << x;
+        self.colors[x as usize][y as usize] = Some(color);
+    }
+
+    // returns true if the given row mask, shifted to column x, hits a wall or a block at row y
+    pub fn collides_row (&self, mask: u32, x: i8, y: i8) -> bool {
+
+        // empty rows of a piece never collide
+        if mask == 0 { return false }
+
+        // any block above, below or outside the playfield collides
+        if y < 0 || y >= self.height() { return true }
+
+        // shift the mask into playfield columns, colliding if any bit falls off the sides
+        let shifted = if x < 0 {
+            if mask & ((1u32 << -x) - 1) != 0 { return true }
+            mask >> -x
+        } else {
+            let wide = (mask as u64) << x;
+            if wide & !(self.full_row as u64) != 0 { return true }
+            wide as u32
+        };
+
+        // test against the blocks already on the row
+        self.rows[y as usize] & shifted != 0
+    }
+
+    // returns true if the row at y has every column filled
+    pub fn row_is_full (&self, y: usize) -> bool {
+        self.rows[y] == self.full_row
+    }
+
+    // returns the indexes of every full row, from the top down
+    pub fn full_rows (&self) -> Vec<usize> {
+        (0..self.rows.len()).filter(|y| self.row_is_full(*y)).collect()
+    }
+
+    // removes every full row in a single pass, shifting the rows above down. Returns the number of cleared rows
+    pub fn clear_full_rows (&mut self) -> u8 {
+
+        // index of the row being written, starting from the bottom
+        let mut write = self.rows.len();
+
+        // loop through the rows from the bottom up, keeping only the ones that aren't full
+        for y in (0..self.rows.len()).rev() {
+            if self.row_is_full(y) { continue; }
+            write -= 1;
+            if write == y { continue; }
+            self.rows[write] = self.rows[y];
+            for column in self.colors.iter_mut() { column[write] = column[y]; }
+        }
+
+        // every row above the last written one is now empty
+        self.rows[..write].fill(0);
+        for column in self.colors.iter_mut() { column[..write].fill(None); }
+
+        write as u8
+    }
+}
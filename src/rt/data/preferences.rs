@@ -0,0 +1,77 @@
+use crate::RTColor;
+
+// frames a direction has to be held before it auto repeats (DAS), and frames between each repeat (ARR)
+pub const DAS_OPTIONS: [u8; 6] = [4, 6, 8, 10, 12, 16];
+pub const ARR_OPTIONS: [u8; 5] = [1, 2, 3, 4, 6];
+
+// number of upcoming pieces shown next to the playfield
+pub const PREVIEW_OPTIONS: [u8; 5] = [1, 2, 3, 4, 5];
+
+// palettes the blocks of the pieces are drawn with
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Theme { Classic, Pastel, Mono }
+
+impl Theme {
+
+    // Returns all themes
+    pub fn all() -> [Theme; 3] {
+        [Theme::Classic, Theme::Pastel, Theme::Mono]
+    }
+
+    // Returns the name of the theme
+    pub fn text (&self) -> &'static str {
+        match self {
+            Theme::Classic => "Classic",
+            Theme::Pastel => "Pastel",
+            Theme::Mono => "Mono",
+        }
+    }
+
+    // returns the theme of given serialization id, defaulting to Classic
+    pub fn from_id (id: u8) -> Self {
+        match id {
+            1 => Theme::Pastel,
+            2 => Theme::Mono,
+            _ => Theme::Classic,
+        }
+    }
+
+    // returns the color a block of the given color is drawn with
+    pub fn color (&self, color: RTColor) -> (u8, u8, u8, u8) {
+        let (r, g, b, a) = color.u8();
+        match self {
+            Theme::Classic => (r, g, b, a),
+            Theme::Pastel => (((r as u16 + 255) / 2) as u8, ((g as u16 + 255) / 2) as u8, ((b as u16 + 255) / 2) as u8, a),
+            Theme::Mono => {
+                let luma = ((r as u16 * 3 + g as u16 * 6 + b as u16) / 10) as u8;
+                (luma, luma, luma, a)
+            },
+        }
+    }
+}
+
+// gameplay preferences of a profile, chosen on the Settings
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Preferences {
+    pub das: u8,
+    pub arr: u8,
+    pub ghost: bool,
+    pub preview: u8,
+    pub theme: Theme,
+
+    // pauses the run when the window stops being updated, as it does once minimized or hidden (see StateHandler)
+    pub auto_pause: bool,
+}
+
+impl Default for Preferences {
+    fn default () -> Self {
+        Self {
+            das: 6,
+            arr: 6,
+            ghost: true,
+            preview: 1,
+            theme: Theme::Classic,
+            auto_pause: true,
+        }
+    }
+}
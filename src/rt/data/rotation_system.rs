@@ -0,0 +1,160 @@
+use crate::{Tetromino, TetrominoID, Playfield, Cells, RTColor, collides, load_ruleset};
+
+// identifies the available rotation systems
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RotationSystemID { Srs, Nes, Ars, Custom }
+
+impl RotationSystemID {
+
+    // Returns all rotation systems
+    pub fn all() -> [RotationSystemID; 4] {
+        [RotationSystemID::Srs, RotationSystemID::Nes, RotationSystemID::Ars, RotationSystemID::Custom]
+    }
+
+    // Returns the name of the rotation system
+    pub fn text (&self) -> &'static str {
+        match self {
+            RotationSystemID::Srs => "SRS",
+            RotationSystemID::Nes => "Classic NES",
+            RotationSystemID::Ars => "ARS (TGM)",
+            RotationSystemID::Custom => "Custom ruleset",
+        }
+    }
+
+    // matches the rotation system to a unique id for serialization
+    pub fn id (&self) -> u8 {
+        match self {
+            RotationSystemID::Srs => 0,
+            RotationSystemID::Nes => 1,
+            RotationSystemID::Ars => 2,
+            RotationSystemID::Custom => 3,
+        }
+    }
+
+    // returns the rotation system of given serialization id, defaulting to SRS
+    pub fn from_id (id: u8) -> Self {
+        match id {
+            1 => RotationSystemID::Nes,
+            2 => RotationSystemID::Ars,
+            3 => RotationSystemID::Custom,
+            _ => RotationSystemID::Srs,
+        }
+    }
+
+    // Returns the implementation of the rotation system. An invalid custom ruleset falls back to SRS
+    pub fn get (&self) -> Box<dyn RotationSystem> {
+        match self {
+            RotationSystemID::Srs => Box::new(Srs),
+            RotationSystemID::Nes => Box::new(Nes),
+            RotationSystemID::Ars => Box::new(Ars),
+            RotationSystemID::Custom => match load_ruleset() {
+                Ok(ruleset) => Box::new(ruleset),
+                Err(err) => {
+                    println!("RotationSystemID::get() -- Error loading custom ruleset, falling back to SRS: {}", err);
+                    Box::new(Srs)
+                }
+            },
+        }
+    }
+}
+
+// defines how Tetrominos spawn, which states they rotate through and how they kick off walls and blocks
+pub trait RotationSystem {
+
+    // to get the rotation states of a Tetromino, starting from it's spawn orientation
+    fn states (&self, id: TetrominoID) -> &[Cells; 4];
+
+    // to get the offsets tested in order when rotating a Tetromino between two states
+    fn kick_tests (&self, id: TetrominoID, from: u8, to: u8) -> &[(i8, i8)];
+
+    // to get the size of the bounding box of a Tetromino
+    fn size (&self, id: TetrominoID) -> u8 { id.size() }
+
+    // to get the color of a Tetromino
+    fn color (&self, id: TetrominoID) -> RTColor { id.color() }
+
+    // to get the column where a Tetromino spawns on a playfield of given width,
+    // and the rows it's moved down (or up when negative) from the guideline spawn rows
+    fn spawn_pos (&self, id: TetrominoID, width: i8) -> (i8, i8) {
+        ((width / 2) - (self.size(id) as i8 / 2), 0)
+    }
+
+    // creates a Tetromino of given id at it's spawn orientation
+    fn spawn (&self, id: TetrominoID) -> Tetromino {
+        Tetromino::new(id, *self.states(id), self.size(id), self.color(id))
+    }
+
+    // returns the offset to apply when rotating the Tetromino to the given state, or None if it can't rotate
+    fn kick (&self, tetromino: &Tetromino, to: u8, pos: (i8, i8), field: &Playfield) -> Option<(i8, i8)> {
+        let cells = tetromino.cells_at(to);
+        self.kick_tests(tetromino.id, tetromino.rotation, to).iter().copied()
+            .find(|kick| !collides(cells, (pos.0 + kick.0, pos.1 + kick.1), field))
+    }
+}
+
+// Super Rotation System: the guideline rotation with it's wall kick tables
+pub struct Srs;
+
+impl RotationSystem for Srs {
+    fn states (&self, id: TetrominoID) -> &[Cells; 4] { id.states() }
+
+    fn kick_tests (&self, id: TetrominoID, from: u8, to: u8) -> &[(i8, i8)] {
+        match id {
+            TetrominoID::I | TetrominoID::I5 => match (from, to) {
+                (0, 1) | (3, 2) => &[(0, 0), (-2,  0 ), ( 1,  0), (-2,  1), ( 1, -2)],
+                (0, 3) | (1, 2) => &[(0, 0), (-1,  0 ), ( 2,  0), (-1, -2), ( 2,  1)],
+                (1, 0) | (2, 3) => &[(0, 0), ( 2,  0 ), (-1,  0), ( 2, -1), (-1,  2)],
+                (2, 1) | (3, 0) => &[(0, 0), ( 1,  0 ), (-2,  0), ( 1,  2), (-2, -1)],
+                _ => &[(0, 0)],
+            },
+            _ => match (from, to) {
+                (0, 1) | (2, 1) => &[(0, 0), (-1,  0), (-1, -1), (0,  2), (-1,  2)],
+                (0, 3) | (2, 3) => &[(0, 0), ( 1,  0), ( 1, -1), (0,  2), ( 1,  2)],
+                (1, 0) | (1, 2) => &[(0, 0), ( 1,  0), ( 1,  1), (0, -2), ( 1, -2)],
+                (3, 0) | (3, 2) => &[(0, 0), (-1,  0), (-1,  1), (0, -2), (-1, -2)],
+                _ => &[(0, 0)],
+            }
+        }
+    }
+}
+
+// Nintendo Rotation System: right-handed rotation without any kicks
+pub struct Nes;
+
+impl RotationSystem for Nes {
+    fn states (&self, id: TetrominoID) -> &[Cells; 4] { id.nes_states() }
+
+    fn kick_tests (&self, _: TetrominoID, _: u8, _: u8) -> &[(i8, i8)] { &[(0, 0)] }
+}
+
+// Arika Rotation System (TGM): bottom-aligned states that kick one column right, then left
+pub struct Ars;
+
+impl RotationSystem for Ars {
+    fn states (&self, id: TetrominoID) -> &[Cells; 4] { id.ars_states() }
+
+    fn kick_tests (&self, id: TetrominoID, _: u8, _: u8) -> &[(i8, i8)] {
+        match id {
+            TetrominoID::I | TetrominoID::I5 => &[(0, 0)],
+            _ => &[(0, 0), (1, 0), (-1, 0)],
+        }
+    }
+
+    fn kick (&self, tetromino: &Tetromino, to: u8, pos: (i8, i8), field: &Playfield) -> Option<(i8, i8)> {
+        let cells = tetromino.cells_at(to);
+
+        // the basic rotation always has priority
+        if !collides(cells, pos, field) { return Some((0, 0)) }
+
+        // center column rule: L, J and T can't kick if the first blocked cell (in reading order) is on the middle column
+        if matches!(tetromino.id, TetrominoID::L | TetrominoID::J | TetrominoID::T) {
+            let first_blocked = cells.iter()
+                .filter(|(x, y)| collides(&[(*x, *y)], pos, field))
+                .min_by_key(|(x, y)| (*y, *x));
+            if let Some((1, _)) = first_blocked { return None }
+        }
+
+        self.kick_tests(tetromino.id, tetromino.rotation, to).iter().copied()
+            .find(|kick| !collides(cells, (pos.0 + kick.0, pos.1 + kick.1), field))
+    }
+}
@@ -24,6 +24,17 @@ impl RTColor {
         static RTCOLORS: [RTColor; COUNT] = [Black, White, Red, Green, Blue, Yellow, Magenta, Cyan, Orange];
         RTCOLORS.iter()
     }
+    pub fn from_text (text: &str) -> Option<RTColor> {
+        Self::iter().find(|color| color.text() == text).copied()
+    }
+    // matches the color to a unique id for serialization
+    pub fn id (&self) -> u8 {
+        *self as u8
+    }
+    // returns the color of given serialization id
+    pub fn from_id (id: u8) -> Option<RTColor> {
+        [Black, White, Red, Green, Blue, Yellow, Magenta, Cyan, Orange, Gray, DarkGray, DarkerGray].get(id as usize).copied()
+    }
     pub fn text (&self) -> &str {
         match self {
             Black       => "black",
@@ -0,0 +1,53 @@
+use crate::{TetrominoID, Cells, RTColor, RotationSystem, Srs};
+
+// kick tests indexed by [from][to] rotation state
+pub type KickTable = [[Vec<(i8, i8)>; 4]; 4];
+
+// the shape, color, spawn position and kick tables of a single piece of a Ruleset
+pub struct PieceRules {
+    pub states: [Cells; 4],
+    pub size: u8,
+    pub color: RTColor,
+    pub spawn: Option<(i8, i8)>,
+    pub kicks: KickTable,
+}
+
+// a rotation system defined by a ruleset file (see serialization::ruleset_loader)
+pub struct Ruleset {
+    pub name: String,
+
+    // rules of each piece, indexed by TetrominoID::index(). Pieces left out of the file follow SRS
+    pub pieces: Vec<Option<PieceRules>>,
+}
+
+impl Ruleset {
+    fn piece (&self, id: TetrominoID) -> Option<&PieceRules> { self.pieces[id.index()].as_ref() }
+}
+
+impl RotationSystem for Ruleset {
+    fn states (&self, id: TetrominoID) -> &[Cells; 4] {
+        match self.piece(id) { Some(piece) => &piece.states, None => Srs.states(id) }
+    }
+
+    fn kick_tests (&self, id: TetrominoID, from: u8, to: u8) -> &[(i8, i8)] {
+        match self.piece(id) {
+            Some(piece) => &piece.kicks[from as usize % 4][to as usize % 4],
+            None => Srs.kick_tests(id, from, to),
+        }
+    }
+
+    fn size (&self, id: TetrominoID) -> u8 {
+        match self.piece(id) { Some(piece) => piece.size, None => id.size() }
+    }
+
+    fn color (&self, id: TetrominoID) -> RTColor {
+        match self.piece(id) { Some(piece) => piece.color, None => id.color() }
+    }
+
+    fn spawn_pos (&self, id: TetrominoID, width: i8) -> (i8, i8) {
+        match self.piece(id).and_then(|piece| piece.spawn) {
+            Some(pos) => (pos.0.min(width - self.size(id) as i8).max(0), pos.1),
+            None => ((width / 2) - (self.size(id) as i8 / 2), 0),
+        }
+    }
+}
@@ -1,10 +1,11 @@
-use super::{generics::*, rt_color::RTColor, TetrominoID};
+use super::{rt_color::RTColor, TetrominoID, Cells};
 
 // a Tetris piece
-#[derive(Clone)]
+#[derive(Clone, Copy)]
 pub struct Tetromino {
     pub id: TetrominoID,
-    pub grid: Vec<Vec<bool>>,
+    pub states: [Cells; 4],
+    pub size: u8,
     pub color: RTColor,
     pub rotation: u8,
 }
@@ -20,50 +21,45 @@ impl fmt::Debug for Tetromino {
 // Tetromino's mechanics implementation
 impl Tetromino {
 
-    // create a new Tetromino from array
-    pub fn from_array<const W: usize, const H: usize>(id: TetrominoID, grid:[[bool;W];H], color:RTColor) -> Self {
+    // create a new Tetromino at it's spawn rotation
+    pub fn new(id: TetrominoID, states: [Cells; 4], size: u8, color: RTColor) -> Self {
         Tetromino {
             id,
-            grid:(0..W).map(|x| (0..W).map(|y| grid[x][y]).collect::<Vec<bool>>()).collect::<Vec<Vec<bool>>>(),
+            states,
+            size,
             color,
             rotation: 0,
         }
     }
 
-    // returns the Tetrommino as a String 
+    // returns the Tetrommino as a String
     pub fn str (&self) -> String {
-        self.grid.str()
+        let size = self.size as i8;
+        (0..size).map(|y| String::from_iter(
+            (0..size).map(|x|
+                if self.cells().contains(&(x, y)) {'#'} else {'_'}
+            )
+        )).collect::<Vec<String>>().join("\n")
     }
 
-    // overwrites the Tetromino's grid
-    pub fn set_grid (&mut self, new_grid: Vec<Vec<bool>>) {
-        self.grid = new_grid
+    // returns the cells of the current rotation state
+    pub fn cells (&self) -> &Cells {
+        &self.states[self.rotation as usize]
     }
 
-    // rotates the Tetromino
-    pub fn get_rotated (&mut self, clockwise: bool) -> Vec<Vec<bool>> {
-        rotate(&self.grid, clockwise)
+    // returns the cells of given rotation state
+    pub fn cells_at (&self, rotation: u8) -> &Cells {
+        &self.states[rotation as usize % 4]
     }
 
-}
-
-trait _2D {
-    fn str (&self) -> String;
-}
-
-impl _2D for Vec<Vec<bool>> {
-
-    // returns the Tetrommino as a String 
-    fn str (&self) -> String {
-        (0..self[0].len()).map(|y| String::from_iter(
-            (0..self.len()).map(|x| 
-                if self[x][y] {'#'} else {'_'}   
-            ).collect::<Vec<char>>()
-        )).collect::<Vec<String>>().join("\n")
+    // overwrites the Tetromino's rotation state
+    pub fn set_rotation (&mut self, rotation: u8) {
+        self.rotation = rotation % 4
     }
-}
-impl fmt::Debug for dyn _2D {
-    fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.str())
+
+    // returns the rotation state reached by rotating the Tetromino
+    pub fn get_rotated (&self, clockwise: bool) -> u8 {
+        (self.rotation + if clockwise { 1 } else { 3 }) % 4
     }
+
 }
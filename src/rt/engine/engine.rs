@@ -1,8 +1,8 @@
 use crate::routine_handler::*;
-use crate::{Game, GameEvent, RunState, RenderEngine, InputHandler};
+use crate::{Game, GameEvent, RunState, RenderEngine, InputHandler, ReplayInput, RESUMED, record_frame};
 // use super::render::*;
 
-extern crate doryen_rs; use doryen_rs::{DoryenApi, UpdateEvent};
+extern crate doryen_rs; use doryen_rs::{DoryenApi, InputApi, UpdateEvent};
 
 pub trait RustyEngine {
     fn init(&mut self);
@@ -16,6 +16,10 @@ impl RustyEngine for Game {
     // initialize the engine
     fn init(&mut self) {
         self.register_inputs();
+
+        // a resumed run already has it's routines and Tetromino (or is waiting for the next one)
+        if self.cur_tetromino.is_some() || self.entry_delay.is_some() { return }
+
         self.initialize_routines();
 
         // get the first Tetromino for the match
@@ -28,21 +32,68 @@ impl RustyEngine for Game {
         // get the current input
         let input = api.input();
         self.mouse_pos = input.mouse_pos();
+        self.tick_toasts();
+
+        let running = matches!(self.run_state, RunState::Countdown(_) | RunState::Playing);
+
+        // a replay plays the recorded keys of the run instead of the keyboard, until it runs out of them
+        if let Some(frame) = self.replay {
+            let recorded = match self.recording.get(frame) { Some(recorded) if running => *recorded, _ => return (None, None) };
+            self.replay = Some(frame + 1);
+            if recorded & RESUMED != 0 { self.register_inputs() }
+            let mut input = ReplayInput::new(input, self.controller.clone(), recorded);
+            return (self.play_frame(&mut input), None);
+        }
+
+        // otherwise the keys are recorded while the run is played
+        if running {
+            self.recording.push(record_frame(input, &self.controller, self.resumed));
+            self.resumed = false;
+        }
+
+        (self.play_frame(input), None)
+    }
+
+    // master render method
+    fn render(&mut self, api: &mut dyn DoryenApi) {
         
-        
+        // initialize the console
+        let con = api.con();
+
+        self.rt_render(con);
+
+    }
+
+}
+
+impl Game {
+
+    // plays a frame of the run with the given input
+    fn play_frame (&mut self, input: &mut dyn InputApi) -> Option<GameEvent> {
+
         // match the current state of the run
         match self.run_state {
 
-            // Allow player to position and rotate piece freely without y movement until player presses up/down or skip
-            RunState::Start => {
-                self.handle_input(input, "priority");
-                self.handle_input(input, "game");
-                self.handle_routines("priority");
+            // the first Tetromino waits at the top until the countdown ends, only Retry is handled meanwhile
+            RunState::Countdown(frames) => {
+                let event = self.handle_input(input, "countdown");
+                if event.is_some() { return event }
+                self.set_state(match frames { 0 | 1 => RunState::Playing, _ => RunState::Countdown(frames - 1) });
             },
 
             // also handle inputs but also calls routines to move y
             RunState::Playing => {
-                self.handle_input(input, "priority");
+                self.stats.frames += 1;
+                let event = self.handle_input(input, "priority");
+                if event.is_some() { return event }
+
+                // during the line clear and entry delays there's nothing to control, held keys are charged for the next Tetromino instead
+                if self.entry_delay.is_some() {
+                    self.charge_inputs(input);
+                    if let RunState::Playing = self.run_state { self.tick_entry_delay() }
+                    return None;
+                }
+
                 self.handle_input(input, "game");
                 self.handle_routines("priority");
                 self.handle_routines("game");
@@ -50,33 +101,20 @@ impl RustyEngine for Game {
 
             // handles inputs specific to the state and maybe return GameEvent
             RunState::Paused(_) => {
-                self.handle_input(input, "priority");
-                // paused should open a menu with the option to quit the run, that would return GameEvent
+                return self.handle_input(input, "priority");
             },
 
             // handle input and return GameEvent on input
-            RunState::Over => {
-                // return GameEvent to return to MainMenu on keypress
-                // self.reset()
-                return (self.handle_input(input, "over"), None);
+            RunState::Over(_) => {
+                // return GameEvent to go to the summary (or retry) on keypress
+                return self.handle_input(input, "over");
             },
             // _=> {}
         }
         
         // if update reaches this point, return None as the resulting GameEvent
-        (None, None)
+        None
 
     }
-
-    // master render method
-    fn render(&mut self, api: &mut dyn DoryenApi) {
-        
-        // initialize the console
-        let con = api.con();
-
-        self.rt_render(con);
-
-    }
-
 }
 
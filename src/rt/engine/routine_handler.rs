@@ -13,6 +13,10 @@ impl Routine {
         Self { key: key.to_owned(), category: category.to_owned(), cooldown, timer: match cooldown { Some(t) => t, None => 0 } }
     }
 
+    pub fn key (&self) -> &str {
+        &self.key
+    }
+
     pub fn trigger (&mut self) -> bool {
         // if self.key == "move_y" { println!("{}/{:?}", self.timer, self.cooldown)}
         match self.cooldown {
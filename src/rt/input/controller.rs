@@ -1,3 +1,83 @@
+// keys that can be bound to an input, by their doryen-rs names
+pub const VALID_KEYS: [&str; 76] = [
+    "Digit1",
+    "Digit2",
+    "Digit3",
+    "Digit4",
+    "Digit5",
+    "Digit6",
+    "Digit7",
+    "Digit8",
+    "Digit9",
+    "Digit0",
+    "KeyA",
+    "KeyB",
+    "KeyC",
+    "KeyD",
+    "KeyE",
+    "KeyF",
+    "KeyG",
+    "KeyH",
+    "KeyI",
+    "KeyJ",
+    "KeyK",
+    "KeyL",
+    "KeyM",
+    "KeyN",
+    "KeyO",
+    "KeyP",
+    "KeyQ",
+    "KeyR",
+    "KeyS",
+    "KeyT",
+    "KeyU",
+    "KeyV",
+    "KeyW",
+    "KeyX",
+    "KeyY",
+    "KeyZ",
+    "F1",
+    "F2",
+    "F3",
+    "F4",
+    "F5",
+    "F6",
+    "F7",
+    "F8",
+    "F9",
+    "F10",
+    "F11",
+    "F12",
+    "Snapshot",
+    "Insert",
+    "Home",
+    "Delete",
+    "End",
+    "PageDown",
+    "PageUp",
+    "ArrowLeft",
+    "ArrowUp",
+    "ArrowRight",
+    "ArrowDown",
+    "Backspace",
+    "Enter",
+    "Space",
+    "Numpad5",
+    "NumpadAdd",
+    "Backslash",
+    "Colon",
+    "Comma",
+    "NumpadDivide",
+    "Equal",
+    "Backquote",
+    "BracketLeft",
+    "Minus",
+    "Period",
+    "BracketRight",
+    "NumpadSubtract",
+    "Tab",
+];
+
 pub enum InputID {
     Pause,
     Left,
@@ -7,12 +87,13 @@ pub enum InputID {
     RotateL,
     RotateR,
     Skip,
+    Retry,
 }
 
 impl InputID {
 
     // returns a &str correspondingg to this InputID
-    pub fn as_str(&self) -> &str {
+    pub fn as_str(&self) -> &'static str {
         match self {
             InputID::Pause => "Pause",
             InputID::Left => "Left",
@@ -22,6 +103,7 @@ impl InputID {
             InputID::RotateL => "RotateL",
             InputID::RotateR => "RotateR",
             InputID::Skip => "Skip",
+            InputID::Retry => "Retry",
         }
     }
 
@@ -36,13 +118,14 @@ impl InputID {
             5 => Self::RotateL,
             6 => Self::RotateR,
             7 => Self::Skip,
+            8 => Self::Retry,
             _ => panic!("InputID::from_index({}) -- index out of range", index)
         }
     }
 
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Controller {
     pub action: String,
     pub left: String,
@@ -52,6 +135,7 @@ pub struct Controller {
     pub rotate_l: String,
     pub rotate_r: String,
     pub skip: String,
+    pub retry: String,
 }
 
 impl Controller {
@@ -65,25 +149,9 @@ impl Controller {
         up: String,
         rotate_l: String,
         rotate_r: String,
-        skip: String
-    ) -> Self { Self { action, left, right, down, up, rotate_l, rotate_r, skip, }}
-
-    pub fn from_vec (list: Vec<String>) -> Option<Self> {
-        if list.len() != 8 { 
-            println!("Controller::from_vec() -- Error: expected vec of length == 8 but got {} instead: {:?}", list.len(), list);
-            return None
-        }
-        Some(Self {
-            action:   list[0].to_owned(),
-            left:     list[1].to_owned(),
-            right:    list[2].to_owned(),
-            down:     list[3].to_owned(),
-            up:       list[4].to_owned(),
-            rotate_l: list[5].to_owned(),
-            rotate_r: list[6].to_owned(),
-            skip:     list[7].to_owned(),
-        })
-    }
+        skip: String,
+        retry: String,
+    ) -> Self { Self { action, left, right, down, up, rotate_l, rotate_r, skip, retry, }}
 
     // default controller
     pub fn default () -> Self {
@@ -96,6 +164,7 @@ impl Controller {
             "KeyW".to_string(),
             "KeyE".to_string(),
             "KeyQ".to_string(),
+            "KeyR".to_string(),
         )
     }
 
@@ -114,6 +183,7 @@ impl Controller {
             ["KeyN", "KeyW"][player].to_string(),
             ["KeyV", "KeyE"][player].to_string(),
             ["KeyB", "KeyQ"][player].to_string(),
+            "KeyR".to_string(),
         )
     }
 
@@ -128,6 +198,7 @@ impl Controller {
             InputID::RotateL => self.rotate_l.as_str(),
             InputID::RotateR => self.rotate_r.as_str(),
             InputID::Skip => self.skip.as_str(),
+            InputID::Retry => self.retry.as_str(),
         }
     }
 
@@ -143,12 +214,13 @@ impl Controller {
             5 => self.rotate_l.as_str(),
             6 => self.rotate_r.as_str(),
             7 => self.skip.as_str(),
+            8 => self.retry.as_str(),
             _ => panic!("Controller::get_at({}) -- index out of range", index)
         }
     }
 
     // returns an array with all String keys of the Controller
-    pub fn get_all (&self) -> [&str; 8] {[
+    pub fn get_all (&self) -> [&str; 9] {[
         self.action.as_str(),
         self.left.as_str(),
         self.right.as_str(),
@@ -157,6 +229,7 @@ impl Controller {
         self.rotate_l.as_str(),
         self.rotate_r.as_str(),
         self.skip.as_str(),
+        self.retry.as_str(),
     ]}
 
     // sets the key of the InputID at the given index
@@ -170,6 +243,7 @@ impl Controller {
             5 => self.rotate_l = key.to_string(),
             6 => self.rotate_r = key.to_string(),
             7 => self.skip = key.to_string(),
+            8 => self.retry = key.to_string(),
             _ => panic!("Controller.set_at({}, {}) -- Trying to set key of InputID at invalid index", index, key) 
 
         }
@@ -1,4 +1,4 @@
-use crate::{InputHandler, KeyMap, GameEvent, InputID, Game, DEBUG_MOVEMENT, rt::state::{RunState, PauseMenuAction}};
+use crate::{InputHandler, KeyMap, GameEvent, GameState, InputID, Game, DEBUG_MOVEMENT, rt::state::{RunState, PauseMenuAction, GameOverReason}};
 
 // implement the InputHandler trait on RustyTetris
 impl InputHandler for Game {
@@ -8,10 +8,10 @@ impl InputHandler for Game {
 
         self.inputmap = vec![
 
-            // KeyMap::new("Backspace",                "priority", None ),
             KeyMap::new(self.controller.get(InputID::Pause),   "priority", None ),
-            KeyMap::new(self.controller.get(InputID::Left),    "priority", Some(6) ),
-            KeyMap::new(self.controller.get(InputID::Right),   "priority", Some(6) ),
+            KeyMap::new(self.controller.get(InputID::Retry),   "priority", None ),
+            KeyMap::new(self.controller.get(InputID::Left),    "priority", Some(self.preferences.arr) ).with_delay(self.preferences.das),
+            KeyMap::new(self.controller.get(InputID::Right),   "priority", Some(self.preferences.arr) ).with_delay(self.preferences.das),
 
             KeyMap::new(self.controller.get(InputID::Up),       "game", Some(0) ),
             KeyMap::new(self.controller.get(InputID::Down),     "game", Some(0) ),
@@ -21,7 +21,10 @@ impl InputHandler for Game {
             KeyMap::new(self.controller.get(InputID::RotateR),  "game", Some(8) ),
             KeyMap::new(self.controller.get(InputID::Skip),     "game", None ),
 
+            KeyMap::new(self.controller.get(InputID::Retry),   "countdown", None ),
+
             KeyMap::new(self.controller.get(InputID::Pause),   "over", None ),
+            KeyMap::new(self.controller.get(InputID::Retry),   "over", None ),
 
         ];
 
@@ -42,15 +45,23 @@ impl InputHandler for Game {
 
                 if key == "" {}
 
-                // priority (checked before paused)
-                else if key == "Backspace"                { self.reset() }
+                // Retry starts the game mode over with a new seed, on any state of the run
+                else if key == self.controller.get(InputID::Retry) { return Some(GameEvent::Retry) }
 
                 // Pause
                 else if key == self.controller.get(InputID::Pause) { 
                     match &mut self.run_state {
+
+                        // the key bindings close back to the menu
+                        RunState::Paused(menu) if menu.controls => { menu.controls = false; return None },
                         RunState::Paused(menu) => match menu.action() {
                             PauseMenuAction::Continue => { self.pause(); return None },
-                            PauseMenuAction::Exit => { self.pause(); self.set_state(RunState::Over); return None }
+                            PauseMenuAction::Restart => return Some(GameEvent::Retry),
+
+                            // the settings are shown over the run, which is still paused when they're closed
+                            PauseMenuAction::Settings => return Some(GameEvent::Suspend(GameState::settings())),
+                            PauseMenuAction::Controls => { menu.controls = true; return None },
+                            PauseMenuAction::Exit => { self.pause(); self.set_state(RunState::Over(GameOverReason::Quit)); return None }
                         },
                         _=> {
                             if self.inputmap[index].category == "over".to_owned() { return Some(GameEvent::GameOver); }
@@ -102,4 +113,24 @@ impl InputHandler for Game {
         None
 
     }
+}
+
+impl Game {
+
+    // reads the keys held during the entry delay: a held direction shifts the next Tetromino as soon as it spawns (charged DAS),
+    // and a held rotate key rotates it if initial rotation is on. The last key held when the delay ends wins
+    pub fn charge_inputs (&mut self, input: &mut dyn doryen_rs::InputApi) {
+        let held = |id: InputID| input.key(self.controller.get(id));
+
+        self.charged_shift = match (held(InputID::Left), held(InputID::Right)) {
+            (true, false) => -1,
+            (false, true) => 1,
+            _ => 0,
+        };
+        self.charged_rotation = match (held(InputID::RotateL), held(InputID::RotateR)) {
+            (true, false) => Some(true),
+            (false, true) => Some(false),
+            _ => None,
+        };
+    }
 }
\ No newline at end of file
@@ -4,6 +4,12 @@ pub struct KeyMap {
     pub category: String,
     pub cooldown: Option<u8>,
     pub timer: u8,
+
+    // frames to wait after the first trigger of a hold before auto repeating every cooldown frames (DAS)
+    pub delay: Option<u8>,
+
+    // number of triggers since the key was pressed
+    held: u8,
 }
 
 // implementation of functionallities of InputStatus
@@ -11,7 +17,19 @@ impl KeyMap {
 
     // creates a new InputStatus. Initializes it's timer with it's KeyMap cooldown value if Some
     pub fn new (key_text: &str, category: &str, cooldown: Option<u8>) -> Self {
-        Self { key_text: key_text.to_owned(), category: category.to_owned(), cooldown, timer: match cooldown { Some(t) => t, None => 0 } }
+        Self { key_text: key_text.to_owned(), category: category.to_owned(), cooldown, timer: cooldown.unwrap_or_default(), delay: None, held: 0 }
+    }
+
+    // sets the delay before the key starts auto repeating
+    pub fn with_delay (mut self, delay: u8) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    // skips the delay of a held key that just triggered, so it auto repeats every cooldown frames from now on (charged DAS)
+    pub fn charge (&mut self) {
+        self.held = self.held.max(2);
+        self.timer = 0;
     }
 
     // checks if key is pressed / held and returns true if input triggers
@@ -26,21 +44,27 @@ impl KeyMap {
             // Some cooldown is set, input should auto trigger when reaching cooldown
             Some(cooldown) => {
 
+                // the first repeat of a hold waits for the delay, if any
+                let cooldown = if self.held == 1 { self.delay.unwrap_or(cooldown) } else { cooldown };
+
                 // it timer since last successfull trigger is less than input's cooldown 
                 if self.timer < cooldown {
 
                     // increase the timer by one frame and return
                     self.timer = (self.timer + 1).min(cooldown);
+                    if !input.key(&self.key_text) { self.held = 0 }
                     return false;
                 }
 
                 // if key is not pressed or held, also don't trigger
                 if !input.key(&self.key_text) {
+                    self.held = 0;
                     return false
                 }
 
                 // reset timer only if input returns true
                 self.timer = 0;
+                self.held = self.held.saturating_add(1);
 
                 // successfull trigger
                 true
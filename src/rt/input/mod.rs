@@ -2,3 +2,4 @@ pub mod input_handler; pub use input_handler::*;
 pub mod controller; pub use controller::*;
 pub mod key_map; pub use key_map::*;
 pub mod impls; pub use impls::*;
+pub mod replay; pub use replay::*;
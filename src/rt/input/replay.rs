@@ -0,0 +1,54 @@
+use crate::Controller;
+use doryen_rs::{InputApi, Keys};
+
+// the keys of the controller held (low byte) and pressed (high byte) on a frame of a run, by their index on the
+// controller (see Controller::get_at). The pause key isn't replayed, it's bit marks the frames a paused run was resumed on
+pub type ReplayFrame = u16;
+pub const RESUMED: ReplayFrame = 1;
+
+// number of keys replayed, the ones of the controller up to Skip. Retry leaves the run, so it's never replayed
+const KEYS: usize = 8;
+
+// reads the keys of the controller on the current frame
+pub fn record_frame (input: &mut dyn InputApi, controller: &Controller, resumed: bool) -> ReplayFrame {
+    let mut frame = if resumed { RESUMED } else { 0 };
+    for i in 1..KEYS {
+        let key = controller.get_at(i);
+        if input.key(key) { frame |= 1 << i }
+        if input.key_pressed(key) { frame |= 1 << (i + KEYS) }
+    }
+    frame
+}
+
+// input that answers with the keys of a recorded frame instead of the keyboard, for the keys of the controller.
+// Anything else is asked to the real input
+pub struct ReplayInput<'a> {
+    input: &'a mut dyn InputApi,
+    controller: Controller,
+    frame: ReplayFrame,
+}
+
+impl<'a> ReplayInput<'a> {
+    pub fn new (input: &'a mut dyn InputApi, controller: Controller, frame: ReplayFrame) -> Self {
+        Self { input, controller, frame }
+    }
+
+    // true if any key of the controller with given name has it's bit set, offset by the byte of the frame
+    fn recorded (&self, key: &str, offset: usize) -> bool {
+        (1..KEYS).any(|i| self.controller.get_at(i) == key && self.frame & 1 << (i + offset) != 0)
+    }
+}
+
+impl<'a> InputApi for ReplayInput<'a> {
+    fn key (&self, key: &str) -> bool { self.recorded(key, 0) }
+    fn key_pressed (&mut self, key: &str) -> bool { self.recorded(key, KEYS) }
+    fn keys_pressed (&self) -> Keys<'_> { self.input.keys_pressed() }
+    fn key_released (&mut self, key: &str) -> bool { self.input.key_released(key) }
+    fn keys_released (&self) -> Keys<'_> { self.input.keys_released() }
+    fn text (&self) -> String { String::new() }
+    fn mouse_button (&self, num: usize) -> bool { self.input.mouse_button(num) }
+    fn mouse_button_pressed (&mut self, num: usize) -> bool { self.input.mouse_button_pressed(num) }
+    fn mouse_button_released (&mut self, num: usize) -> bool { self.input.mouse_button_released(num) }
+    fn mouse_pos (&self) -> (f32, f32) { self.input.mouse_pos() }
+    fn close_requested (&self) -> bool { self.input.close_requested() }
+}
@@ -102,6 +102,26 @@ fn decode_hex(s: &str) -> (u8, u8, u8, u8) {
     
 }
 
+// characters of the font's upper half (code page 437) that can show up on text typed by the player, and their glyphs
+const EXTENDED_GLYPHS: [(char, u8); 50] = [
+    ('Ç', 128), ('ü', 129), ('é', 130), ('â', 131), ('ä', 132), ('à', 133), ('å', 134), ('ç', 135), ('ê', 136), ('ë', 137),
+    ('è', 138), ('ï', 139), ('î', 140), ('ì', 141), ('Ä', 142), ('Å', 143), ('É', 144), ('æ', 145), ('Æ', 146), ('ô', 147),
+    ('ö', 148), ('ò', 149), ('û', 150), ('ù', 151), ('ÿ', 152), ('Ö', 153), ('Ü', 154), ('¢', 155), ('£', 156), ('¥', 157),
+    ('₧', 158), ('ƒ', 159), ('á', 160), ('í', 161), ('ó', 162), ('ú', 163), ('ñ', 164), ('Ñ', 165), ('ª', 166), ('º', 167),
+    ('¿', 168), ('⌐', 169), ('¬', 170), ('½', 171), ('¼', 172), ('¡', 173), ('«', 174), ('»', 175), ('ß', 225), ('µ', 230),
+];
+
+// converts text to the glyphs of the font: characters out of ASCII are replaced by their code page 437 glyph, or by '?'
+pub fn glyphs (text: &str) -> String {
+    text.chars().map(|c| match c {
+        ' '..='~' => c,
+        _ => match EXTENDED_GLYPHS.iter().find(|(glyph, _)| *glyph == c) {
+            Some((_, index)) => *index as char,
+            None => '?',
+        },
+    }).collect()
+}
+
 pub fn render_rect (con: &mut Console,
     x: i32, y: i32, w: u32, h: u32,
     fore: Option<(char, (u8, u8, u8, u8))>, back: Option<(u8, u8, u8, u8)>,
@@ -133,4 +153,29 @@ pub fn render_button (con: &mut Console,
     con.rectangle(x + x_offs, y + y_offs, width, height, fore, back, Some('+' as u16));
     con.rectangle(x + x_offs, y + y_offs, width, height, Some(color.u8()), None, None);
     con.print_color(x + x_offs + (width as i32 / 2), y + y_offs + (height as i32 / 2), format!("#[{}]{}", color.text(), text).as_str(), doryen_rs::TextAlign::Center, None);
-}
\ No newline at end of file
+}
+// renders the values as a column chart of given height, one column per value from left to right. The columns are
+// scaled to the highest value, each row of the chart holding two steps with the full (219) and lower half (220) blocks
+pub fn render_sparkline (con: &mut Console, x: i32, y: i32, height: i32, values: &[f32], color: (u8, u8, u8, u8)) {
+    let max = values.iter().copied().fold(0.0, f32::max);
+    if max <= 0.0 { return }
+    for (i, value) in values.iter().enumerate() {
+        let steps = ((value / max) * (height * 2) as f32).round() as i32;
+        for row in 0..height {
+            let glyph = match steps - row * 2 { 1 => 220, filled if filled > 1 => 219, _ => continue };
+            con.ascii(x + i as i32, y + height - 1 - row, glyph);
+            con.fore(x + i as i32, y + height - 1 - row, color);
+        }
+    }
+}
+
+// renders a horizontal bar of given width filled by the share of value on max, with the full (219) and left half
+// (221) blocks over a light shade (176)
+pub fn render_bar (con: &mut Console, x: i32, y: i32, width: i32, value: f32, max: f32, color: (u8, u8, u8, u8)) {
+    let steps = if max > 0.0 { ((value / max).min(1.0) * (width * 2) as f32).round() as i32 } else { 0 };
+    for i in 0..width {
+        let (glyph, fore) = match steps - i * 2 { 1 => (221, color), filled if filled > 1 => (219, color), _ => (176, RTColor::DarkerGray.u8()) };
+        con.ascii(x + i, y, glyph);
+        con.fore(x + i, y, fore);
+    }
+}
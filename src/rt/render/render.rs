@@ -2,29 +2,45 @@ extern crate doryen_rs; use doryen_rs::Console;
 use crate::Align;
 
 use crate::data::tetromino::Tetromino;
-use crate::RTColor;
+use crate::{RTColor, Playfield, Theme};
 
 use crate::BLOCK_SCALE;
 use crate::CONSOLE_WIDTH;   use crate::CONSOLE_HEIGHT;
-use crate::PLAYFIELD_WIDTH; use crate::PLAYFIELD_HEIGHT;
-
-// render position of the playfield
-pub const R_PLAYFIELD_X: i32 = CONSOLE_WIDTH as i32 / 2 - (PLAYFIELD_WIDTH * BLOCK_SCALE) as i32 / 2 - 1;
-pub const R_PLAYFIELD_Y: i32 = CONSOLE_HEIGHT as i32 / 2 - (PLAYFIELD_HEIGHT * BLOCK_SCALE) as i32 / 2 - 1;
-
-// render sizes of the playfield
-pub const R_PLAYFIELD_SIZE_X: u32 = (PLAYFIELD_WIDTH * BLOCK_SCALE) as u32 + 2;
-pub const R_PLAYFIELD_SIZE_Y: u32 = (PLAYFIELD_HEIGHT * BLOCK_SCALE) as u32 + 2;
 
 pub const NEXT_CON_WIDTH : u32 = 6;
 pub const NEXT_CON_HEIGHT : u32 = 6;
 
+// render position of the playfield, centered on the console by it's number of columns / rows
+pub fn r_playfield_x (width: i8, scale: u8) -> i32 { CONSOLE_WIDTH as i32 / 2 - (width as i32 * scale as i32) / 2 - 1 }
+pub fn r_playfield_y (height: i8, scale: u8) -> i32 { CONSOLE_HEIGHT as i32 / 2 - (height as i32 * scale as i32) / 2 - 1 }
+
+// render sizes of the playfield, including it's borders
+pub fn r_playfield_size_x (width: i8, scale: u8) -> u32 { (width as i32 * scale as i32) as u32 + 2 }
+pub fn r_playfield_size_y (height: i8, scale: u8) -> u32 { (height as i32 * scale as i32) as u32 + 2 }
+
+// returns the biggest block scale up to BLOCK_SCALE that fits the playfield of given visible size, two rows
+// of the buffer zone above it, the score below it and the next Tetromino beside it on the console.
+// Versus games only get half of the console each
+pub fn fit_block_scale (width: i8, height: i8, versus: bool) -> u8 {
+    let available_x = if versus { CONSOLE_WIDTH as i32 / 2 - 1 } else { CONSOLE_WIDTH as i32 };
+    (1..=BLOCK_SCALE).rev().find(|scale| {
+        let next_width = NEXT_CON_WIDTH as i32 * *scale as i32;
+        let fits_x = if versus {
+            r_playfield_size_x(width, *scale) as i32 + next_width <= available_x
+        } else {
+            r_playfield_size_x(width, *scale) as i32 / 2 + next_width <= available_x / 2
+        };
+        fits_x && r_playfield_size_y(height, *scale) as i32 + 2 * (2 * *scale as i32 + 2) <= CONSOLE_HEIGHT as i32
+    }).unwrap_or(1)
+}
+
 pub fn clear (con: &mut Console) {
     con.clear(Some(RTColor::Black.u8()), Some(RTColor::Black.u8()), Some(' ' as u16));
 }
 
-// renders a playfield
-pub fn render_playfield<'a, const W: usize, const H: usize> (playfield_con: Option<&'a mut Console>, playfield: &[[Option<RTColor>; H]; W], scale: i32, render_blocks: bool) -> Option<&'a mut Console> {
+// renders the visible rows of a playfield. Rows being cleared flash white while dissolving from the center outwards,
+// given the rows and the progress of their animation from 0 to 1. Blocks are colored by the given theme
+pub fn render_playfield<'a> (playfield_con: Option<&'a mut Console>, playfield: &Playfield, scale: i32, render_blocks: bool, clearing: Option<(&[usize], f32)>, theme: Theme) -> Option<&'a mut Console> {
 
     match playfield_con {
         Some(pfcon) => {
@@ -35,28 +51,40 @@ pub fn render_playfield<'a, const W: usize, const H: usize> (playfield_con: Opti
             pfcon.rectangle(
                 0,
                 0,
-                W as u32 * BLOCK_SCALE as u32 + 2,
-                H as u32 * BLOCK_SCALE as u32 + 2,
+                r_playfield_size_x(playfield.width(), scale as u8),
+                r_playfield_size_y(playfield.visible_height(), scale as u8),
                 Some((128, 128, 128, 255)),
                 Some((0, 0, 0, 255)),
                 Some(' ' as u16),
             );
 
             if render_blocks {
-                for x in 0..playfield.len() {
-                    for y in 0..playfield[x].len() {
-                        match playfield[x][y] {
-                            Some(color) => render_block (
-                                pfcon,
-                                x as i32,
-                                y as i32,
-                                color.u8(), 
-                                scale,
-                                1, 1,
-                                Some(RTColor::Gray.u8())
-                            ),
+                let hidden = playfield.hidden_rows() as usize;
+                let half_width = playfield.width() as f32 / 2.0;
+                for x in 0..playfield.colors.len() {
+                    for y in hidden..playfield.colors[x].len() {
+                        let mut color = match playfield.colors[x][y] {
+                            Some(color) => theme.color(color),
                             None => continue
                         };
+
+                        // line clear animation
+                        if let Some((rows, progress)) = clearing {
+                            if rows.contains(&y) {
+                                if (x as f32 + 0.5 - half_width).abs() < progress * half_width { continue }
+                                if (progress * 8.0) as i32 % 2 == 0 { color = RTColor::White.u8() }
+                            }
+                        }
+
+                        render_block (
+                            pfcon,
+                            x as i32,
+                            (y - hidden) as i32,
+                            color,
+                            scale,
+                            1, 1,
+                            Some(RTColor::Gray.u8())
+                        );
                     }
                 }
             }
@@ -69,8 +97,8 @@ pub fn render_playfield<'a, const W: usize, const H: usize> (playfield_con: Opti
 
 }
 
-// renders a Tetromino
-pub fn render_tetromino<'a>(t_con: Option<&'a mut Console>, tetromino: &Option<Tetromino>, pos: (i8, i8), scale:i32, fore: Option<(u8, u8, u8, u8)>) -> Option<&'a mut Console> {
+// renders a Tetromino, colored by the given theme
+pub fn render_tetromino<'a>(t_con: Option<&'a mut Console>, tetromino: &Option<Tetromino>, pos: (i8, i8), scale:i32, fore: Option<(u8, u8, u8, u8)>, theme: Theme) -> Option<&'a mut Console> {
 
     // match console Some / None
     match t_con {
@@ -84,11 +112,11 @@ pub fn render_tetromino<'a>(t_con: Option<&'a mut Console>, tetromino: &Option<T
             match tetromino {
                 Some(t) => {
 
-                    // for each position on the Tetromino's grid
-                    for x in 0..t.grid.len() {
-                        for y in 0..t.grid[0].len() {
+                    // for each position on the Tetromino's bounding box
+                    for x in 0..t.size as i8 {
+                        for y in 0..t.size as i8 {
 
-                            let color = if t.grid[x][y] { t.color.u8() } else { RTColor::White.u8() };
+                            let color = if t.cells().contains(&(x, y)) { theme.color(t.color) } else { RTColor::White.u8() };
                             render_block(con, pos.0 as i32 + x as i32, pos.1 as i32 + y as i32, color, scale, 0, 0, fore);
                             
                         }
@@ -160,18 +188,49 @@ pub fn render_popup_window (
 
 }
 
-pub fn render_paused_popup (con: &mut Console, x: i32, y: i32, w: u32, h: u32, menu: &crate::PauseMenu) {
+pub fn render_paused_popup (con: &mut Console, x: i32, y: i32, w: u32, h: u32, menu: &crate::PauseMenu, controller: &crate::Controller) {
+    if menu.controls { return render_controls_popup(con, x, y, controller) }
+
     render_popup_window(con, x, y, w, h, Align::center2(), Some(RTColor::DarkerGray.u8()), Some(RTColor::Black.u8()), Some('/' as u16));
-    con.print(x, y-3, "Paused", doryen_rs::TextAlign::Center, Some(RTColor::White.u8()), None);
-    let (exit_fore, exit_back, cont_fore, cont_back) = match menu.cursor {
-        0 => (Some(RTColor::Red.u8()), Some(RTColor::White.u8()), Some(RTColor::DarkGray.u8()), None),
-        _ => (Some(RTColor::DarkGray.u8()), None, Some(RTColor::Blue.u8()), Some(RTColor::White.u8()))
-    };
-    con.print(x - 8, y, "Exit", doryen_rs::TextAlign::Center, exit_fore, exit_back);
-    con.print(x + 8, y, "Continue", doryen_rs::TextAlign::Center, cont_fore, cont_back);
+    let title = match &menu.paused_by { Some(player) => format!("Paused by {}", player), None => "Paused".to_string() };
+    con.print(x, y-3, crate::glyphs(&title).as_str(), doryen_rs::TextAlign::Center, Some(RTColor::White.u8()), None);
+
+    // the actions side by side, the one under the cursor highlighted
+    let actions = crate::PauseMenuAction::all();
+    for (i, action) in actions.iter().enumerate() {
+        let color = match action { crate::PauseMenuAction::Exit => RTColor::Red, _ => RTColor::Blue };
+        let (fore, back) = match menu.cursor == i {
+            true => (Some(color.u8()), Some(RTColor::White.u8())),
+            false => (Some(RTColor::DarkGray.u8()), None),
+        };
+        let offset = (i as i32 * 2 - actions.len() as i32 + 1) * 6;
+        con.print(x + offset, y, action.text(), doryen_rs::TextAlign::Center, fore, back);
+    }
+}
+
+// lists the key bindings of the controller, over the pause menu
+pub fn render_controls_popup (con: &mut Console, x: i32, y: i32, controller: &crate::Controller) {
+    let keys = controller.get_all();
+    render_popup_window(con, x, y, 40, keys.len() as u32 + 6, Align::center2(), Some(RTColor::DarkerGray.u8()), Some(RTColor::Black.u8()), Some(' ' as u16));
+    let top = y - keys.len() as i32 / 2 - 1;
+    con.print(x, top, "Controls", doryen_rs::TextAlign::Center, Some(RTColor::White.u8()), None);
+    for (i, key) in keys.iter().enumerate() {
+        con.print(x - 2, top + 2 + i as i32, crate::InputID::from_index(i).as_str(), doryen_rs::TextAlign::Right, Some(RTColor::Gray.u8()), None);
+        con.print(x + 2, top + 2 + i as i32, key, doryen_rs::TextAlign::Left, Some(RTColor::White.u8()), None);
+    }
+}
+
+// renders a popup announcing the unlocked achievement
+pub fn render_achievement_toast (con: &mut Console, x: i32, y: i32, achievement: crate::Achievement) {
+    let width = achievement.description().len().max(24) as u32 + 4;
+    render_popup_window(con, x, y, width, 5, Align::center2(), Some(RTColor::Yellow.u8()), Some(RTColor::Black.u8()), Some(' ' as u16));
+    con.print(x, y - 1, "Achievement unlocked", doryen_rs::TextAlign::Center, Some(RTColor::Yellow.u8()), None);
+    con.print(x, y, achievement.text(), doryen_rs::TextAlign::Center, Some(RTColor::White.u8()), None);
+    con.print(x, y + 1, achievement.description(), doryen_rs::TextAlign::Center, Some(RTColor::Gray.u8()), None);
 }
 
-pub fn render_game_over_popup (con: &mut Console, x: i32, y: i32, w: u32, h: u32) {
+pub fn render_game_over_popup (con: &mut Console, x: i32, y: i32, w: u32, h: u32, reason: &crate::GameOverReason) {
     render_popup_window(con, x, y, w, h, Align::center2(), Some(RTColor::Gray.u8()), Some(RTColor::Black.u8()), Some(' ' as u16));
-    con.print(x, y, "Game Over", doryen_rs::TextAlign::Center, Some(RTColor::White.u8()), None);
+    con.print(x, y - 1, "Game Over", doryen_rs::TextAlign::Center, Some(RTColor::White.u8()), None);
+    con.print(x, y, reason.text(), doryen_rs::TextAlign::Center, Some(RTColor::Gray.u8()), None);
 }
\ No newline at end of file
@@ -1,12 +1,10 @@
 use doryen_rs::{Console, TextAlign};
 
-use crate::{Game, RunState, render::*, RTColor};
+use crate::{Game, RunState, EntryDelay, GO_FRAMES, render::*, RTColor, Alpha};
 
 use crate::DEBUG_RENDER;
 
-use crate::BLOCK_SCALE;
 use crate::CONSOLE_WIDTH;   use crate::CONSOLE_HEIGHT;
-use crate::PLAYFIELD_WIDTH; use crate::PLAYFIELD_HEIGHT;
 
 pub trait RenderEngine {
     fn rt_render (&mut self, con: &mut Console);
@@ -16,26 +14,45 @@ impl RenderEngine for Game {
 
     fn rt_render (&mut self, con: &mut Console) {
 
-        let paused = match self.run_state { crate::RunState::Paused(_) => true, _=> false };
+        // the blocks are hidden while paused, and while the other side of versus is
+        let paused = self.frozen || matches!(self.run_state, crate::RunState::Paused(_));
 
         let side =  if self.player == 0 {0} else { ((self.player as i32 - 1) * 2) - 1 };
 
-        let player_x_offset = ((R_PLAYFIELD_SIZE_X as i32 / 2) + 1) * side;
+        // render position and size of this Game's playfield, scaled to fit the console
+        let r_playfield_x = r_playfield_x(self.playfield.width(), self.block_scale);
+        let r_playfield_y = r_playfield_y(self.playfield.visible_height(), self.block_scale);
+        let r_playfield_size_x = r_playfield_size_x(self.playfield.width(), self.block_scale);
+        let r_playfield_size_y = r_playfield_size_y(self.playfield.visible_height(), self.block_scale);
 
-        let block_scale = BLOCK_SCALE as i32;
-        let _half_pf_width = PLAYFIELD_WIDTH as i32 / 2;
-        let half_pf_height = PLAYFIELD_HEIGHT as i32 / 2;
+        let player_x_offset = ((r_playfield_size_x as i32 / 2) + 1) * side;
+
+        let block_scale = self.block_scale as i32;
+
+        // the score and the start prompt are rendered right below the playfield, leaving the space above it to the buffer zone
+        let score_y = r_playfield_y + r_playfield_size_y as i32 + 1;
+
+        // render position of the current Tetromino's bounding box, the hidden rows are above the playfield
+        let piece_x = r_playfield_x + player_x_offset + 1 + self.cur_pos.0 as i32 * block_scale;
+        let piece_y = |y: i8| r_playfield_y + 1 + (y - self.playfield.hidden_rows()) as i32 * block_scale;
 
         let half_con_width = CONSOLE_WIDTH as i32 / 2;
         let half_con_height = CONSOLE_HEIGHT as i32 / 2;
 
         // con.back( half_con_width + player_x_offset, 0, RTColor::Orange.u8());
 
-        match render_playfield(self.playfield_con.as_mut(), &self.playfield, BLOCK_SCALE as i32, !paused) {
+        // progress of the line clear animation, if any
+        let clearing = match &self.entry_delay {
+            Some(EntryDelay::LineClear(rows, frames)) => Some((rows.as_slice(), 1.0 - *frames as f32 / self.rules.line_clear_delay.max(1) as f32)),
+            _ => None,
+        };
+
+        let theme = self.preferences.theme;
+        match render_playfield(self.playfield_con.as_mut(), &self.playfield, block_scale, !paused, clearing, theme) {
             Some(pfcon) => {
                 pfcon.blit(
-                    R_PLAYFIELD_X + player_x_offset,
-                    R_PLAYFIELD_Y,
+                    r_playfield_x + player_x_offset,
+                    r_playfield_y,
                     con,
                     1.0,
                     1.0,
@@ -45,23 +62,33 @@ impl RenderEngine for Game {
             None => { println!("render playfield error") }
         }
 
-        // get a reference to the current position of the Tetromino
-        let cur_pos = (self.cur_pos.0 + player_x_offset as i8, self.cur_pos.1 );
-
         // render the score
-        render_score(con, half_con_width + player_x_offset, half_pf_height, self.score);
+        render_score(con, half_con_width + player_x_offset, score_y, self.score);
+
+        // the achievement unlocked last is shown above the playfield, over the popups
+        if let Some((achievement, _)) = self.toasts.first() {
+            render_achievement_toast(con, half_con_width + player_x_offset, 4, *achievement);
+        }
 
         if match &self.run_state {
-            RunState::Start => {
-                con.print(half_con_width, half_pf_height, "Press \"Down\", \"Up\' or \"Skip\" to start", TextAlign::Center, Some(RTColor::White.u8()), None);
+
+            // a frozen side of versus only shows it's playfield, the other side shows who paused
+            _ if self.frozen => true,
+            RunState::Countdown(frames) => {
+                let count = (*frames as u32).div_ceil(60);
+                con.print(half_con_width + player_x_offset, half_con_height, format!("{}", count).as_str(), TextAlign::Center, Some(RTColor::Yellow.u8()), None);
+                false
+            },
+            RunState::Playing if self.stats.frames < GO_FRAMES => {
+                con.print(half_con_width + player_x_offset, half_con_height, "Go!", TextAlign::Center, Some(RTColor::Green.u8()), None);
                 false
             },
             RunState::Paused(menu) => {
-                render_paused_popup(con, half_con_width, half_con_height, 32, 7, menu);
+                render_paused_popup(con, half_con_width, half_con_height, 64, 9, menu, &self.controller);
                 true
             },
-            RunState::Over => {
-                render_game_over_popup(con, half_con_width, half_con_height, 24, 7);
+            RunState::Over(reason) => {
+                render_game_over_popup(con, half_con_width, half_con_height, 36, 7, reason);
                 con.print(half_con_width, half_con_height + 1, format!("Scored {} points!", &self.score).as_str(), doryen_rs::TextAlign::Center, Some(RTColor::Red.u8()), None);
                 true
             }
@@ -70,31 +97,41 @@ impl RenderEngine for Game {
 
         let white = Some(RTColor::White.u8());
 
-        // render the current Tetromino
-        let s = self.get_skip_steps(&self.cur_tetromino.to_owned().unwrap());
+        // render the current Tetromino, if any outside of the entry delay
+        let s = match &self.cur_tetromino { Some(t) => self.get_skip_steps(t), None => 0 };
         let t_con = self.cur_con.as_mut();
 
-        match render_tetromino(t_con, &self.cur_tetromino, (0, 0), block_scale, white) {
-            Some(cur_con) => {
+        if let Some(cur_con) = render_tetromino(t_con, &self.cur_tetromino, (0, 0), block_scale, white, theme) {
+            cur_con.blit(
+                piece_x,
+                piece_y(self.cur_pos.1),
+                con, 
+                1.0,
+                1.0, 
+                if DEBUG_RENDER {None} else {white}
+            );
+
+            // ghost piece where the Tetromino would land, unless turned off on the preferences
+            if self.preferences.ghost {
                 cur_con.blit(
-                    R_PLAYFIELD_X - player_x_offset + (1 + cur_pos.0 as i32 * block_scale) ,
-                    half_con_height + (cur_pos.1 as i32 - half_pf_height) * block_scale,
-                    con, 
-                    1.0,
-                    1.0, 
-                    if DEBUG_RENDER {None} else {white}
-                );
-                cur_con.blit(
-                    R_PLAYFIELD_X - player_x_offset + (1 + cur_pos.0 as i32 * block_scale) ,
-                    half_con_height + ((cur_pos.1 + s) as i32 - half_pf_height) * block_scale,
+                    piece_x,
+                    piece_y(self.cur_pos.1 + s),
                     con, 
                     0.3,
                     0.3, 
                     if DEBUG_RENDER {None} else {white}
                 );
+            }
+        }
 
-            },
-            None => {}
+        // blocks of the current Tetromino still above the visible playfield are drawn dimmed
+        if let Some(t) = &self.cur_tetromino {
+            for (x, y) in t.cells().iter() {
+                if self.cur_pos.1 + y >= self.playfield.hidden_rows() { continue }
+                let block_y = piece_y(self.cur_pos.1 + y);
+                if block_y < 0 { continue }
+                render_block(con, 0, 0, theme.color(t.color).dim(3), block_scale, piece_x + *x as i32 * block_scale, block_y, Some(RTColor::White.u8().dim(3)));
+            }
         }
 
         match self.next_con.as_mut() {
@@ -114,22 +151,24 @@ impl RenderEngine for Game {
         }
 
         use crate::HasBag;
+        let r_half_pf_size_x = r_playfield_size_x as i32 / 2;
+        let next_x = half_con_width + player_x_offset + (r_half_pf_size_x * (1 - side.abs())) - (r_half_pf_size_x * -side) + ((NEXT_CON_WIDTH as i32 * block_scale) * side.min(0));
+        let next_y = r_playfield_y + r_playfield_size_y as i32 - (NEXT_CON_HEIGHT as i32 * block_scale);
         match self.bag_peek_next() {
             Some(next_tetromino) => { 
-                let nt = next_tetromino.get();
-                let nt_width = nt.grid.len();
-                let nt_heigth = nt.grid[0].len();
+                let nt = self.rotation_system.spawn(next_tetromino);
+                let nt_width = nt.size;
+                let nt_heigth = nt.size;
 
                 let nt_con = self.next_con.as_mut();
                 match render_tetromino(nt_con, &Some(nt), (
                     (NEXT_CON_WIDTH as i8 - nt_width  as i8) / 2, 
                     (NEXT_CON_WIDTH as i8 - nt_heigth as i8) - 1,
-                ), block_scale, white) {
+                ), block_scale, white, theme) {
                     Some(nt_con) => {
-                        const R_HALF_PF_SIZE_X_I32: i32 = R_PLAYFIELD_SIZE_X as i32 / 2;
                         nt_con.blit(
-                            half_con_width + player_x_offset + (R_HALF_PF_SIZE_X_I32 * (1 - side.abs())) - (R_HALF_PF_SIZE_X_I32 * -side) + ((NEXT_CON_WIDTH as i32 * 2) * side.min(0)),
-                            half_con_height + (R_PLAYFIELD_SIZE_Y as i32 / 2) - (NEXT_CON_HEIGHT as i32 * block_scale),
+                            next_x,
+                            next_y,
                             con, 
                             1.0,
                             1.0, 
@@ -142,6 +181,19 @@ impl RenderEngine for Game {
             None => { println!("render -- bag_peek_next returned None")}
         }
 
+        // the pieces after the next one are stacked above it's box, at the smallest scale
+        let box_x = next_x + (NEXT_CON_WIDTH as i32 * block_scale - NEXT_CON_WIDTH as i32) / 2;
+        for (i, id) in self.bag_peek(self.preferences.preview as usize).into_iter().enumerate().skip(1) {
+            let box_y = next_y - i as i32 * NEXT_CON_HEIGHT as i32;
+            if box_y < 0 { break }
+            con.rectangle(box_x, box_y, NEXT_CON_WIDTH, NEXT_CON_HEIGHT, Some((128, 128, 128, 255)), Some((80, 80, 80, 255)), Some(' ' as u16));
+            let t = self.rotation_system.spawn(id);
+            let (offs_x, offs_y) = ((NEXT_CON_WIDTH as i32 - t.size as i32) / 2, (NEXT_CON_HEIGHT as i32 - t.size as i32) - 1);
+            for (x, y) in t.cells().iter() {
+                render_block(con, offs_x + *x as i32, offs_y + *y as i32, theme.color(t.color), 1, box_x, box_y, white);
+            }
+        }
+
         // con.back(28, 10, (127, 127, 0, 127));
         // con.back(50, 10, (127, 127, 0, 127));
 
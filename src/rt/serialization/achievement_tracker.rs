@@ -0,0 +1,113 @@
+use crate::{write_records, read_records, RecordReader, DataFileError, Achievement};
+
+// path where the achievements of the profiles are saved, next to the profiles
+const PATH_ACHIEVEMENTS: &str = "profiles/achievements";
+
+// version of the layout of the achievement records
+const VERSION: u16 = 1;
+
+// the progress of a profile on an achievement
+#[derive(Debug, Clone, Copy)]
+pub struct AchievementProgress {
+    pub profile: u32,
+    pub achievement: Achievement,
+    pub progress: u32,
+
+    // seconds since the unix epoch when it was unlocked, 0 while it's locked
+    pub unlocked: u64,
+}
+
+// formats the given progress: profile id, achievement id, progress and unlock time
+fn to_bytes (progress: &AchievementProgress) -> Vec<u8> {
+    let mut bytes = progress.profile.to_be_bytes().to_vec();
+    bytes.push(progress.achievement.id());
+    bytes.extend(progress.progress.to_be_bytes());
+    bytes.extend(progress.unlocked.to_be_bytes());
+    bytes
+}
+
+// reads a progress record, skipping the ones of achievements this version doesn't know about
+fn from_bytes (record: &[u8]) -> Option<AchievementProgress> {
+    let mut reader = RecordReader::new(record);
+    Some(AchievementProgress {
+        profile: reader.u32()?,
+        achievement: Achievement::from_id(reader.u8()?)?,
+        progress: reader.u32().unwrap_or(0),
+        unlocked: reader.u64().unwrap_or(0),
+    })
+}
+
+// loads the progress of every profile
+fn load_all () -> Result<Vec<AchievementProgress>, std::io::Error> {
+    match read_records(PATH_ACHIEVEMENTS) {
+        Ok(file) => Ok(file.records.iter().filter_map(|record| from_bytes(record)).collect()),
+        Err(DataFileError::Missing) => Ok(vec![]),
+        Err(e) => Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}: {}", PATH_ACHIEVEMENTS, e)))
+    }
+}
+
+fn save_all (progress: &[AchievementProgress]) -> Result<(), std::io::Error> {
+    write_records(PATH_ACHIEVEMENTS, VERSION, &progress.iter().map(to_bytes).collect::<Vec<Vec<u8>>>())
+}
+
+/// returns the progress of the given profile on every achievement, in the order of Achievement::all()
+pub fn load_achievements (profile: u32) -> Vec<AchievementProgress> {
+    let saved = match load_all() {
+        Ok(saved) => saved,
+        Err(err) => { println!("achievement_tracker::load_achievements({}) -- Error: {}", profile, err); vec![] }
+    };
+    Achievement::all().iter().map(|achievement| match saved.iter().find(|p| p.profile == profile && p.achievement == *achievement) {
+        Some(progress) => *progress,
+        None => AchievementProgress { profile, achievement: *achievement, progress: 0, unlocked: 0 },
+    }).collect()
+}
+
+/// returns the achievements the given profile unlocked
+pub fn unlocked_achievements (profile: u32) -> Vec<Achievement> {
+    load_achievements(profile).iter().filter(|p| p.unlocked != 0).map(|p| p.achievement).collect()
+}
+
+// applies the change to the progress of the profile on the achievement, unlocking it once it reaches the goal.
+// Returns true if it was unlocked by this change. The guest doesn't keep achievements
+fn update (profile: u32, achievement: Achievement, change: impl Fn(u32) -> u32) -> bool {
+    if profile == 0 { return false }
+    let mut saved = match load_all() {
+        Ok(saved) => saved,
+        Err(err) => { println!("achievement_tracker::update({}, {}) -- Error: {}", profile, achievement.text(), err); return false }
+    };
+    let index = match saved.iter().position(|p| p.profile == profile && p.achievement == achievement) {
+        Some(index) => index,
+        None => { saved.push(AchievementProgress { profile, achievement, progress: 0, unlocked: 0 }); saved.len() - 1 }
+    };
+
+    let entry = &mut saved[index];
+    let previous = entry.progress;
+    entry.progress = change(entry.progress);
+    let unlocked = entry.unlocked == 0 && entry.progress >= achievement.goal();
+    if unlocked {
+        entry.unlocked = match std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) { Ok(time) => time.as_secs().max(1), Err(_) => 1 };
+    }
+    if entry.progress == previous && !unlocked { return false }
+
+    match save_all(&saved) {
+        Ok(_) => unlocked,
+        Err(err) => { println!("achievement_tracker::update({}, {}) -- Error: {}", profile, achievement.text(), err); false }
+    }
+}
+
+/// raises the progress of the profile on the achievement to the given value, if it's higher
+pub fn reach_progress (profile: u32, achievement: Achievement, progress: u32) -> bool {
+    update(profile, achievement, |current| current.max(progress))
+}
+
+/// adds to the progress of the profile on the achievement
+pub fn add_progress (profile: u32, achievement: Achievement, amount: u32) -> bool {
+    update(profile, achievement, |current| current.saturating_add(amount))
+}
+
+/// forgets the achievements of the given profile, called when it's deleted
+pub fn delete_achievements (profile: u32) -> Result<(), std::io::Error> {
+    let saved = load_all()?;
+    if !saved.iter().any(|p| p.profile == profile) { return Ok(()) }
+    save_all(&saved.into_iter().filter(|p| p.profile != profile).collect::<Vec<AchievementProgress>>())
+}
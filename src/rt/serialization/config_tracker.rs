@@ -1,122 +1,747 @@
-use crate::{ clear_binary, append_binary, load_binary };
-use crate::Controller;
+use std::{fmt, time::SystemTime};
+use serde::{Deserialize, de::DeserializeOwned};
+use toml_edit::{DocumentMut, Item, Table, TableLike, Value};
+use crate::{ read_records, write_atomic, data_path, migrate_legacy, push_str, delete_binary, RecordReader };
+use crate::{Controller, RotationSystemID, PieceSet, BoardSize, GameRules, Preferences, Theme, VALID_KEYS, PREVIEW_OPTIONS};
 
-const CONFIG_PATH: &str = "data/config";
-const CONTROLLERS: [&str; 3] = ["default", "versus1", "versus2"];
+const CONFIG_PATH: &str = "config";
+
+// the settings of the guest, the window and the rules of each game mode. Profiles override the
+// key bindings and preferences on their own file (see profile_path)
+const CONFIG_FILE: &str = "config/config.toml";
+
+// names of the key binding tables, one per controller
+const CONTROLLERS: [&str; 3] = ["singleplayer", "versus1", "versus2"];
+const GAME_MODES: [&str; 2] = ["singleplayer", "versus"];
+
+// names of the keys of a controller table, in the order of their InputID
+const INPUTS: [&str; 9] = ["pause", "left", "right", "down", "up", "rotate_left", "rotate_right", "skip", "retry"];
+
+// sizes a window cell can be scaled to, in pixels
+const MIN_SCALE: u32 = 4;
+const MAX_SCALE: u32 = 16;
 
 // toggle for runtime debbuging
 const DEBUG: bool = false;
 
-/// converts given index InputID and corresponding key str to a singgle array of bytes
-fn to_bytes (input_id: u8, key: String) -> Result<[u8; 16], std::io::Error> {
-    match crate::file_handler::to_bytes::<16>(key, true) {
-        Ok(mut bytes) => {
-            bytes[0] = input_id;
-            Ok(bytes)
-        },
-        Err(err) => Err(err)
+// everything that can go wrong while reading a config file, each pointing at the file
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(String, String),
+    Write(String, String),
+    Parse(String, String),
+    UnknownOption(String, String, String),
+    UnknownKey(String, String, String),
+    OutOfRange(String, String, u32, u32, u32),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, err) => write!(f, "{}: can't be read: {}", path, err),
+            ConfigError::Write(path, err) => write!(f, "{}: can't be written: {}", path, err),
+            ConfigError::Parse(path, err) => write!(f, "{}: {}", path, err),
+            ConfigError::UnknownOption(path, field, value) => write!(f, "{}: {} can't be \"{}\"", path, field, value),
+            ConfigError::UnknownKey(path, field, key) => write!(f, "{}: {} is bound to \"{}\", which is not a key", path, field, key),
+            ConfigError::OutOfRange(path, field, value, min, max) => write!(f, "{}: {} is {}, expected {} to {}", path, field, value, min, max),
+        }
     }
 }
 
-/// saves the given controller
-pub fn save_controllers (controllers: &mut [Controller; 3]) -> Result<(), std::io::Error> {
+impl ConfigError {
 
-    // clear binary while catching error
-    for controller in CONTROLLERS {
-        if let Err(err) = clear_binary(format!("{}/{}", CONFIG_PATH, controller).as_str()) { return Err(err) }
+    // true if the settings can't be saved to the file until it's fixed: only a file that was read and parsed is edited
+    pub fn blocks_saving (&self) -> bool {
+        matches!(self, ConfigError::Io(..) | ConfigError::Parse(..))
     }
+}
 
-    // loop through the controllers
-    for i in 0..controllers.len() {
+// the window the game opens, read on launch
+#[derive(Debug, Clone, Copy)]
+pub struct WindowSettings {
+    pub fullscreen: bool,
+    pub vsync: bool,
+    pub resizable: bool,
 
-        // get the keys of this controller
-        let keys = controllers[i].get_all();
+    // pixels per cell of the console
+    pub scale: u32,
+}
 
-        // loop through the keys
-        for j in 0..keys.len() {
+impl Default for WindowSettings {
+    fn default () -> Self {
+        Self { fullscreen: false, vsync: true, resizable: true, scale: 8 }
+    }
+}
 
-            // convert to bytes and match result
-            match to_bytes(j as u8, keys[j].to_string()) {
+// every setting, as resolved from the config files of a profile
+pub struct Config {
+    pub window: WindowSettings,
+    pub controllers: [Controller; 3],
+    pub rules: [GameRules; 2],
+    pub preferences: Preferences,
+}
 
-                // Ok: append to the file and catch if error
-                Ok(bytes) => {
-                    if let Err(err) = append_binary(
-                        format!("{}/{}", CONFIG_PATH, CONTROLLERS[i])
-                        .as_str(), bytes) { return Err(err) }
-                },
-                // Error: return the error
-                Err(err) => return Err(err)
-            }
+impl Default for Config {
+    fn default () -> Self {
+        Self {
+            window: WindowSettings::default(),
+            controllers: [Controller::default(), Controller::default_versus1(), Controller::default_versus2()],
+            rules: [GameRules::default(); 2],
+            preferences: Preferences::default(),
+        }
+    }
+}
+
+// the layout of the config file. Every field is optional, missing ones keep their default
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    window: WindowFile,
+    keys: KeysFile,
+    gameplay: GameplayFile,
+    render: RenderFile,
+}
+
+// the layout of the file of a profile, which only holds what profiles can override
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ProfileFile {
+    keys: KeysFile,
+    gameplay: HandlingFile,
+    render: RenderFile,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct WindowFile {
+    fullscreen: Option<bool>,
+    vsync: Option<bool>,
+    resizable: Option<bool>,
+    scale: Option<u32>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct KeysFile {
+    singleplayer: KeyFile,
+    versus1: KeyFile,
+    versus2: KeyFile,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct KeyFile {
+    pause: Option<String>,
+    left: Option<String>,
+    right: Option<String>,
+    down: Option<String>,
+    up: Option<String>,
+    rotate_left: Option<String>,
+    rotate_right: Option<String>,
+    skip: Option<String>,
+    retry: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct GameplayFile {
+    das: Option<u32>,
+    arr: Option<u32>,
+    auto_pause: Option<bool>,
+    singleplayer: RulesFile,
+    versus: RulesFile,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct HandlingFile {
+    das: Option<u32>,
+    arr: Option<u32>,
+    auto_pause: Option<bool>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct RulesFile {
+    rotation: Option<String>,
+    pieces: Option<String>,
+    board: Option<String>,
+    line_clear_delay: Option<u32>,
+    entry_delay: Option<u32>,
+    initial_rotation: Option<bool>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct RenderFile {
+    ghost: Option<bool>,
+    preview: Option<u32>,
+    theme: Option<String>,
+}
+
+// path of the file holding the key bindings and preferences of a profile
+fn profile_path (profile: u32) -> String {
+    format!("{}/profile{}.toml", CONFIG_PATH, profile)
+}
+
+// reads and parses the config file at path, a missing file holding no settings
+fn read_file<T: DeserializeOwned + Default> (path: &str) -> Result<T, ConfigError> {
+    let path = data_path(path);
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(T::default()),
+        Err(err) => return Err(ConfigError::Io(path.to_string(), err.to_string())),
+    };
+    match toml::from_str(&text) {
+        Ok(file) => Ok(file),
+        Err(err) => {
+            // keep the message on a single line, pointing at the line of the error
+            let line = err.span().map(|span| text[..span.start].matches('\n').count() + 1).unwrap_or(1);
+            Err(ConfigError::Parse(path.to_string(), format!("line {}: {}", line, err.message().trim())))
         }
     }
-    
-    // successfull operation
-    Ok(())
+}
 
+// applies the values of a config file over the given settings. Invalid values are skipped and reported
+struct Resolver<'a> {
+    path: &'a str,
+    errors: Vec<ConfigError>,
 }
 
-/// returns the saved controller configuration
-pub fn get_controllers () -> Result<[Controller; 3], std::io::Error> {
+impl Resolver<'_> {
 
-    let mut controllers = vec![];
+    // returns the option named by value, matched case insensitively to the text of each option
+    fn option<T: Copy> (&mut self, field: String, value: &Option<String>, all: &[T], text: fn(&T) -> &'static str) -> Option<T> {
+        let value = value.as_ref()?;
+        match all.iter().find(|option| text(option).eq_ignore_ascii_case(value.trim())) {
+            Some(option) => Some(*option),
+            None => { self.errors.push(ConfigError::UnknownOption(self.path.to_string(), field, value.to_string())); None }
+        }
+    }
 
-    // loop through the loaded buffer with increments of 128 u8 -- controller
-    for i in 0..3 {
+    // returns the number if it's inside the given range
+    fn number (&mut self, field: String, value: Option<u32>, min: u32, max: u32) -> Option<u8> {
+        let value = value?;
+        if value < min || value > max {
+            self.errors.push(ConfigError::OutOfRange(self.path.to_string(), field, value, min, max));
+            return None
+        }
+        Some(value as u8)
+    }
 
-        match get_controller(i) {
-            Ok(controller) => controllers.push(controller),
-            Err(err) => return Err(err)
+    fn keys (&mut self, keys: &KeysFile, controllers: &mut [Controller; 3]) {
+        for (i, file) in [&keys.singleplayer, &keys.versus1, &keys.versus2].iter().enumerate() {
+            let values = [&file.pause, &file.left, &file.right, &file.down, &file.up, &file.rotate_left, &file.rotate_right, &file.skip, &file.retry];
+            for (j, value) in values.iter().enumerate() {
+                let key = match value { Some(key) => key, None => continue };
+                match VALID_KEYS.contains(&key.as_str()) {
+                    true => controllers[i].set_at(j, key),
+                    false => self.errors.push(ConfigError::UnknownKey(self.path.to_string(), format!("keys.{}.{}", CONTROLLERS[i], INPUTS[j]), key.to_string())),
+                }
+            }
         }
     }
 
-    // return the result
-    Ok(controllers.try_into().unwrap_or_else(|_| panic!("config_tracker -- Error converting Controller vec to array")))
+    fn handling (&mut self, das: Option<u32>, arr: Option<u32>, auto_pause: Option<bool>, preferences: &mut Preferences) {
+        if let Some(das) = self.number("gameplay.das".to_string(), das, 1, 60) { preferences.das = das }
+        if let Some(arr) = self.number("gameplay.arr".to_string(), arr, 0, 60) { preferences.arr = arr }
+        if let Some(auto_pause) = auto_pause { preferences.auto_pause = auto_pause }
+    }
+
+    fn render (&mut self, render: &RenderFile, preferences: &mut Preferences) {
+        if let Some(ghost) = render.ghost { preferences.ghost = ghost }
+        let max = PREVIEW_OPTIONS[PREVIEW_OPTIONS.len() - 1] as u32;
+        if let Some(preview) = self.number("render.preview".to_string(), render.preview, 1, max) { preferences.preview = preview }
+        if let Some(theme) = self.option("render.theme".to_string(), &render.theme, &Theme::all(), Theme::text) { preferences.theme = theme }
+    }
 
+    fn rules (&mut self, game_mode: usize, file: &RulesFile, rules: &mut GameRules) {
+        let field = |name: &str| format!("gameplay.{}.{}", GAME_MODES[game_mode], name);
+        if let Some(rotation) = self.option(field("rotation"), &file.rotation, &RotationSystemID::all(), RotationSystemID::text) { rules.rotation_system = rotation }
+        if let Some(pieces) = self.option(field("pieces"), &file.pieces, &PieceSet::all(), PieceSet::text) { rules.piece_set = pieces }
+        if let Some(board) = self.option(field("board"), &file.board, &BoardSize::all(), BoardSize::text) { rules.board_size = board }
+        if let Some(delay) = self.number(field("line_clear_delay"), file.line_clear_delay, 0, 120) { rules.line_clear_delay = delay }
+        if let Some(delay) = self.number(field("entry_delay"), file.entry_delay, 0, 120) { rules.entry_delay = delay }
+        if let Some(irs) = file.initial_rotation { rules.initial_rotation = irs }
+    }
 }
 
-/// returns the configured controller for the given category.
-/// 0: singleplayer;    1: versus1;     2: versus2;
-pub fn get_controller (player: usize) -> Result<Controller, std::io::Error> {
+// applies the config file over the given settings, returning the errors found on it
+fn resolve_config (config: &mut Config) -> Vec<ConfigError> {
+    let file = match read_file::<ConfigFile>(CONFIG_FILE) { Ok(file) => file, Err(err) => return vec![err] };
+    let path = data_path(CONFIG_FILE);
+    let mut resolver = Resolver { path: &path, errors: vec![] };
+    let window = &file.window;
+    if let Some(fullscreen) = window.fullscreen { config.window.fullscreen = fullscreen }
+    if let Some(vsync) = window.vsync { config.window.vsync = vsync }
+    if let Some(resizable) = window.resizable { config.window.resizable = resizable }
+    if let Some(scale) = resolver.number("window.scale".to_string(), window.scale, MIN_SCALE, MAX_SCALE) { config.window.scale = scale as u32 }
+    resolver.keys(&file.keys, &mut config.controllers);
+    resolver.handling(file.gameplay.das, file.gameplay.arr, file.gameplay.auto_pause, &mut config.preferences);
+    resolver.rules(0, &file.gameplay.singleplayer, &mut config.rules[0]);
+    resolver.rules(1, &file.gameplay.versus, &mut config.rules[1]);
+    resolver.render(&file.render, &mut config.preferences);
+    resolver.errors
+}
+
+// applies the file of the given profile over the given settings, returning the errors found on it
+fn resolve_profile (profile: u32, config: &mut Config) -> Vec<ConfigError> {
+    let path = profile_path(profile);
+    let file = match read_file::<ProfileFile>(&path) { Ok(file) => file, Err(err) => return vec![err] };
+    let path = data_path(&path);
+    let mut resolver = Resolver { path: &path, errors: vec![] };
+    resolver.keys(&file.keys, &mut config.controllers);
+    resolver.handling(file.gameplay.das, file.gameplay.arr, file.gameplay.auto_pause, &mut config.preferences);
+    resolver.render(&file.render, &mut config.preferences);
+    resolver.errors
+}
 
+/// resolves the settings of the given profile, or of the guest (profile 0).
+/// Values that can't be read keep the ones of the guest, or their defaults
+pub fn load_config (profile: u32) -> Config {
+    let mut config = Config::default();
+    let mut errors = resolve_config(&mut config);
+    if profile != 0 { errors.extend(resolve_profile(profile, &mut config)) }
+    if DEBUG { for err in &errors { println!("config_tracker::load_config({}) -- {}", profile, err) } }
+    config
+}
+
+/// returns the errors on the config file and on the files of the given profiles
+pub fn config_errors (profiles: &[u32]) -> Vec<ConfigError> {
+    let mut config = Config::default();
+    let mut errors = resolve_config(&mut config);
+    for profile in profiles.iter().filter(|profile| **profile != 0) {
+        errors.extend(resolve_profile(*profile, &mut config));
+    }
+    errors
+}
+
+/// returns the time each config file read by the given profiles was last modified, to tell when they change
+pub fn config_stamp (profiles: &[u32]) -> Vec<Option<SystemTime>> {
+    let modified = |path: &str| std::fs::metadata(data_path(path)).and_then(|metadata| metadata.modified()).ok();
+    let mut stamp = vec![modified(CONFIG_FILE)];
+    stamp.extend(profiles.iter().filter(|profile| **profile != 0).map(|profile| modified(&profile_path(*profile))));
+    stamp
+}
+
+/// returns the window settings, read on launch
+pub fn get_window () -> WindowSettings {
+    load_config(0).window
+}
+
+/// returns the configured controllers of the given profile, or of the guest (profile 0)
+pub fn get_controllers (profile: u32) -> [Controller; 3] {
+    load_config(profile).controllers
+}
+
+/// returns the configured controller of the given profile for the given category.
+/// Keys a profile doesn't bind are the guest's (profile 0)
+/// 0: singleplayer;    1: versus1;     2: versus2;
+pub fn get_controller (profile: u32, player: usize) -> Controller {
     assert!(player <= 2, "config_tracker::get_controller({}) -- Error: expected one of (0, 1, 2) player values but got {} instead!", player, player);
+    let [singleplayer, versus1, versus2] = get_controllers(profile);
+    match player { 0 => singleplayer, 1 => versus1, _ => versus2 }
+}
+
+/// returns the preferences of the given profile, or of the guest (profile 0)
+pub fn get_preferences (profile: u32) -> Preferences {
+    load_config(profile).preferences
+}
+
+/// returns the rules chosen for the given game mode.
+/// 0: singleplayer;    1: versus;
+pub fn get_rules (game_mode: usize) -> GameRules {
+    load_config(0).rules[game_mode]
+}
+
+// formats the key binding tables of the given controllers
+fn keys_text (controllers: &[Controller; 3]) -> String {
+    let mut text = String::new();
+    for (i, controller) in controllers.iter().enumerate() {
+        text += &format!("\n[keys.{}]\n", CONTROLLERS[i]);
+        for (j, key) in controller.get_all().iter().enumerate() {
+            text += &format!("{} = {:?}\n", INPUTS[j], key);
+        }
+    }
+    text
+}
+
+// formats the preferences shared by the config file and the files of the profiles
+fn handling_text (preferences: &Preferences) -> String {
+    format!("\
+# frames a direction is held before the piece starts shifting (DAS), and frames between each shift (ARR)
+das = {}
+arr = {}
+# pauses the run when the window stops being updated, as it's minimized or hidden on most platforms.
+# Switching to another window that leaves it visible isn't noticed
+auto_pause = {}
+", preferences.das, preferences.arr, preferences.auto_pause)
+}
+
+fn render_text (preferences: &Preferences) -> String {
+    let themes: Vec<&str> = Theme::all().iter().map(Theme::text).collect();
+    format!("
+[render]
+# shows where the piece would land
+ghost = {}
+# number of upcoming pieces shown, 1 to {}
+preview = {}
+# one of {}
+theme = {:?}
+", preferences.ghost, PREVIEW_OPTIONS[PREVIEW_OPTIONS.len() - 1], preferences.preview, themes.join(", "), preferences.theme.text())
+}
+
+fn rules_text (game_mode: usize, rules: &GameRules) -> String {
+    format!("
+[gameplay.{}]
+rotation = {:?}
+pieces = {:?}
+board = {:?}
+line_clear_delay = {}
+entry_delay = {}
+initial_rotation = {}
+", GAME_MODES[game_mode], rules.rotation_system.text(), rules.piece_set.text(), rules.board_size.text(), rules.line_clear_delay, rules.entry_delay, rules.initial_rotation)
+}
 
-    // loads the binary and match result
-    match load_binary(format!("{}/{}", CONFIG_PATH, CONTROLLERS[player]).as_str()) {
+// formats the whole config file, with comments describing each setting
+fn config_text (config: &Config) -> String {
+    let window = &config.window;
+    let list = |texts: Vec<&str>| texts.join(", ");
+    format!("\
+# Rusty Tetris settings. Changes made here while the game runs are applied right away,
+# except for the window, read on launch, and the rules of a run that already started.
+# Profiles override the keys, [gameplay] das / arr and [render] on profile<id>.toml, next to this file.
 
-        // file is loaded successfully
-        Ok(buffer) => {
+[window]
+fullscreen = {}
+vsync = {}
+resizable = {}
+# pixels per cell of the 80x80 console, {} to {}
+scale = {}
 
-            if DEBUG { println!("config_tracker::get_controller() -- Buffer read successfull for {}", CONTROLLERS[player]); }
+# key names as in the Settings screen: KeyA, Digit1, ArrowLeft, Space, Enter, F1...
+{}
+[gameplay]
+{}
+# rules of each game mode
+# rotation:         one of {}
+# pieces:           one of {}
+# board:            one of {}
+# line_clear_delay: frames cleared rows flash before collapsing
+# entry_delay:      frames between a piece locking and the next one spawning (ARE)
+# initial_rotation: holding a rotate key during the delays rotates the next piece (IRS)
+{}{}{}",
+        window.fullscreen, window.vsync, window.resizable, MIN_SCALE, MAX_SCALE, window.scale,
+        keys_text(&config.controllers).trim_start(),
+        handling_text(&config.preferences),
+        list(RotationSystemID::all().iter().map(RotationSystemID::text).collect()),
+        list(PieceSet::all().iter().map(PieceSet::text).collect()),
+        list(BoardSize::all().iter().map(BoardSize::text).collect()),
+        rules_text(0, &config.rules[0]).trim_start(), rules_text(1, &config.rules[1]),
+        render_text(&config.preferences),
+    )
+}
 
-            // create a vec of tuple for player, gamemode and score
-            let mut list = vec![]; 
+// formats the file of a profile
+fn profile_text (controllers: &[Controller; 3], preferences: &Preferences) -> String {
+    format!("\
+# Settings of a profile, overriding the ones of config.toml, next to this file.
+# Changes made here while the game runs are applied right away.
+{}
+[gameplay]
+{}{}",
+        keys_text(controllers),
+        handling_text(preferences),
+        render_text(preferences),
+    )
+}
 
-            // loop through the keys
-            for j in 0..buffer.len() / 16 {
+// the parsed text of a config file, where the settings are set in place to keep the comments and layout of the rest
+struct ConfigEdit {
+    doc: DocumentMut,
+    changed: bool,
+}
 
-                // add the following tuple to the list
-                list.push(
-                    match String::from_utf8(buffer[(j * 16) + 1 .. (j * 16) + 16].to_vec()) {
-                        Ok(key) => key.trim_matches(char::from(0)).to_string(),
-                        Err(_) => "!!invalid key!!".to_string()
-                    }
-                )
+impl ConfigEdit {
+
+    // sets the key of the table at path to the new value if it differs from the old one, creating the tables if missing
+    fn set<T: PartialEq + Into<Value>> (&mut self, path: &[&str], key: &str, old: T, new: T) {
+        if old == new { return }
+        let mut table: &mut dyn TableLike = self.doc.as_table_mut();
+        for name in path {
+            if !table.contains_key(name) {
+                let mut new_table = Table::new();
+                new_table.set_implicit(true);
+                table.insert(name, Item::Table(new_table));
             }
+            table = match table.get_mut(name).and_then(Item::as_table_like_mut) { Some(table) => table, None => return };
+        }
 
-            Ok(match Controller::from_vec(list) {
-                Some(controller) => {
-                    if DEBUG { println!("config_tracker::get_controller() -- Controller loaded successfully: {:?}", controller); }
-                    controller
-                },
-                None => {
-                    let controller = [Controller::default, Controller::default_versus1, Controller::default_versus2][player]();
-                    if DEBUG { println!("config_tracker::get_controller() -- No controller found, loading default: {:?}", controller); }
-                    controller
-                }
-            })
-        },
+        // the comment after the old value stays
+        let mut value: Value = new.into();
+        match table.get_mut(key).and_then(Item::as_value_mut) {
+            Some(old) => { *value.decor_mut() = old.decor().clone(); *old = value; },
+            None => { table.insert(key, Item::Value(value)); },
+        }
+        self.changed = true;
+    }
+
+    fn keys (&mut self, old: &[Controller; 3], new: &[Controller; 3]) {
+        for i in 0..CONTROLLERS.len() {
+            for (j, (old, new)) in old[i].get_all().iter().zip(new[i].get_all()).enumerate() {
+                self.set(&["keys", CONTROLLERS[i]], INPUTS[j], *old, new);
+            }
+        }
+    }
+
+    fn handling (&mut self, old: &Preferences, new: &Preferences) {
+        self.set(&["gameplay"], "das", old.das as i64, new.das as i64);
+        self.set(&["gameplay"], "arr", old.arr as i64, new.arr as i64);
+        self.set(&["gameplay"], "auto_pause", old.auto_pause, new.auto_pause);
+    }
+
+    fn render (&mut self, old: &Preferences, new: &Preferences) {
+        self.set(&["render"], "ghost", old.ghost, new.ghost);
+        self.set(&["render"], "preview", old.preview as i64, new.preview as i64);
+        self.set(&["render"], "theme", old.theme.text(), new.theme.text());
+    }
+
+    fn rules (&mut self, game_mode: usize, old: &GameRules, new: &GameRules) {
+        let path = ["gameplay", GAME_MODES[game_mode]];
+        self.set(&path, "rotation", old.rotation_system.text(), new.rotation_system.text());
+        self.set(&path, "pieces", old.piece_set.text(), new.piece_set.text());
+        self.set(&path, "board", old.board_size.text(), new.board_size.text());
+        self.set(&path, "line_clear_delay", old.line_clear_delay as i64, new.line_clear_delay as i64);
+        self.set(&path, "entry_delay", old.entry_delay as i64, new.entry_delay as i64);
+        self.set(&path, "initial_rotation", old.initial_rotation, new.initial_rotation);
+    }
+}
+
+// edits the config file at path in place, starting from the given text if it's missing, and writes it only if a value changed.
+// A file that can't be read or parsed is left for the player to fix, as writing over it would lose it
+fn edit_file<T: DeserializeOwned + Default> (path: &str, text: impl FnOnce() -> String, apply: impl FnOnce(&mut ConfigEdit)) -> Result<(), ConfigError> {
+    read_file::<T>(path)?;
+    let full_path = data_path(path);
+    let source = match std::fs::read_to_string(&full_path) {
+        Ok(source) => source,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => text(),
+        Err(err) => return Err(ConfigError::Io(full_path, err.to_string())),
+    };
+    let doc = match source.parse::<DocumentMut>() {
+        Ok(doc) => doc,
+        Err(err) => return Err(ConfigError::Parse(full_path, err.message().trim().to_string())),
+    };
+    let mut edit = ConfigEdit { doc, changed: false };
+    apply(&mut edit);
+    if !edit.changed { return Ok(()) }
+    write_atomic(path, edit.doc.to_string().as_bytes()).map_err(|err| ConfigError::Write(full_path, err.to_string()))
+}
+
+/// saves the settings chosen on the Settings screen: the rules go to the config file, the controllers and
+/// preferences to the file of the given profile, or to the config file for the guest (profile 0).
+/// Only the values that changed are written, the rest of the files is kept as is
+pub fn save_settings (profile: u32, controllers: &[Controller; 3], rules: &[GameRules; 2], preferences: &Preferences) -> Result<(), ConfigError> {
+    let guest = load_config(0);
+    edit_file::<ConfigFile>(CONFIG_FILE, || config_text(&guest), |edit| {
+        for (game_mode, new) in rules.iter().enumerate() { edit.rules(game_mode, &guest.rules[game_mode], new) }
+        if profile == 0 {
+            edit.keys(&guest.controllers, controllers);
+            edit.handling(&guest.preferences, preferences);
+            edit.render(&guest.preferences, preferences);
+        }
+    })?;
+    if profile == 0 { return Ok(()) }
+
+    // the values of a profile start as the ones of the guest
+    let current = load_config(profile);
+    edit_file::<ProfileFile>(&profile_path(profile), || profile_text(&current.controllers, &current.preferences), |edit| {
+        edit.keys(&current.controllers, controllers);
+        edit.handling(&current.preferences, preferences);
+        edit.render(&current.preferences, preferences);
+    })
+}
+
+/// deletes the preferences and controllers of a deleted profile
+pub fn delete_profile_config (profile: u32) -> Result<(), std::io::Error> {
+    match std::fs::remove_file(data_path(&profile_path(profile))) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err),
+        _ => Ok(())
+    }
+}
+
+// binary files the settings were stored on before the config file, converted to it on launch
+const BINARY_CONTROLLERS: [&str; 3] = ["default", "versus1", "versus2"];
+const BINARY_OPTIONS: [&str; 4] = ["rotation", "pieces", "board", "delays"];
+const BINARY_PREFERENCES: &str = "preferences";
+const BINARY_VERSION: u16 = 1;
+
+// reads a binary controller file: one record per key, the index of the InputID followed by the key
+fn binary_controller (records: &[Vec<u8>], player: usize) -> Controller {
+    let mut controller = [Controller::default, Controller::default_versus1, Controller::default_versus2][player]();
+    for record in records {
+        let mut reader = RecordReader::new(record);
+        if let (Some(index), Some(key)) = (reader.u8(), reader.str()) {
+            if (index as usize) < INPUTS.len() && VALID_KEYS.contains(&key.as_str()) { controller.set_at(index as usize, &key) }
+        }
+    }
+    controller
+}
+
+// reads a binary preferences record: das, arr, ghost, preview and theme
+fn binary_preferences (record: Option<&Vec<u8>>) -> Preferences {
+    let mut preferences = Preferences::default();
+    if let Some(record) = record {
+        let mut reader = RecordReader::new(record);
+        if let Some(das) = reader.u8() { preferences.das = das }
+        if let Some(arr) = reader.u8() { preferences.arr = arr }
+        if let Some(ghost) = reader.u8() { preferences.ghost = ghost != 0 }
+        if let Some(preview) = reader.u8() { preferences.preview = preview }
+        if let Some(theme) = reader.u8() { preferences.theme = Theme::from_id(theme) }
+    }
+    preferences
+}
+
+// returns the records of the binary file of given name on the config folder
+fn binary_records (name: &str) -> Vec<Vec<u8>> {
+    match read_records(format!("{}/{}", CONFIG_PATH, name).as_str()) {
+        Ok(file) => file.records,
+        Err(_) => vec![],
+    }
+}
+
+// converts the binary settings to the config files, deleting the binary files. The guest's are read from a file each,
+// a profile's from it's own file: the preferences on the first record, then one record per key prefixed by it's controller
+fn migrate_binary () {
+    let mut config = Config::default();
+    for (i, name) in BINARY_CONTROLLERS.iter().enumerate() {
+        config.controllers[i] = binary_controller(&binary_records(name), i);
+    }
+    config.preferences = binary_preferences(binary_records(BINARY_PREFERENCES).first());
+    let options = BINARY_OPTIONS.map(binary_records);
+    for (game_mode, rules) in config.rules.iter_mut().enumerate() {
+        let option = |records: &[Vec<u8>]| records.get(game_mode).and_then(|record| RecordReader::new(record).u8());
+        if let Some(id) = option(&options[0]) { rules.rotation_system = RotationSystemID::from_id(id) }
+        if let Some(id) = option(&options[1]) { rules.piece_set = PieceSet::from_id(id) }
+        if let Some(id) = option(&options[2]) { rules.board_size = BoardSize::from_id(id) }
+        if let Some(record) = options[3].get(game_mode) {
+            let mut reader = RecordReader::new(record);
+            if let Some(delay) = reader.u8() { rules.line_clear_delay = delay }
+            if let Some(delay) = reader.u8() { rules.entry_delay = delay }
+            if let Some(irs) = reader.u8() { rules.initial_rotation = irs != 0 }
+        }
+    }
+    if let Err(err) = write_atomic(CONFIG_FILE, config_text(&config).as_bytes()) { println!("config_tracker::migrate_config() -- Error writing {}: {}", CONFIG_FILE, err); return }
+
+    // profiles
+    let names: Vec<String> = match std::fs::read_dir(data_path(CONFIG_PATH)) {
+        Ok(entries) => entries.filter_map(|entry| entry.ok()?.file_name().into_string().ok()).collect(),
+        Err(_) => vec![],
+    };
+    for name in names.iter().filter_map(|name| name.strip_suffix(".bin")) {
+        let profile = match name.strip_prefix("profile").and_then(|id| id.parse::<u32>().ok()) { Some(profile) => profile, None => continue };
+        let records = binary_records(name);
+        let mut controllers = config.controllers.clone();
+        for (i, controller) in controllers.iter_mut().enumerate() {
+            let keys: Vec<Vec<u8>> = records.iter().skip(1).filter(|record| record.first() == Some(&(i as u8))).map(|record| record[1..].to_vec()).collect();
+            if !keys.is_empty() { *controller = binary_controller(&keys, i) }
+        }
+        match write_atomic(&profile_path(profile), profile_text(&controllers, &binary_preferences(records.first())).as_bytes()) {
+            Ok(_) => { let _ = delete_binary(&format!("{}/{}", CONFIG_PATH, name)); },
+            Err(err) => println!("config_tracker::migrate_config() -- Error writing {}: {}", profile_path(profile), err),
+        }
+    }
+
+    for name in BINARY_CONTROLLERS.iter().chain(BINARY_OPTIONS.iter()).chain([BINARY_PREFERENCES].iter()) {
+        let _ = delete_binary(&format!("{}/{}", CONFIG_PATH, name));
+    }
+}
+
+// converts the config files from older versions. Controllers from before the versioned format took 16 bytes per key
+// (the index of the InputID followed by the key aligned to the right), and the per game mode options a fixed number
+// of bytes each. Versioned binary files are then converted to the config file, which is written with the defaults if missing
+pub fn migrate_config () {
+    fn convert_controller (buffer: &[u8]) -> Vec<Vec<u8>> {
+        buffer.chunks_exact(16).map(|bytes| {
+            let mut record = vec![bytes[0]];
+            match String::from_utf8(bytes[1..].to_vec()) {
+                Ok(key) => push_str(&mut record, key.trim_matches(char::from(0))),
+                Err(_) => push_str(&mut record, "!!invalid key!!"),
+            }
+            record
+        }).collect()
+    }
+    fn convert_option (buffer: &[u8]) -> Vec<Vec<u8>> { buffer.chunks(1).map(|bytes| bytes.to_vec()).collect() }
+    fn convert_delays (buffer: &[u8]) -> Vec<Vec<u8>> { buffer.chunks_exact(3).map(|bytes| bytes.to_vec()).collect() }
+
+    let path = |name: &str| format!("{}/{}", CONFIG_PATH, name);
+    for controller in BINARY_CONTROLLERS {
+        migrate_legacy(path(controller).as_str(), BINARY_VERSION, convert_controller);
+    }
+    migrate_legacy(path("rotation").as_str(), BINARY_VERSION, convert_option);
+    migrate_legacy(path("pieces").as_str(), BINARY_VERSION, convert_option);
+    migrate_legacy(path("board").as_str(), BINARY_VERSION, convert_option);
+    migrate_legacy(path("delays").as_str(), BINARY_VERSION, convert_delays);
+
+    if !std::path::Path::new(&data_path(CONFIG_FILE)).exists() { migrate_binary() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read (path: &str) -> String {
+        std::fs::read_to_string(data_path(path)).expect("read")
+    }
+
+    fn write (path: &str, text: &str) {
+        std::fs::write(data_path(path), text).expect("write")
+    }
+
+    #[test]
+    fn settings_are_edited_in_place () {
+        let _dir = crate::init_test_data_dir();
+        write(CONFIG_FILE, "# my settings\n[gameplay]\ndas = 12 # fast\n\n[window]\nscale = 10\n");
+        let guest = load_config(0);
+
+        // nothing changed, nothing is written
+        save_settings(0, &guest.controllers, &guest.rules, &guest.preferences).expect("save");
+        assert_eq!(read(CONFIG_FILE), "# my settings\n[gameplay]\ndas = 12 # fast\n\n[window]\nscale = 10\n");
+
+        let mut preferences = guest.preferences;
+        preferences.das = 9;
+        preferences.ghost = false;
+        let mut rules = guest.rules;
+        rules[1].board_size = BoardSize::Tall;
+        save_settings(0, &guest.controllers, &rules, &preferences).expect("save");
+        let text = read(CONFIG_FILE);
+        assert!(text.starts_with("# my settings\n[gameplay]\ndas = 9 # fast\n") && text.contains("\n[window]\nscale = 10\n"), "{}", text);
+        assert!(!text.contains("arr") && !text.contains("[keys"), "{}", text);
+
+        let config = load_config(0);
+        assert_eq!(config_errors(&[]).len(), 0);
+        assert_eq!((config.preferences.das, config.preferences.ghost, config.window.scale), (9, false, 10));
+        assert_eq!(config.rules[1].board_size, BoardSize::Tall);
+    }
+
+    #[test]
+    fn profile_settings_keep_the_guest_file () {
+        let _dir = crate::init_test_data_dir();
+        write(CONFIG_FILE, "[gameplay]\narr = 3\n");
+        let current = load_config(7);
+        let mut preferences = current.preferences;
+        preferences.arr = 1;
+        save_settings(7, &current.controllers, &current.rules, &preferences).expect("save");
+        assert_eq!(read(CONFIG_FILE), "[gameplay]\narr = 3\n");
+        assert_eq!((get_preferences(0).arr, get_preferences(7).arr), (3, 1));
+    }
 
-        // error loading binary file
-        Err(e) => return Err(e)
+    #[test]
+    fn broken_files_are_not_overwritten () {
+        let _dir = crate::init_test_data_dir();
+        write(CONFIG_FILE, "[gameplay\ndas = 12\n");
+        let mut config = load_config(0);
+        config.preferences.das = 9;
+        assert!(save_settings(0, &config.controllers, &config.rules, &config.preferences).is_err());
+        assert_eq!(read(CONFIG_FILE), "[gameplay\ndas = 12\n");
     }
 }
@@ -0,0 +1,132 @@
+use std::{path::{Path, PathBuf}, sync::OnceLock};
+
+// name of the folder holding the data inside the platform's data directory
+const APP_FOLDER: &str = "rusty_tetris";
+
+// environment variable overriding the data directory, the --data-dir argument overrides both
+pub const DATA_DIR_ENV: &str = "RUSTY_TETRIS_DATA";
+const DATA_DIR_ARG: &str = "--data-dir";
+
+// folders of the data directory
+const FOLDERS: [&str; 5] = ["config", "profiles", "rulesets", "saves", "scores"];
+
+// folder the data was kept on before the data directory, relative to the working directory
+const LEGACY_DIR: &str = "data";
+
+// the custom ruleset shipped with the game, written to the data directory when it has none
+const DEFAULT_RULESET: &str = include_str!("../../../data/rulesets/custom.toml");
+
+// the data directory, resolved once on launch
+static DATA_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+// returns the data directory given by the arguments (--data-dir <path> or --data-dir=<path>), the environment or the platform:
+// $XDG_DATA_HOME or ~/.local/share on Linux and BSD, ~/Library/Application Support on macOS and %APPDATA% on Windows
+fn resolve_data_dir (args: &[String]) -> PathBuf {
+    let argument = args.iter().enumerate().find_map(|(i, arg)| match arg.strip_prefix(DATA_DIR_ARG) {
+        Some("") => args.get(i + 1).cloned(),
+        Some(value) => value.strip_prefix('=').map(str::to_string),
+        None => None,
+    });
+    if let Some(path) = argument { return PathBuf::from(path) }
+
+    let env = |key: &str| std::env::var_os(key).filter(|value| !value.is_empty()).map(PathBuf::from);
+    if let Some(path) = env(DATA_DIR_ENV) { return path }
+
+    let platform = if cfg!(windows) {
+        env("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        env("HOME").map(|home| home.join("Library").join("Application Support"))
+    } else {
+        env("XDG_DATA_HOME").filter(|path| path.is_absolute()).or_else(|| env("HOME").map(|home| home.join(".local").join("share")))
+    };
+    match platform {
+        Some(path) => path.join(APP_FOLDER),
+
+        // no home to put it in, keep it next to the working directory as before
+        None => PathBuf::from(LEGACY_DIR),
+    }
+}
+
+/// resolves the data directory from the command line arguments, creating it and it's folders on first run.
+/// Called once on launch, before anything reads or writes data
+pub fn init_data_dir (args: &[String]) -> Result<(), std::io::Error> {
+    let dir = resolve_data_dir(args);
+    let first_run = !dir.exists();
+    create_folders(&dir)?;
+    println!("data directory: {}", dir.display());
+
+    // data kept on the working directory by older versions is copied over the first time
+    let legacy = Path::new(LEGACY_DIR);
+    if first_run && legacy.is_dir() && !same_dir(legacy, &dir) {
+        for folder in FOLDERS {
+            let entries = match std::fs::read_dir(legacy.join(folder)) { Ok(entries) => entries, Err(_) => continue };
+            for entry in entries.flatten() {
+                let name = entry.file_name();
+                if name.to_string_lossy().starts_with('.') || !entry.path().is_file() { continue }
+                match std::fs::copy(entry.path(), dir.join(folder).join(&name)) {
+                    Ok(_) => println!("copied {} to the data directory", entry.path().display()),
+                    Err(err) => println!("data_dir::init_data_dir() -- Error copying {}: {}", entry.path().display(), err),
+                }
+            }
+        }
+    }
+
+    let ruleset = dir.join(crate::RULESET_PATH);
+    if !ruleset.exists() { std::fs::write(ruleset, DEFAULT_RULESET)? }
+
+    let _ = DATA_DIR.set(dir);
+    Ok(())
+}
+
+// creates the data directory and it's folders if missing
+fn create_folders (dir: &Path) -> Result<(), std::io::Error> {
+    for folder in FOLDERS { std::fs::create_dir_all(dir.join(folder))? }
+    Ok(())
+}
+
+// true if both paths lead to the same folder
+fn same_dir (a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// returns the path of the given file of the data directory
+pub fn data_path (file: &str) -> String {
+    #[cfg(test)]
+    if let Some(dir) = TEST_DATA_DIR.with(|dir| dir.borrow().clone()) { return dir.join(file).to_string_lossy().to_string() }
+
+    match DATA_DIR.get() {
+        Some(dir) => dir.join(file).to_string_lossy().to_string(),
+        None => Path::new(LEGACY_DIR).join(file).to_string_lossy().to_string(),
+    }
+}
+
+// the data directory of the test running on the thread, overriding the one of the game
+#[cfg(test)]
+thread_local! { static TEST_DATA_DIR: std::cell::RefCell<Option<PathBuf>> = const { std::cell::RefCell::new(None) } }
+
+// an empty data directory for a single test, removed when it's dropped
+#[cfg(test)]
+pub struct TestDataDir(PathBuf);
+
+#[cfg(test)]
+impl Drop for TestDataDir {
+    fn drop (&mut self) {
+        TEST_DATA_DIR.with(|dir| *dir.borrow_mut() = None);
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+// points the data directory of the calling test to a new temporary folder, without copying the legacy data over
+#[cfg(test)]
+pub fn init_test_data_dir () -> TestDataDir {
+    static COUNT: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+    let count = COUNT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    let dir = std::env::temp_dir().join(format!("rusty_tetris_tests_{}_{}", std::process::id(), count));
+    let _ = std::fs::remove_dir_all(&dir);
+    create_folders(&dir).expect("test data directory");
+    TEST_DATA_DIR.with(|test_dir| *test_dir.borrow_mut() = Some(dir.clone()));
+    TestDataDir(dir)
+}
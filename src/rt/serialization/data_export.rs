@@ -0,0 +1,442 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use crate::{write_atomic_at, data_path, get_profiles, save_profiles, next_profile_id, valid_name, load_all_scores, merge_scores, format_date, Profile, ScoreRecord, MAX_NAME_LENGTH};
+
+// folders of the data directory used by the Export and Import buttons, the commands take any folder
+const EXPORT_FOLDER: &str = "exports";
+const IMPORT_FOLDER: &str = "imports";
+
+// names of the exported files: everything on a single JSON file, and one CSV file per list for spreadsheets
+const JSON_FILE: &str = "rusty_tetris.json";
+const PROFILES_CSV: &str = "profiles.csv";
+const HISTORY_CSV: &str = "history.csv";
+const BEST_CSV: &str = "best.csv";
+
+// version of the layout of the exported files
+const VERSION: u16 = 1;
+
+// a profile as exported
+#[derive(Serialize, Deserialize)]
+struct ProfileEntry {
+    id: u32,
+    name: String,
+}
+
+// a score record as exported. The player's name, date and pieces per second are there for reading, imports ignore
+// them unless the player isn't on the exported profiles
+#[derive(Clone, Serialize, Deserialize)]
+struct ScoreEntry {
+    player: u32,
+    #[serde(default)]
+    player_name: String,
+    game_mode: String,
+    score: i32,
+    #[serde(default)]
+    date: String,
+    #[serde(default)]
+    timestamp: u64,
+    #[serde(default)]
+    duration: u32,
+    #[serde(default)]
+    lines: u32,
+    #[serde(default)]
+    level: u32,
+    #[serde(default)]
+    pieces: u32,
+    #[serde(default)]
+    pps: f32,
+    #[serde(default)]
+    max_combo: u32,
+    #[serde(default)]
+    singles: u32,
+    #[serde(default)]
+    doubles: u32,
+    #[serde(default)]
+    triples: u32,
+    #[serde(default)]
+    tetrises: u32,
+    #[serde(default)]
+    pentrises: u32,
+    #[serde(default)]
+    attack: u32,
+
+    // hexadecimal, as shown on the Scores screen. Spreadsheets would round a 64 bit number
+    #[serde(default)]
+    seed: String,
+}
+
+// the JSON file
+#[derive(Serialize, Deserialize)]
+struct ExportFile {
+    version: u16,
+    profiles: Vec<ProfileEntry>,
+    history: Vec<ScoreEntry>,
+    best: Vec<ScoreEntry>,
+}
+
+// names of the game modes on the exported files, by id
+const GAME_MODES: [&str; 2] = ["singleplayer", "versus"];
+
+// what an import added
+pub struct ImportSummary {
+    pub profiles_added: usize,
+    pub profiles_merged: usize,
+
+    // new profiles whose name was too short, named after their imported id instead
+    pub profiles_renamed: usize,
+    pub history_added: usize,
+    pub best_added: usize,
+}
+
+impl std::fmt::Display for ImportSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} new profiles ({} renamed for an invalid name), {} merged by name, {} scores added to the history and {} to the best scores",
+            self.profiles_added, self.profiles_renamed, self.profiles_merged, self.history_added, self.best_added)
+    }
+}
+
+// an error reading or writing one of the exported files
+fn invalid (path: &Path, err: impl std::fmt::Display) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}: {}", path.display(), err))
+}
+
+fn to_entry (record: &ScoreRecord, profiles: &[Profile]) -> ScoreEntry {
+    ScoreEntry {
+        player: record.player,
+        player_name: match record.player { 0 => "Guest".to_string(), player => profiles.iter().find(|p| p.id == player).map(|p| p.name.to_string()).unwrap_or_default() },
+        game_mode: GAME_MODES.get(record.game_mode as usize).unwrap_or(&"unknown").to_string(),
+        score: record.score,
+        date: format_date(record.timestamp),
+        timestamp: record.timestamp,
+        duration: record.duration,
+        lines: record.lines,
+        level: record.level,
+        pieces: record.pieces,
+        pps: (record.pps() * 100.0).round() / 100.0,
+        max_combo: record.max_combo,
+        singles: record.clears[0],
+        doubles: record.clears[1],
+        triples: record.clears[2],
+        tetrises: record.clears[3],
+        pentrises: record.clears[4],
+        attack: record.attack,
+        seed: format!("{:016x}", record.seed),
+    }
+}
+
+// reads an exported score, None if the game mode or seed isn't valid
+fn from_entry (entry: &ScoreEntry) -> Option<ScoreRecord> {
+    Some(ScoreRecord {
+        player: entry.player,
+        game_mode: GAME_MODES.iter().position(|mode| mode.eq_ignore_ascii_case(entry.game_mode.trim()))? as u8,
+        score: entry.score,
+        timestamp: entry.timestamp,
+        duration: entry.duration,
+        lines: entry.lines,
+        level: entry.level,
+        pieces: entry.pieces,
+        max_combo: entry.max_combo,
+        seed: match entry.seed.trim() { "" => 0, seed => u64::from_str_radix(seed, 16).ok()? },
+        clears: [entry.singles, entry.doubles, entry.triples, entry.tetrises, entry.pentrises],
+        attack: entry.attack,
+    })
+}
+
+/// folder the Export button writes to
+pub fn export_folder () -> PathBuf {
+    PathBuf::from(data_path(EXPORT_FOLDER))
+}
+
+/// folder the Import button reads from
+pub fn import_folder () -> PathBuf {
+    PathBuf::from(data_path(IMPORT_FOLDER))
+}
+
+/// writes the profiles, history and best scores to the given folder, as JSON and as CSV
+pub fn export_data (folder: &Path) -> Result<(), std::io::Error> {
+    let profiles = get_profiles()?;
+    let (history, best) = load_all_scores()?;
+
+    let file = ExportFile {
+        version: VERSION,
+        profiles: profiles.iter().map(|p| ProfileEntry { id: p.id, name: p.name.to_string() }).collect(),
+        history: history.iter().map(|s| to_entry(s, &profiles)).collect(),
+        best: best.iter().map(|s| to_entry(s, &profiles)).collect(),
+    };
+
+    let path = folder.join(JSON_FILE);
+    let json = serde_json::to_string_pretty(&file).map_err(|err| invalid(&path, err))?;
+    write_atomic_at(&path, json.as_bytes())?;
+
+    write_csv(&folder.join(PROFILES_CSV), &file.profiles)?;
+    write_csv(&folder.join(HISTORY_CSV), &file.history)?;
+    write_csv(&folder.join(BEST_CSV), &file.best)
+}
+
+fn write_csv<T: Serialize> (path: &Path, rows: &[T]) -> Result<(), std::io::Error> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for row in rows { writer.serialize(row).map_err(|err| invalid(path, err))? }
+    let data = writer.into_inner().map_err(|err| invalid(path, err))?;
+    write_atomic_at(path, &data)
+}
+
+fn read_csv<T: serde::de::DeserializeOwned> (path: &Path) -> Result<Vec<T>, std::io::Error> {
+    let mut reader = csv::Reader::from_path(path).map_err(|err| invalid(path, err))?;
+    reader.deserialize().collect::<Result<Vec<T>, csv::Error>>().map_err(|err| invalid(path, err))
+}
+
+// reads the CSV file of given name on the folder, if there is one
+fn read_list<T: serde::de::DeserializeOwned> (folder: &Path, file: &str) -> Result<Vec<T>, std::io::Error> {
+    match folder.join(file).is_file() {
+        true => read_csv(&folder.join(file)),
+        false => Ok(vec![]),
+    }
+}
+
+// reads the exported files at given path: a JSON file, a CSV file of scores, or a folder holding either.
+// A folder's JSON file is preferred over it's CSV files
+fn read_export (path: &Path) -> Result<ExportFile, std::io::Error> {
+    let read_json = |path: &Path| -> Result<ExportFile, std::io::Error> {
+        let file: ExportFile = serde_json::from_str(&std::fs::read_to_string(path)?).map_err(|err| invalid(path, err))?;
+        if file.version > VERSION { return Err(invalid(path, format!("exported by a newer version ({})", file.version))) }
+        Ok(file)
+    };
+
+    if path.is_file() {
+        return match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => read_json(path),
+
+            // a lone list of profiles or scores, best scores are recalculated from the scores
+            Some(ext) if ext.eq_ignore_ascii_case("csv") && path.file_name().is_some_and(|name| name == PROFILES_CSV) => {
+                Ok(ExportFile { version: VERSION, profiles: read_csv(path)?, history: vec![], best: vec![] })
+            },
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => {
+                let scores: Vec<ScoreEntry> = read_csv(path)?;
+                Ok(ExportFile { version: VERSION, profiles: vec![], history: scores.clone(), best: scores })
+            },
+            _ => Err(invalid(path, "expected a .json or .csv file")),
+        }
+    }
+
+    if path.join(JSON_FILE).is_file() { return read_json(&path.join(JSON_FILE)) }
+
+    // missing CSV files are empty lists
+    if ![PROFILES_CSV, HISTORY_CSV, BEST_CSV].iter().any(|file| path.join(file).is_file()) {
+        return Err(invalid(path, format!("no {} or CSV files found", JSON_FILE)))
+    }
+    Ok(ExportFile { version: VERSION, profiles: read_list(path, PROFILES_CSV)?, history: read_list(path, HISTORY_CSV)?, best: read_list(path, BEST_CSV)? })
+}
+
+// maps the imported ids to local ones, adding the profiles missing from the given ones. Names are compared ignoring case,
+// so imported profiles named alike end up as the same local one
+fn map_profiles (names: Vec<(u32, String)>, profiles: &mut Vec<Profile>, summary: &mut ImportSummary) -> Vec<(u32, u32)> {
+    let mut ids: Vec<(u32, u32)> = vec![(0, 0)];
+    let mut placeholders: Vec<u32> = vec![];
+    for (imported, name) in names {
+        if imported == 0 || ids.iter().any(|(id, _)| *id == imported) { continue }
+
+        // names a profile couldn't be created with get one after their imported id, and are never merged with others
+        let (name, renamed) = match valid_name(&name) {
+            Some(name) => (name, false),
+            None => (format!("Player {}", imported).chars().take(MAX_NAME_LENGTH).collect(), true),
+        };
+        let merged = match renamed {
+            true => None,
+            false => profiles.iter().find(|p| !placeholders.contains(&p.id) && p.name.to_lowercase() == name.to_lowercase()),
+        };
+        let local = match merged {
+            Some(profile) => { summary.profiles_merged += 1; profile.id },
+            None => {
+                let id = next_profile_id(profiles);
+                if renamed { summary.profiles_renamed += 1; placeholders.push(id) }
+                profiles.push(Profile { id, name });
+                summary.profiles_added += 1;
+                id
+            }
+        };
+        ids.push((imported, local));
+    }
+    ids
+}
+
+/// merges the exported files at given path (see read_export) into the profiles and scores.
+/// Imported profiles named like a local one are taken as the same player, the others are added with new ids
+pub fn import_data (path: &Path) -> Result<ImportSummary, std::io::Error> {
+    let file = read_export(path)?;
+    let mut profiles = get_profiles()?;
+    let mut summary = ImportSummary { profiles_added: 0, profiles_merged: 0, profiles_renamed: 0, history_added: 0, best_added: 0 };
+
+    // the name of each imported player: from the exported profiles, or the name on the score of players missing there
+    let mut names: Vec<(u32, String)> = file.profiles.iter().map(|p| (p.id, p.name.to_string())).collect();
+    for entry in file.history.iter().chain(file.best.iter()) {
+        if entry.player != 0 && !entry.player_name.is_empty() && !names.iter().any(|(id, _)| *id == entry.player) {
+            names.push((entry.player, entry.player_name.to_string()));
+        }
+    }
+
+    let ids = map_profiles(names, &mut profiles, &mut summary);
+    if summary.profiles_added > 0 { save_profiles(&profiles)? }
+
+    // scores of players without a profile or name were played as guest
+    let records = |entries: &[ScoreEntry]| -> Vec<ScoreRecord> {
+        entries.iter().enumerate().filter_map(|(i, entry)| match from_entry(entry) {
+            Some(mut record) => {
+                record.player = ids.iter().find(|(id, _)| *id == record.player).map(|(_, local)| *local).unwrap_or(0);
+                Some(record)
+            },
+            None => { println!("data_export::import_data({}) -- Error: score {} has an invalid game mode or seed, skipped", path.display(), i); None }
+        }).collect()
+    };
+    let (history_added, best_added) = merge_scores(&records(&file.history), &records(&file.best))?;
+    summary.history_added = history_added;
+    summary.best_added = best_added;
+    Ok(summary)
+}
+
+/// runs the `export [folder]` and `import <path>` commands given on the command line, returning None when there's
+/// no command so the game starts. Arguments starting with "--" (and the value of --data-dir) aren't commands
+pub fn run_command (args: &[String]) -> Option<i32> {
+    let mut positional = vec![];
+    let mut skip = false;
+    for arg in args.iter().skip(1) {
+        if skip { skip = false; continue }
+        if arg == "--data-dir" { skip = true; continue }
+        if !arg.starts_with("--") { positional.push(arg.as_str()) }
+    }
+
+    match positional.as_slice() {
+        [] => None,
+        ["export"] | ["export", _] => {
+            let folder = positional.get(1).map(PathBuf::from).unwrap_or_else(export_folder);
+            match export_data(&folder) {
+                Ok(()) => { println!("exported the profiles and scores to {}", folder.display()); Some(0) },
+                Err(err) => { println!("export failed: {}", err); Some(1) },
+            }
+        },
+        ["import"] | ["import", _] => {
+            let path = positional.get(1).map(PathBuf::from).unwrap_or_else(import_folder);
+            match import_data(&path) {
+                Ok(summary) => { println!("imported {}: {}", path.display(), summary); Some(0) },
+                Err(err) => { println!("import failed: {}", err); Some(1) },
+            }
+        },
+        _ => {
+            println!("usage: rusty_tetris [--data-dir <path>] [export [folder] | import [file or folder]]");
+            Some(2)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record (player: u32) -> ScoreRecord {
+        ScoreRecord {
+            player, game_mode: 1, score: 1234, timestamp: 1_700_000_000, duration: 3600, lines: 12, level: 2, pieces: 30,
+            max_combo: 3, seed: 0xDEAD_BEEF_0123_4567, clears: [1, 2, 3, 4, 5], attack: 9,
+        }
+    }
+
+    fn export_file () -> ExportFile {
+        let profiles = vec![Profile { id: 1, name: "Alice".to_string() }];
+        ExportFile {
+            version: VERSION,
+            profiles: profiles.iter().map(|p| ProfileEntry { id: p.id, name: p.name.to_string() }).collect(),
+            history: vec![to_entry(&record(1), &profiles), to_entry(&record(0), &profiles)],
+            best: vec![to_entry(&record(1), &profiles)],
+        }
+    }
+
+    // the fields of a record, to compare them
+    fn fields (record: &ScoreRecord) -> String {
+        format!("{:?}", record)
+    }
+
+    #[test]
+    fn entries_round_trip () {
+        let entry = to_entry(&record(1), &[Profile { id: 1, name: "Alice".to_string() }]);
+        assert_eq!(entry.player_name, "Alice");
+        assert_eq!(entry.seed, "deadbeef01234567");
+        assert_eq!(fields(&from_entry(&entry).expect("record")), fields(&record(1)));
+
+        let mut invalid = entry.clone();
+        invalid.game_mode = "marathon".to_string();
+        assert!(from_entry(&invalid).is_none());
+    }
+
+    #[test]
+    fn json_round_trip () {
+        let _dir = crate::init_test_data_dir();
+        let folder = PathBuf::from(data_path("export"));
+        let file = export_file();
+        let json = serde_json::to_string_pretty(&file).expect("json");
+        write_atomic_at(&folder.join(JSON_FILE), json.as_bytes()).expect("write");
+
+        let read = read_export(&folder).expect("read");
+        assert_eq!(read.profiles.len(), 1);
+        assert_eq!(read.profiles[0].name, "Alice");
+        let records: Vec<ScoreRecord> = read.history.iter().filter_map(from_entry).collect();
+        assert_eq!(records.iter().map(fields).collect::<Vec<_>>(), vec![fields(&record(1)), fields(&record(0))]);
+        assert_eq!(read.best.len(), 1);
+    }
+
+    #[test]
+    fn csv_round_trip () {
+        let _dir = crate::init_test_data_dir();
+        let folder = PathBuf::from(data_path("export"));
+        let file = export_file();
+        write_csv(&folder.join(PROFILES_CSV), &file.profiles).expect("profiles");
+        write_csv(&folder.join(HISTORY_CSV), &file.history).expect("history");
+
+        // the missing best scores are an empty list
+        let read = read_export(&folder).expect("read");
+        assert_eq!(read.profiles[0].id, 1);
+        assert_eq!(read.history.iter().filter_map(from_entry).map(|r| fields(&r)).collect::<Vec<_>>(), vec![fields(&record(1)), fields(&record(0))]);
+        assert!(read.best.is_empty());
+
+        // a lone CSV of scores is both the history and the best scores
+        let read = read_export(&folder.join(HISTORY_CSV)).expect("read");
+        assert_eq!((read.history.len(), read.best.len()), (2, 2));
+    }
+
+    #[test]
+    fn unreadable_scores_abort_the_export () {
+        let _dir = crate::init_test_data_dir();
+        let folder = PathBuf::from(data_path("export"));
+
+        // missing files are exported as no scores
+        export_data(&folder).expect("export");
+        assert!(read_export(&folder).expect("read").history.is_empty());
+
+        std::fs::remove_dir_all(&folder).expect("clear");
+        crate::write_bytes("scores/history", b"RTDC\0").expect("write");
+        assert!(export_data(&folder).is_err());
+        assert!(!folder.join(JSON_FILE).exists());
+    }
+
+    #[test]
+    fn profiles_are_mapped_by_name () {
+        let _dir = crate::init_test_data_dir();
+        let mut profiles = vec![Profile { id: 4, name: "Alice".to_string() }];
+        let mut summary = ImportSummary { profiles_added: 0, profiles_merged: 0, profiles_renamed: 0, history_added: 0, best_added: 0 };
+        let names = vec![
+            (1, " alice ".to_string()),
+            (2, "Bob".to_string()),
+            (3, "BOB".to_string()),
+            (2, "Robert".to_string()),
+            (0, "Guest".to_string()),
+            (6, "".to_string()),
+            (7, "   ".to_string()),
+            (8, " ab\t".to_string()),
+            (9, "Player 8".to_string()),
+        ];
+        let ids = map_profiles(names, &mut profiles, &mut summary);
+
+        // both Bobs are the new profile, the second name of the same id is ignored. Invalid names stay apart
+        assert_eq!(ids, vec![(0, 0), (1, 4), (2, 5), (3, 5), (6, 6), (7, 7), (8, 8), (9, 9)]);
+        assert_eq!((summary.profiles_added, summary.profiles_merged, summary.profiles_renamed), (5, 2, 3));
+        assert_eq!(profiles.iter().map(|p| (p.id, p.name.as_str())).collect::<Vec<_>>(),
+            vec![(4, "Alice"), (5, "Bob"), (6, "Player 6"), (7, "Player 7"), (8, "Player 8"), (9, "Player 8")]);
+    }
+}
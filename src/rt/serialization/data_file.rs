@@ -0,0 +1,295 @@
+use std::fmt;
+use crate::{write_bytes, load_binary, binary_exists, migrate_scores, migrate_profiles, migrate_config};
+
+// every data file starts with this header: magic, version of the file's layout, length of the payload and it's checksum
+pub const MAGIC: [u8; 4] = *b"RTDC";
+const HEADER_SIZE: usize = 14;
+
+// the payload is a list of records, each prefixed by it's length as a u16 and followed by it's own checksum,
+// so a damaged file only loses the damaged records. A damaged payload is scanned for the records that are still intact:
+// the damaged file is kept next to it as <name>.corrupt.bin and replaced by the recovered records.
+// Readers ignore the bytes after the fields they know about, while the version tells which layout the records have
+pub struct DataFile {
+    pub version: u16,
+    pub records: Vec<Vec<u8>>,
+}
+
+// everything that can go wrong while reading a data file
+#[derive(Debug)]
+pub enum DataFileError {
+    Io(std::io::Error),
+    Missing,
+
+    // the file doesn't start with the magic, it was written before the versioned format. Holds it's raw bytes
+    Legacy(Vec<u8>),
+
+    Truncated,
+}
+
+impl fmt::Display for DataFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DataFileError::Io(err) => write!(f, "{}", err),
+            DataFileError::Missing => write!(f, "file not found"),
+            DataFileError::Legacy(_) => write!(f, "file has no header, it needs to be migrated"),
+            DataFileError::Truncated => write!(f, "file is shorter than it's header"),
+        }
+    }
+}
+
+// CRC-32 (IEEE) of the given bytes
+fn checksum (bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// writes the given records to the data file at path, replacing previous content.
+/// Records longer than u16::MAX bytes can't be written, the file is left as it was
+pub fn write_records (path: &str, version: u16, records: &[Vec<u8>]) -> Result<(), std::io::Error> {
+
+    if let Some(record) = records.iter().find(|record| record.len() > u16::MAX as usize) {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("{}: a record of {} bytes is longer than the maximum of {}", path, record.len(), u16::MAX)))
+    }
+
+    // concatenate the records with their lengths and checksums
+    let mut payload = vec![];
+    for record in records {
+        payload.extend((record.len() as u16).to_be_bytes());
+        payload.extend(record);
+        payload.extend(checksum(record).to_be_bytes());
+    }
+
+    let mut bytes = Vec::with_capacity(HEADER_SIZE + payload.len());
+    bytes.extend(MAGIC);
+    bytes.extend(version.to_be_bytes());
+    bytes.extend((payload.len() as u32).to_be_bytes());
+    bytes.extend(checksum(&payload).to_be_bytes());
+    bytes.extend(payload);
+
+    write_bytes(path, &bytes)
+}
+
+/// reads the records of the data file at path
+pub fn read_records (path: &str) -> Result<DataFile, DataFileError> {
+
+    if !binary_exists(path) { return Err(DataFileError::Missing) }
+    let bytes = match load_binary(path) {
+        Ok(bytes) => bytes,
+        Err(err) => return Err(DataFileError::Io(err)),
+    };
+
+    if bytes.get(0..4) != Some(&MAGIC[..]) { return Err(DataFileError::Legacy(bytes)) }
+    if bytes.len() < HEADER_SIZE { return Err(DataFileError::Truncated) }
+
+    let version = u16::from_be_bytes([bytes[4], bytes[5]]);
+    let length = u32::from_be_bytes([bytes[6], bytes[7], bytes[8], bytes[9]]) as usize;
+    let sum = u32::from_be_bytes([bytes[10], bytes[11], bytes[12], bytes[13]]);
+    let payload = &bytes[HEADER_SIZE..];
+
+    // split the payload back into records
+    let (records, lost) = scan_records(payload);
+    if payload.len() == length && checksum(payload) == sum && lost == 0 { return Ok(DataFile { version, records }) }
+
+    // the file is damaged: keep a copy of it and replace it with the records that could be read
+    println!("data_file::read_records({}) -- Error: the file is damaged, recovered {} records and lost {} bytes. The damaged file is kept as {}.corrupt.bin",
+        path, records.len(), lost + length.saturating_sub(payload.len()), path);
+    if let Err(err) = write_bytes(&format!("{}.corrupt", path), &bytes) { println!("data_file::read_records({}) -- Error keeping the damaged file: {}", path, err) }
+    if let Err(err) = write_records(path, version, &records) { println!("data_file::read_records({}) -- Error rewriting the file: {}", path, err) }
+
+    Ok(DataFile { version, records })
+}
+
+// splits a payload into it's records, returning them with the number of bytes that couldn't be read.
+// Records are validated one by one against their checksum and the damaged ones skipped, moving byte by byte until the next intact record
+fn scan_records (payload: &[u8]) -> (Vec<Vec<u8>>, usize) {
+    let record_at = |index: usize| -> Option<(Vec<u8>, usize)> {
+        let length = u16::from_be_bytes(payload.get(index..index + 2)?.try_into().ok()?) as usize;
+        let record = payload.get(index + 2..index + 2 + length)?;
+        let sum = u32::from_be_bytes(payload.get(index + 2 + length..index + 6 + length)?.try_into().ok()?);
+        if checksum(record) != sum { return None }
+        Some((record.to_vec(), index + 6 + length))
+    };
+
+    let mut records = vec![];
+    let mut lost = 0;
+    let mut index = 0;
+    while index < payload.len() {
+        match record_at(index) {
+            Some((record, next)) => { records.push(record); index = next },
+            None => { lost += 1; index += 1 },
+        }
+    }
+    (records, lost)
+}
+
+/// adds a record at the end of the data file at path, creating it if needed
+pub fn append_record (path: &str, version: u16, record: Vec<u8>) -> Result<(), std::io::Error> {
+    let mut records = match read_records(path) {
+        Ok(file) => file.records,
+        Err(DataFileError::Missing) => vec![],
+        Err(err) => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}: {}", path, err))),
+    };
+    records.push(record);
+    write_records(path, version, &records)
+}
+
+/// rewrites a data file from before the versioned format with the records returned by convert.
+/// Files that are missing or already versioned are left untouched
+pub fn migrate_legacy (path: &str, version: u16, convert: fn(&[u8]) -> Vec<Vec<u8>>) {
+    match read_records(path) {
+        Err(DataFileError::Legacy(bytes)) => match write_records(path, version, &convert(&bytes)) {
+            Ok(_) => println!("migrated {}.bin to version {}", path, version),
+            Err(err) => println!("data_file::migrate_legacy({}) -- Error: {}", path, err),
+        },
+
+        // newer files are still read, ignoring the fields this version doesn't know about
+        Ok(file) if file.version > version => println!("{}.bin was written by a newer version ({} > {})", path, file.version, version),
+        _ => {}
+    }
+}
+
+/// converts every data file written before the versioned format, called once on launch
+pub fn migrate_data_files () {
+    migrate_scores();
+    migrate_profiles();
+    migrate_config();
+}
+
+/// adds a string to a record, prefixed by it's length in bytes (up to 255)
+pub fn push_str (record: &mut Vec<u8>, text: &str) {
+    let mut end = text.len().min(u8::MAX as usize);
+    while !text.is_char_boundary(end) { end -= 1 }
+    record.push(end as u8);
+    record.extend(&text.as_bytes()[..end]);
+}
+
+/// reads the fields of a record in order. Fields past the end of the record are missing, so readers can default them
+pub struct RecordReader<'a> {
+    record: &'a [u8],
+    index: usize,
+}
+
+impl<'a> RecordReader<'a> {
+    pub fn new (record: &'a [u8]) -> Self { Self { record, index: 0 } }
+
+    pub fn take (&mut self, count: usize) -> Option<&'a [u8]> {
+        let bytes = self.record.get(self.index..self.index + count)?;
+        self.index += count;
+        Some(bytes)
+    }
+    pub fn u8 (&mut self) -> Option<u8> { self.take(1).map(|bytes| bytes[0]) }
+    pub fn u32 (&mut self) -> Option<u32> { self.take(4).map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])) }
+    pub fn i32 (&mut self) -> Option<i32> { self.u32().map(|value| value as i32) }
+    pub fn u64 (&mut self) -> Option<u64> { self.take(8).map(|bytes| u64::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]])) }
+
+    // a string written by push_str
+    pub fn str (&mut self) -> Option<String> {
+        let length = self.u8()? as usize;
+        self.take(length).map(|bytes| String::from_utf8_lossy(bytes).to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // formats records the way write_records lays out the payload
+    fn payload (records: &[&[u8]]) -> Vec<u8> {
+        let mut payload = vec![];
+        for record in records {
+            payload.extend((record.len() as u16).to_be_bytes());
+            payload.extend(*record);
+            payload.extend(checksum(record).to_be_bytes());
+        }
+        payload
+    }
+
+    #[test]
+    fn checksum_matches_crc32 () {
+        assert_eq!(checksum(b""), 0);
+        assert_eq!(checksum(b"123456789"), 0xCBF4_3926);
+        assert_eq!(checksum(b"The quick brown fox jumps over the lazy dog"), 0x414F_A339);
+    }
+
+    #[test]
+    fn records_round_trip () {
+        let _dir = crate::init_test_data_dir();
+        let records = vec![vec![1, 2, 3], vec![], vec![0xFF; 300]];
+        write_records("scores/test_round_trip", 7, &records).expect("write");
+        let file = read_records("scores/test_round_trip").expect("read");
+        assert_eq!(file.version, 7);
+        assert_eq!(file.records, records);
+    }
+
+    #[test]
+    fn records_too_long_are_rejected () {
+        let _dir = crate::init_test_data_dir();
+        let path = "scores/test_too_long";
+        write_records(path, 1, &[vec![1]]).expect("write");
+        assert!(write_records(path, 1, &[vec![0; u16::MAX as usize + 1]]).is_err());
+        assert_eq!(read_records(path).expect("untouched").records, vec![vec![1]]);
+    }
+
+    #[test]
+    fn scan_skips_damaged_record () {
+        let mut bytes = payload(&[b"first", b"second", b"third"]);
+
+        // flip a byte inside "second", after the 11 bytes of "first" and it's own length
+        bytes[11 + 2 + 1] ^= 0xFF;
+        let (records, lost) = scan_records(&bytes);
+        assert_eq!(records, vec![b"first".to_vec(), b"third".to_vec()]);
+        assert_eq!(lost, 2 + 6 + 4);
+    }
+
+    #[test]
+    fn truncated_header () {
+        let _dir = crate::init_test_data_dir();
+        write_bytes("scores/test_truncated_header", b"RTDC\0").expect("write");
+        assert!(matches!(read_records("scores/test_truncated_header"), Err(DataFileError::Truncated)));
+    }
+
+    #[test]
+    fn truncated_file_keeps_the_complete_records () {
+        let _dir = crate::init_test_data_dir();
+        let path = "scores/test_truncated";
+        write_records(path, 3, &[vec![1; 10], vec![2; 10], vec![3; 10]]).expect("write");
+        let mut bytes = load_binary(path).expect("load");
+        bytes.truncate(bytes.len() - 5);
+        write_bytes(path, &bytes).expect("write");
+
+        let file = read_records(path).expect("recovered");
+        assert_eq!(file.version, 3);
+        assert_eq!(file.records, vec![vec![1; 10], vec![2; 10]]);
+
+        // the damaged file is kept aside and replaced by the recovered records
+        assert_eq!(load_binary(&format!("{}.corrupt", path)).expect("kept"), bytes);
+        assert_eq!(read_records(path).expect("rewritten").records, file.records);
+    }
+
+    #[test]
+    fn damaged_file_loses_only_the_damaged_record () {
+        let _dir = crate::init_test_data_dir();
+        let path = "scores/test_damaged";
+        write_records(path, 1, &[b"first".to_vec(), b"second".to_vec(), b"third".to_vec()]).expect("write");
+        let mut bytes = load_binary(path).expect("load");
+        bytes[HEADER_SIZE + 11 + 2 + 1] ^= 0xFF;
+        write_bytes(path, &bytes).expect("write");
+
+        let file = read_records(path).expect("recovered");
+        assert_eq!(file.records, vec![b"first".to_vec(), b"third".to_vec()]);
+    }
+
+    #[test]
+    fn missing_and_legacy_files () {
+        let _dir = crate::init_test_data_dir();
+        assert!(matches!(read_records("scores/test_missing"), Err(DataFileError::Missing)));
+        write_bytes("scores/test_legacy", &[0x21, 0, 1, 2]).expect("write");
+        assert!(matches!(read_records("scores/test_legacy"), Err(DataFileError::Legacy(bytes)) if bytes == vec![0x21, 0, 1, 2]));
+    }
+}
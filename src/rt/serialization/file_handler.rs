@@ -1,54 +1,61 @@
-use std::{fs::OpenOptions, io::{Write, Read}};
+use std::{fs::OpenOptions, io::{Write, Read}, path::Path};
+use crate::data_path;
 
-// formats the given profile to binary
-pub fn to_bytes<const S: usize> (data: String, align_right: bool) -> Result<[u8; S], std::io::Error> {
+// paths given to these functions are relative to the data directory (see data_dir), unless they take a Path
 
-    // get the name as bytes
-    let name_bytes = data.to_owned().into_bytes();
-
-    // initialize a S length u8 array
-    let mut bytes: [u8; S] = [0; S];
-
-    // loop trough the string bytes
-    for i in 0..name_bytes.len() {
-        bytes[if align_right {16 - name_bytes.len() + i} else { i }] = name_bytes[i];
-    }
-
-    // return the result
-    Ok(bytes)
+// writes the data to the file of given name (extension included), replacing previous content (see write_atomic_at)
+pub fn write_atomic (file: &str, data: &[u8]) -> Result<(), std::io::Error> {
+    write_atomic_at(Path::new(&data_path(file)), data)
 }
 
-// private fn to write to binary
-fn _write_binary <const S: usize> (path: &str, data: [u8; S], append: bool) -> Result<(), std::io::Error> {
+// writes the data to the file at given path, outside of the data directory. The data is written to a temporary
+// file next to it and then renamed over it, so a crash leaves either the old or the new content
+pub fn write_atomic_at (file: &Path, data: &[u8]) -> Result<(), std::io::Error> {
+    let mut temporary = file.as_os_str().to_owned();
+    temporary.push(".tmp");
+
+    // the folder may have been deleted while the game runs
+    if let Some(folder) = file.parent() { std::fs::create_dir_all(folder)? }
 
     // open file with following permissions
     let mut f = OpenOptions::new()
         .create(true)
-        .write(!append)
-        .truncate(!append)
-        .append(append)
-        // .read(true)
-        .open(format!("{}.bin", path))?;
-
-    // append to the file and return the result
-    match f.write(&data[..]) {
-        Ok(_) => Ok(()),
-        Err(error) => Err(error)
+        .write(true)
+        .truncate(true)
+        .open(&temporary)?;
+
+    // make sure the data reached the disk before replacing the file
+    f.write_all(data)?;
+    f.sync_all()?;
+    drop(f);
+    std::fs::rename(&temporary, file)?;
+
+    // the rename itself is only durable once the folder is synced, which isn't possible on every platform
+    if let Some(folder) = file.parent() {
+        if let Ok(folder) = std::fs::File::open(folder) { let _ = folder.sync_all(); }
     }
+    Ok(())
 }
 
-pub fn clear_binary (path: &str) -> Result<(), std::io::Error> {
-    _write_binary(path, [], false)
+// writes a stream of bytes to the binary file at given path, replacing previous content
+pub fn write_bytes (path: &str, data: &[u8]) -> Result<(), std::io::Error> {
+    write_atomic(&format!("{}.bin", path), data)
 }
 
-// writes a stream of bytes to the binary file at given path, replacing previous content
-pub fn write_binary <const S: usize> (path: &str, data: [u8; S]) -> Result<(), std::io::Error> {
-    _write_binary(path, data, false)
+// returns true if the binary file at given path exists and isn't empty
+pub fn binary_exists (path: &str) -> bool {
+    match std::fs::metadata(data_path(&format!("{}.bin", path))) {
+        Ok(metadata) => metadata.len() > 0,
+        Err(_) => false
+    }
 }
 
-// appends a new stream of bytes to the binary file at given path
-pub fn append_binary <const S: usize> (path: &str, data: [u8; S]) -> Result<(), std::io::Error> {
-    _write_binary(path, data, true)
+// deletes the binary file at given path, if any
+pub fn delete_binary (path: &str) -> Result<(), std::io::Error> {
+    match std::fs::remove_file(data_path(&format!("{}.bin", path))) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err),
+        _ => Ok(())
+    }
 }
 
 // loads the bytes of the file at given path
@@ -57,20 +64,13 @@ pub fn load_binary (path: &str) -> Result<Vec<u8>, std::io::Error> {
     // open file with following permissions
     let mut f = OpenOptions::new()
         .read(true)
-        .write(true)
-        .create(true)
-        .open(format!("{}.bin", path))?;
-
-    let num_bytes = f.metadata()?.len() as usize;
+        .open(data_path(&format!("{}.bin", path)))?;
 
     // initialize a buffer for the file data
-    let mut buffer = vec![0; num_bytes];
+    let mut buffer = vec![];
 
     // read the file and store on buffer
     f.read_to_end(&mut buffer)?;
 
-    // println!("file_handler.load_binary -- path: {} | num_bytes: {} | buffer: {:?} | return: {:?}", path, num_bytes, buffer, &buffer[num_bytes..]);
-
-    // skip 24 bytes from the beggining of the buffer and return the result
-    Ok(buffer[num_bytes..].to_vec())
+    Ok(buffer)
 }
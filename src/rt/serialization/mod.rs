@@ -1,4 +1,10 @@
+pub mod data_dir; pub use data_dir::*;
 pub mod file_handler; pub use file_handler::*;
+pub mod data_file; pub use data_file::*;
 pub mod score_tracker; pub use score_tracker::*;
 pub mod profile_tracker; pub use profile_tracker::*;
-pub mod config_tracker; pub use config_tracker::*;
\ No newline at end of file
+pub mod config_tracker; pub use config_tracker::*;
+pub mod ruleset_loader; pub use ruleset_loader::*;
+pub mod save_tracker; pub use save_tracker::*;
+pub mod achievement_tracker; pub use achievement_tracker::*;
+pub mod data_export; pub use data_export::*;
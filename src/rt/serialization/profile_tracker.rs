@@ -1,117 +1,208 @@
-use crate::{ clear_binary, append_binary, load_binary };
+use crate::{write_records, read_records, write_atomic, data_path, migrate_legacy, push_str, RecordReader, DataFileError, highest_player};
 
 
 // path to the profiles file
-const PROFILES_PATH: &str = "data/profiles/profiles";
-const SESSION_PATH: &str = "data/profiles/session";
-
-// maximum number of profiles 
-pub const MAX_PROFILES: usize = 15;
-
-// formats the given profile to binary
-fn to_bytes (name: String) -> Result<[u8; 16], std::io::Error> {
-
-    // make sure that the length of the name is at most 16  chars
-    assert!(name.len() <= 16, "profile_tracker.to_bytes() -- Error: Expected name of size <= 16 but got {} instead", name.len());
+const PROFILES_PATH: &str = "profiles/profiles";
+const SESSION_PATH: &str = "profiles/session";
+
+// version of the layout of the profile records
+const VERSION: u16 = 1;
+
+// first byte of each record, telling what it holds: the counter of ids, or a profile. Tagging them keeps
+// the counter from being mistaken for a profile if a damaged record is dropped
+const TAG_NEXT_ID: u8 = 0;
+const TAG_PROFILE: u8 = 1;
+
+// length limits of profile names, in characters
+pub const MIN_NAME_LENGTH: usize = 3;
+pub const MAX_NAME_LENGTH: usize = 16;
+
+// returns the name as profiles take it: without control characters or surrounding spaces, and cut to MAX_NAME_LENGTH.
+// None if it's shorter than MIN_NAME_LENGTH
+pub fn valid_name (name: &str) -> Option<String> {
+    let name: String = name.chars().filter(|c| !c.is_control()).collect();
+    let name: String = name.trim().chars().take(MAX_NAME_LENGTH).collect::<String>().trim_end().to_string();
+    if name.chars().count() < MIN_NAME_LENGTH { return None }
+    Some(name)
+}
 
-    crate::file_handler::to_bytes::<16>(name, true)
+// a player profile. The id is given once on creation and never reused, scores and the session refer to it
+#[derive(Debug, Clone)]
+pub struct Profile {
+    pub id: u32,
+    pub name: String,
 }
 
-// saves the given vec of profiles, overwriting any previous profiles
-pub fn save_profiles (profiles: &Vec<String>) -> Result<(), std::io::Error> {
+// formats the given profile to a record holding it's id and name
+fn to_bytes (id: u32, name: &str) -> Vec<u8> {
+    let mut bytes = vec![TAG_PROFILE];
+    bytes.extend(id.to_be_bytes());
+    push_str(&mut bytes, name);
+    bytes
+}
 
-    // clear binary while catching error
-    if let Err(err) = clear_binary(PROFILES_PATH) { return Err(err) }
+// formats the record holding the id the next profile will get
+fn next_id_bytes (next_id: u32) -> Vec<u8> {
+    let mut bytes = vec![TAG_NEXT_ID];
+    bytes.extend(next_id.to_be_bytes());
+    bytes
+}
 
-    // loop through given profiles
-    for i in 0..profiles.len() {
+// reads the records of the profiles file: the id the next profile will get and one record per profile.
+// Without the counter the ids taken are found on the profiles and the scores, which outlive deleted profiles
+fn load_records () -> Result<(u32, Vec<Profile>), std::io::Error> {
+    match read_records(PROFILES_PATH) {
+
+        // file is loaded successfully, read the counter and the id and name of each record
+        Ok(file) => {
+            let mut next_id = None;
+            let mut profiles = vec![];
+            for (i, record) in file.records.iter().enumerate() {
+                let mut reader = RecordReader::new(record);
+                match (reader.u8(), reader.u32()) {
+                    (Some(TAG_NEXT_ID), Some(id)) => next_id = Some(id),
+                    (Some(TAG_PROFILE), Some(id)) => profiles.push(Profile { id, name: reader.str().unwrap_or_else(|| "!!invalid name!!".to_string()) }),
+                    _ => println!("profile_tracker::load_records() -- Error: record {} is neither a profile nor the counter, skipped", i),
+                }
+            }
+            let next_id = match next_id {
+                Some(next_id) => next_id,
+                None => {
+                    println!("profile_tracker::load_records() -- Error: the counter of ids is missing, continuing after the highest id in use");
+                    profiles.iter().map(|profile| profile.id).fold(highest_player(), u32::max) + 1
+                },
+            };
+            Ok((next_id, profiles))
+        },
 
-        // get bytes of profile and match result
-        match to_bytes(profiles[i].to_owned()) {
+        // no profiles yet
+        Err(DataFileError::Missing) => Ok((1, vec![])),
 
-            // Ok: append to the file and catch if error
-            Ok(bytes) => if let Err(err) = append_binary(PROFILES_PATH, bytes) { return Err(err) },
-            
-            // Error: return the error
-            Err(err) => return Err(err)
-        }
+        // error loading the file
+        Err(e) => Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}: {}", PROFILES_PATH, e)))
     }
-    
-    // successfull operation
-    Ok(())
 }
 
-pub fn get_profiles () -> Result<Vec<String>, std::io::Error> {
-
-    // loads the binary and match result
-    match load_binary(PROFILES_PATH) {
-
-        // file is loaded successfully
-        Ok(buffer) => {
+// saves the given vec of profiles, overwriting any previous profiles. The ids of deleted profiles stay taken
+pub fn save_profiles (profiles: &[Profile]) -> Result<(), std::io::Error> {
+    let next_id = next_profile_id(profiles);
+    let mut records = vec![next_id_bytes(next_id)];
+    records.extend(profiles.iter().map(|profile| to_bytes(profile.id, &profile.name)));
+    write_records(PROFILES_PATH, VERSION, &records)
+}
 
-            // create a vec of tuple for player, gamemode and score
-            let mut list = vec![]; 
+pub fn get_profiles () -> Result<Vec<Profile>, std::io::Error> {
+    load_records().map(|(_, profiles)| profiles)
+}
 
-            // loop through the loaded buffer with increments of 16 u8
-            for i in 0..buffer.len() / 16 {
+// returns the id for a new profile: never given before, even to deleted profiles
+pub fn next_profile_id (profiles: &[Profile]) -> u32 {
+    let next_id = match load_records() { Ok((next_id, _)) => next_id, Err(_) => 1 };
+    profiles.iter().map(|profile| profile.id + 1).fold(next_id, u32::max)
+}
 
-                // add the following tuple to the list
-                list.push(
+// converts the profiles file from before the versioned format, where each name took 16 bytes aligned to the right.
+// Profiles are given the ids they were referred by until then: their position on the file, starting at 1
+pub fn migrate_profiles () {
+    migrate_legacy(PROFILES_PATH, VERSION, legacy_profiles);
+}
 
-                    // id: first u8
-                    // buffer[i * 16],
+// records of the profiles from a file written before the versioned format
+fn legacy_profiles (buffer: &[u8]) -> Vec<Vec<u8>> {
+    let names = buffer.chunks_exact(16).map(|bytes| match String::from_utf8(bytes.to_vec()) {
+        Ok(name) => name.trim_matches(char::from(0)).to_string(),
+        Err(_) => "!!invalid name!!".to_string(),
+    }).collect();
+    with_ids(names)
+}
 
-                    // score: the 3 remaining u8
-                    // i32::from_be_bytes([0, buffer[(i * 4) + 1], buffer[(i * 4) + 2], buffer[(i * 4) + 3]]) * 10
-                    match String::from_utf8(buffer[(i * 16) .. (i * 16) + 16].to_vec()) {
-                        Ok(name) => name.trim_matches(char::from(0)).to_string(),
-                        Err(_) => "!!invalid name!!".to_string()
-                    }
-                )
-            }
+// records for profiles identified by their position
+fn with_ids (names: Vec<String>) -> Vec<Vec<u8>> {
+    let mut records = vec![next_id_bytes(names.len() as u32 + 1)];
+    records.extend(names.iter().enumerate().map(|(i, name)| to_bytes(i as u32 + 1, name)));
+    records
+}
 
-            // return the result
-            Ok(list)
+pub fn profile_name (profile: u32) -> String {
+    match get_profiles() {
+        Ok(profiles) => match profiles.iter().find(|p| p.id == profile) {
+            Some(p) => p.name.to_string(),
+            None => "Unknown".to_string()
         },
+        Err(_) => "Error".to_string()
+    }
+}
 
-        // error loading binary file
-        Err(e) => Err(e)
+// reads the session file: the profile playing and the one on the second side of versus, one "KEY: id" per line
+fn read_session () -> (Option<u32>, Option<u32>) {
+    use std::{fs::File, io::prelude::*};
+    let mut content = String::new();
+    match File::open(data_path(&format!("{}.txt", SESSION_PATH))) {
+        Ok(mut file) => if file.read_to_string(&mut content).is_err() { println!("read error"); return (None, None) },
+        Err(_) => { println!("file open error"); return (None, None) }
     }
+    let get = |key: &str| content.lines().find_map(|line| {
+        let mut parts = line.split_whitespace();
+        if parts.next()? != key { return None }
+        parts.next()?.parse().ok()
+    });
+    (get("CURRENT_PROFILE:"), get("VERSUS_PROFILE:"))
 }
 
-pub fn profile_name (profile: usize) -> String {
+fn write_session (profile: u32, versus: u32) {
+    let data = format!("CURRENT_PROFILE: {}\nVERSUS_PROFILE: {}", profile, versus);
+    if let Err(err) = write_atomic(&format!("{}.txt", SESSION_PATH), data.as_bytes()) { println!("profile_tracker::write_session() -- Error: {}", err) }
+}
+
+// returns the given profile id if it still exists, it may have been deleted since it was saved on the session
+fn existing (profile: Option<u32>) -> Option<u32> {
+    let profile = profile?;
     match get_profiles() {
-        Ok(profiles) => profiles[profile - 1].to_string(),
-        Err(_) => "Error".to_string()
+        Ok(profiles) if profiles.iter().any(|p| p.id == profile) => Some(profile),
+        _ => None
     }
 }
 
-pub fn set_profile (profile: usize) {
-    use std::{fs::File, io::prelude::*};
-    let data = format!("CURRENT_PROFILE: {}", profile);
-    // std::fs::write(SESSION_PATH, data).expect("profile_tracker.set_profile() -- Error: Unable to write file");
-    if let Ok(mut file) = File::create(format!("{}.txt", SESSION_PATH)) {
-        let _ = write!(file, "{}", data);
-    }
+pub fn set_profile (profile: u32) {
+    write_session(profile, read_session().1.unwrap_or(0))
 }
 
-pub fn load_profile () -> Option<usize> {
+// sets the profile playing on the second side of versus, the first being the session's profile
+pub fn set_versus_profile (profile: u32) {
+    write_session(read_session().0.unwrap_or(0), profile)
+}
+
+// returns the id of the profile of the session, if it still exists
+pub fn load_profile () -> Option<u32> {
     println!("load profile");
-    use std::{fs::File, io::prelude::*};
-    match File::open(format!("{}.txt", SESSION_PATH)) {
-        Ok(mut file) => {
-            println!("file open ok");
-            let mut content = String::new();
-            if let Err(_) = file.read_to_string(&mut content) { println!("read error"); return None }
-            let mut parts = content.split_whitespace();
-            parts.next();
-            match parts.next() {
-                Some(profile) => {
-                    profile.parse().ok()
-                },
-                None => None
-            }
-        },
-        Err(_) => {println!("file open error"); None }
+    existing(read_session().0)
+}
+
+// returns the id of the profile playing on the second side of versus, if it still exists
+pub fn load_versus_profile () -> Option<u32> {
+    existing(read_session().1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_profiles_get_their_position_as_id () {
+        let mut buffer = vec![];
+        for name in ["alice", "bob"] {
+            let mut bytes = vec![0u8; 16 - name.len()];
+            bytes.extend(name.as_bytes());
+            buffer.extend(bytes);
+        }
+        let records = legacy_profiles(&buffer);
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0], next_id_bytes(3));
+        for (i, name) in ["alice", "bob"].iter().enumerate() {
+            let mut reader = RecordReader::new(&records[i + 1]);
+            assert_eq!(reader.u8(), Some(TAG_PROFILE));
+            assert_eq!(reader.u32(), Some(i as u32 + 1));
+            assert_eq!(reader.str().as_deref(), Some(*name));
+        }
     }
-}
\ No newline at end of file
+}
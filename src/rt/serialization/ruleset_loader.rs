@@ -0,0 +1,202 @@
+use std::{collections::BTreeMap, fmt};
+use serde::Deserialize;
+use crate::{data_path, Ruleset, PieceRules, KickTable, TetrominoID, PieceSet, RTColor, Cells, rotation_states, MAX_SIZE, HIDDEN_ROWS, DEFAULT_PLAYFIELD_WIDTH};
+
+pub const RULESET_PATH: &str = "rulesets/custom.toml";
+
+// everything that can go wrong while loading a ruleset file
+#[derive(Debug)]
+pub enum RulesetError {
+    Io(String),
+    Parse(String),
+    UnknownPiece(String),
+    MissingPiece(&'static str),
+    UnknownColor(&'static str, String),
+    SizeTooBig(&'static str, u8),
+    MissingCells(&'static str),
+    StateCount(&'static str, usize),
+    CellCount(&'static str, usize, usize, usize),
+    CellOutOfBounds(&'static str, usize, (i8, i8)),
+    DuplicateCell(&'static str, usize, (i8, i8)),
+    UnknownKicks(&'static str, String),
+    InvalidKickKey(String, String),
+    EmptyKicks(String, String),
+    SpawnOutOfBounds(&'static str, (i8, i8)),
+}
+
+impl fmt::Display for RulesetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RulesetError::Io(err) => write!(f, "can't read {}: {}", data_path(RULESET_PATH), err),
+            RulesetError::Parse(err) => write!(f, "invalid TOML: {}", err),
+            RulesetError::UnknownPiece(name) => write!(f, "unknown piece \"{}\", expected one of I J L O S Z T or a pentomino like F5", name),
+            RulesetError::MissingPiece(name) => write!(f, "piece {} is not defined", name),
+            RulesetError::UnknownColor(name, color) => write!(f, "piece {}: unknown color \"{}\"", name, color),
+            RulesetError::SizeTooBig(name, size) => write!(f, "piece {}: size {} is bigger than the maximum of {}", name, size, MAX_SIZE),
+            RulesetError::MissingCells(name) => write!(f, "piece {}: needs either cells or states", name),
+            RulesetError::StateCount(name, count) => write!(f, "piece {}: expected 4 states but found {}", name, count),
+            RulesetError::CellCount(name, state, expected, count) => write!(f, "piece {} state {}: expected {} cells but found {}", name, state, expected, count),
+            RulesetError::CellOutOfBounds(name, state, cell) => write!(f, "piece {} state {}: cell {:?} is outside of it's bounding box", name, state, cell),
+            RulesetError::DuplicateCell(name, state, cell) => write!(f, "piece {} state {}: cell {:?} is listed twice", name, state, cell),
+            RulesetError::UnknownKicks(name, kicks) => write!(f, "piece {}: unknown kick table \"{}\"", name, kicks),
+            RulesetError::InvalidKickKey(table, key) => write!(f, "kick table {}: \"{}\" should be two different states like \"0-1\"", table, key),
+            RulesetError::EmptyKicks(table, key) => write!(f, "kick table {}: \"{}\" has no tests, list at least [0, 0] to allow the rotation", table, key),
+            RulesetError::SpawnOutOfBounds(name, pos) => write!(f, "piece {}: spawn position {:?} is outside of the playfield or it's buffer zone", name, pos),
+        }
+    }
+}
+
+// the layout of the ruleset file
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesetFile {
+    name: String,
+    pieces: BTreeMap<String, PieceFile>,
+    #[serde(default)]
+    kicks: BTreeMap<String, BTreeMap<String, Vec<[i8; 2]>>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PieceFile {
+    color: String,
+    size: u8,
+
+    // spawn state, the other states are generated by rotating it inside the bounding box
+    cells: Option<Vec<[i8; 2]>>,
+
+    // all 4 states, overriding cells
+    states: Option<Vec<Vec<[i8; 2]>>>,
+    spawn: Option<[i8; 2]>,
+    kicks: Option<String>,
+}
+
+/// loads and validates the custom ruleset
+pub fn load_ruleset () -> Result<Ruleset, RulesetError> {
+    match std::fs::read_to_string(data_path(RULESET_PATH)) {
+        Ok(text) => parse_ruleset(&text),
+        Err(err) => Err(RulesetError::Io(err.to_string())),
+    }
+}
+
+/// parses and validates a ruleset from the contents of a ruleset file
+pub fn parse_ruleset (text: &str) -> Result<Ruleset, RulesetError> {
+
+    let file: RulesetFile = match toml::from_str(text) {
+        Ok(file) => file,
+        Err(err) => {
+            // keep the message on a single line, pointing at the line of the error
+            let line = err.span().map(|span| text[..span.start].matches('\n').count() + 1).unwrap_or(1);
+            return Err(RulesetError::Parse(format!("line {}: {}", line, err.message().trim())))
+        },
+    };
+
+    // every piece on the file has to be known
+    if let Some(name) = file.pieces.keys().find(|name| TetrominoID::from_text(name).is_none()) {
+        return Err(RulesetError::UnknownPiece(name.clone()))
+    }
+
+    // validate the kick tables once, before the pieces refer to them
+    let mut kick_tables: BTreeMap<&str, KickTable> = BTreeMap::new();
+    for (table, pairs) in &file.kicks {
+        kick_tables.insert(table.as_str(), parse_kicks(table, pairs)?);
+    }
+
+    // build the rules of each piece in the order of TetrominoID::all(), only the Tetrominos are required
+    let mut pieces = Vec::new();
+    for id in TetrominoID::all() {
+        let name = id.text();
+        let piece = match file.pieces.get(name) {
+            Some(piece) => piece,
+            None if PieceSet::Tetrominoes.pieces().contains(id) => return Err(RulesetError::MissingPiece(name)),
+            None => { pieces.push(None); continue }
+        };
+        let cell_count = id.cell_count();
+
+        let color = match RTColor::from_text(&piece.color) {
+            Some(color) => color,
+            None => return Err(RulesetError::UnknownColor(name, piece.color.clone())),
+        };
+
+        if piece.size as usize > MAX_SIZE { return Err(RulesetError::SizeTooBig(name, piece.size)) }
+
+        let states = match (&piece.states, &piece.cells) {
+            (Some(states), _) => {
+                if states.len() != 4 { return Err(RulesetError::StateCount(name, states.len())) }
+                [
+                    parse_cells(name, 0, &states[0], cell_count, piece.size)?,
+                    parse_cells(name, 1, &states[1], cell_count, piece.size)?,
+                    parse_cells(name, 2, &states[2], cell_count, piece.size)?,
+                    parse_cells(name, 3, &states[3], cell_count, piece.size)?,
+                ]
+            },
+            (None, Some(cells)) => rotation_states(parse_cells(name, 0, cells, cell_count, piece.size)?, piece.size as i8),
+            (None, None) => return Err(RulesetError::MissingCells(name)),
+        };
+
+        let kicks = match &piece.kicks {
+            Some(table) => match kick_tables.get(table.as_str()) {
+                Some(kicks) => kicks.clone(),
+                None => return Err(RulesetError::UnknownKicks(name, table.clone())),
+            },
+            None => no_kicks(),
+        };
+
+        // the spawn state has to fit inside the columns of the standard playfield (narrower boards clamp it)
+        // and the row offset has to keep it inside the buffer zone
+        let spawn = piece.spawn.map(|[x, y]| (x, y));
+        if let Some(pos) = spawn {
+            let outside_x = states[0].iter().any(|(x, _)| pos.0 + x < 0 || pos.0 + x >= DEFAULT_PLAYFIELD_WIDTH as i8);
+            let outside_y = pos.1.abs() > (HIDDEN_ROWS as usize - MAX_SIZE) as i8;
+            if outside_x || outside_y { return Err(RulesetError::SpawnOutOfBounds(name, pos)) }
+        }
+
+        pieces.push(Some(PieceRules { states, size: piece.size, color, spawn, kicks }));
+    }
+
+    Ok(Ruleset { name: file.name, pieces })
+}
+
+// validates the cells of a single rotation state
+fn parse_cells (name: &'static str, state: usize, cells: &[[i8; 2]], count: usize, size: u8) -> Result<Cells, RulesetError> {
+
+    if cells.len() != count { return Err(RulesetError::CellCount(name, state, count, cells.len())) }
+
+    let mut result: Vec<(i8, i8)> = Vec::new();
+    for [x, y] in cells.iter().copied() {
+        if x < 0 || y < 0 || x >= size as i8 || y >= size as i8 {
+            return Err(RulesetError::CellOutOfBounds(name, state, (x, y)))
+        }
+        if result.contains(&(x, y)) { return Err(RulesetError::DuplicateCell(name, state, (x, y))) }
+        result.push((x, y));
+    }
+
+    match Cells::from_slice(&result) {
+        Some(cells) => Ok(cells),
+        None => Err(RulesetError::CellCount(name, state, count, cells.len())),
+    }
+}
+
+// validates a kick table, keyed by "from-to" rotation states
+fn parse_kicks (table: &str, pairs: &BTreeMap<String, Vec<[i8; 2]>>) -> Result<KickTable, RulesetError> {
+
+    let mut kicks = no_kicks();
+    for (key, tests) in pairs {
+        let states: Result<Vec<usize>, _> = key.split('-').map(|state| state.parse()).collect();
+        match states.as_deref() {
+            Ok(&[from, to]) if from < 4 && to < 4 && from != to => {
+
+                // a rotation without tests could never happen
+                if tests.is_empty() { return Err(RulesetError::EmptyKicks(table.to_string(), key.clone())) }
+                kicks[from][to] = tests.iter().map(|[x, y]| (*x, *y)).collect();
+            },
+            _ => return Err(RulesetError::InvalidKickKey(table.to_string(), key.clone())),
+        }
+    }
+    Ok(kicks)
+}
+
+// a kick table that only tests the basic rotation
+fn no_kicks () -> KickTable {
+    std::array::from_fn(|_| std::array::from_fn(|_| vec![(0, 0)]))
+}
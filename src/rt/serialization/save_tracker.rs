@@ -0,0 +1,310 @@
+use std::fmt;
+use crate::{write_records, read_records, binary_exists, delete_binary, config_tracker};
+use crate::{Game, GameRules, GameStats, RunState, EntryDelay, RoutineHandler, Bag, Playfield, TetrominoID, RTColor, RotationSystemID, PieceSet, BoardSize};
+
+// path of the save slot of the in-progress run
+const PATH_SAVE: &str = "saves/game";
+
+// version of the layout of the save records
+const VERSION: u16 = 1;
+
+// the records after the game mode start with what they hold: the state of a Game, or a part of the recording of one.
+// Recordings outgrow the length of a record on long runs, so they're split in parts of RECORDING_PART frames
+const TAG_GAME: u8 = 0;
+const TAG_RECORDING: u8 = 1;
+const RECORDING_PART: usize = 16384;
+
+// reasons for a saved run to not be resumed
+#[derive(Debug)]
+pub enum SaveError {
+    Missing,
+    Corrupt,
+    RulesChanged,
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Missing => write!(f, "No saved game"),
+            SaveError::Corrupt => write!(f, "The saved game is corrupted and was discarded"),
+            SaveError::RulesChanged => write!(f, "The rules changed since the game was saved, switch them back to continue it"),
+        }
+    }
+}
+
+/// returns true if there's a saved run to continue
+pub fn has_save () -> bool {
+    binary_exists(PATH_SAVE)
+}
+
+/// deletes the saved run
+pub fn delete_save () {
+    if let Err(err) = delete_binary(PATH_SAVE) { println!("save_tracker::delete_save() -- Error: {}", err) }
+}
+
+/// saves the games of the run in progress, replacing any previous save.
+/// records: the game mode id followed by the state of each Game (see game_to_bytes), then the parts of their recordings:
+/// the index of the Game and the keys of each frame (2 each)
+pub fn save_games (game_mode: u8, games: &[&Game]) -> Result<(), std::io::Error> {
+    let mut records = vec![vec![game_mode]];
+    for game in games {
+        let mut bytes = vec![TAG_GAME];
+        game_to_bytes(game, &mut bytes);
+        records.push(bytes);
+    }
+    for (i, game) in games.iter().enumerate() {
+        for part in game.recording.chunks(RECORDING_PART) {
+            let mut bytes = vec![TAG_RECORDING, i as u8];
+            for frame in part { bytes.extend(frame.to_be_bytes()) }
+            records.push(bytes);
+        }
+    }
+    write_records(PATH_SAVE, VERSION, &records)
+}
+
+/// loads the saved run, returning the game mode id and it's games paused at the moment they were saved.
+/// The save is left in place, it's up to the caller to delete it once the run is resumed or found corrupted
+pub fn load_games () -> Result<(u8, Vec<Game>), SaveError> {
+
+    if !has_save() { return Err(SaveError::Missing) }
+    let records = match read_records(PATH_SAVE) {
+        Ok(file) => file.records,
+        Err(_) => return Err(SaveError::Corrupt),
+    };
+
+    let game_mode = match records.first().and_then(|record| record.first()) {
+        Some(game_mode) if *game_mode <= 1 => *game_mode,
+        _ => return Err(SaveError::Corrupt),
+    };
+
+    // the records of the Games, and the parts of their recordings
+    let mut states: Vec<&[u8]> = vec![];
+    let mut recordings: Vec<(usize, &[u8])> = vec![];
+    for record in &records[1..] {
+        match record.split_first() {
+            Some((&TAG_GAME, state)) => states.push(state),
+            Some((&TAG_RECORDING, [index, frames @ ..])) if frames.len() % 2 == 0 => recordings.push((*index as usize, frames)),
+            _ => return Err(SaveError::Corrupt),
+        }
+    }
+    let count = states.len();
+    if count == 0 || count > 2 { return Err(SaveError::Corrupt) }
+
+    // the run has to be resumed with the rules it was started with
+    let rules = config_tracker::get_rules(game_mode as usize);
+
+    let mut games = vec![];
+    for (i, record) in states.into_iter().enumerate() {
+        let player = if game_mode == 0 { 0 } else { i + 1 };
+
+        // the profile playing the Game ends the record
+        let end = record.len().checked_sub(4).ok_or(SaveError::Corrupt)?;
+        let profile = u32::from_be_bytes([record[end], record[end + 1], record[end + 2], record[end + 3]]);
+        games.push(game_from_bytes(&mut Reader { buffer: record, index: 0 }, player, profile, rules)?);
+    }
+    for (index, frames) in recordings {
+        let game = games.get_mut(index).ok_or(SaveError::Corrupt)?;
+        game.recording.extend(frames.chunks(2).map(|frame| u16::from_be_bytes([frame[0], frame[1]])));
+    }
+
+    Ok((game_mode, games))
+}
+
+// appends the state of a Game to the given bytes:
+// rules (6), playfield size (2), score (4), speed (4), gravity timer (4), fall intent (1), current Tetromino (5),
+// entry delay (2), bag: 1 if any (1), seed (8) and pieces drawn (4), rows (4 each), colors (1 per block, 0 if empty),
+// statistics: frames, lines, pieces, combo, max combo, line clears, attack, back to back chain, longest chain and
+// Tetrominoes placed of each kind (4 each), the handling the run was recorded with: 1 and the DAS and ARR,
+// or 0 if it changed during the run (3), and the id of the profile playing the Game (4)
+fn game_to_bytes (game: &Game, bytes: &mut Vec<u8>) {
+
+    let rules = &game.rules;
+    bytes.extend([rules.rotation_system.id(), rules.piece_set.id(), rules.board_size.id(), rules.line_clear_delay, rules.entry_delay, rules.initial_rotation as u8]);
+    bytes.extend([game.playfield.width() as u8, game.playfield.visible_height() as u8]);
+    bytes.extend(game.score.to_be_bytes());
+    bytes.extend(game.move_y_cooldown.to_be_bytes());
+    let timer = game.routines.iter().find(|routine| routine.key() == "move_y").map(|routine| routine.timer).unwrap_or(0);
+    bytes.extend(timer.to_be_bytes());
+    bytes.push(game.move_intent.1 as u8);
+
+    match &game.cur_tetromino {
+        Some(t) => bytes.extend([1, t.id.index() as u8, t.rotation, game.cur_pos.0 as u8, game.cur_pos.1 as u8]),
+        None => bytes.extend([0; 5]),
+    }
+
+    match &game.entry_delay {
+        Some(EntryDelay::LineClear(_, frames)) => bytes.extend([1, *frames]),
+        Some(EntryDelay::Spawn(frames)) => bytes.extend([2, *frames]),
+        None => bytes.extend([0, 0]),
+    }
+
+    let (seed, drawn) = match &game.bag_queue { Some(bag) => bag.seed(), None => (game.seed, 0) };
+    bytes.push(game.bag_queue.is_some() as u8);
+    bytes.extend(seed.to_be_bytes());
+    bytes.extend(drawn.to_be_bytes());
+
+    for row in &game.playfield.rows { bytes.extend(row.to_be_bytes()) }
+    for y in 0..game.playfield.height() as usize {
+        for x in 0..game.playfield.width() as usize {
+            bytes.push(match game.playfield.colors[x][y] { Some(color) => color.id() + 1, None => 0 });
+        }
+    }
+
+    let stats = &game.stats;
+    for value in [stats.frames, stats.lines, stats.pieces, stats.combo, stats.max_combo] { bytes.extend(value.to_be_bytes()) }
+    for value in stats.clears { bytes.extend(value.to_be_bytes()) }
+    bytes.extend(stats.attack.to_be_bytes());
+    bytes.extend(stats.back_to_back.to_be_bytes());
+    bytes.extend(stats.max_back_to_back.to_be_bytes());
+    for value in stats.placed { bytes.extend(value.to_be_bytes()) }
+    match game.handling {
+        Some((das, arr)) => bytes.extend([1, das, arr]),
+        None => bytes.extend([0, 0, 0]),
+    }
+    bytes.extend(game.profile.to_be_bytes());
+}
+
+// reads the state of a Game written by game_to_bytes, rejecting it if it was played with other rules
+fn game_from_bytes (reader: &mut Reader, player: usize, profile: u32, rules: GameRules) -> Result<Game, SaveError> {
+
+    let saved_rules = GameRules {
+        rotation_system: RotationSystemID::from_id(reader.u8()?),
+        piece_set: PieceSet::from_id(reader.u8()?),
+        board_size: BoardSize::from_id(reader.u8()?),
+        line_clear_delay: reader.u8()?,
+        entry_delay: reader.u8()?,
+        initial_rotation: reader.u8()? != 0,
+    };
+    if saved_rules != rules { return Err(SaveError::RulesChanged) }
+
+    let mut game = match player {
+        0 => Game::singleplayer(rules, profile),
+        _ => Game::versus(player, rules, profile),
+    };
+
+    // the playfield has to be the one the rules describe
+    let (width, height) = (reader.u8()?, reader.u8()?);
+    if width as i8 != game.playfield.width() || height as i8 != game.playfield.visible_height() { return Err(SaveError::Corrupt) }
+
+    game.score = reader.i32()?;
+    game.move_y_cooldown = reader.u32()?;
+    let timer = reader.u32()?;
+    let intent_y = reader.u8()? as i8;
+
+    let current = reader.take(5)?;
+    if current[0] != 0 {
+        let id = match TetrominoID::all().get(current[1] as usize) { Some(id) => *id, None => return Err(SaveError::Corrupt) };
+        if !rules.piece_set.pieces().contains(&id) { return Err(SaveError::Corrupt) }
+        let mut t = game.rotation_system.spawn(id);
+        t.set_rotation(current[2]);
+        game.set_current(t, (current[3] as i8, current[4] as i8));
+    }
+
+    let delay = reader.take(2)?;
+
+    // the bag deals the same pieces again from it's seed
+    let has_bag = reader.u8()? != 0;
+    game.seed = reader.u64()?;
+    let drawn = reader.u32()?;
+    if has_bag { game.bag_queue = Some(Bag::resume(rules.piece_set.pieces(), game.seed, drawn)) }
+
+    let mut playfield = Playfield::new(width, height);
+    for y in 0..playfield.height() as usize { playfield.rows[y] = reader.u32()? }
+    for y in 0..playfield.height() as usize {
+        for x in 0..playfield.width() as usize {
+            playfield.colors[x][y] = match reader.u8()? {
+                0 => None,
+                id => match RTColor::from_id(id - 1) { Some(color) => Some(color), None => return Err(SaveError::Corrupt) },
+            };
+        }
+    }
+    game.playfield = playfield;
+
+    game.stats = GameStats {
+        frames: reader.u32()?,
+        lines: reader.u32()?,
+        pieces: reader.u32()?,
+        combo: reader.u32()?,
+        max_combo: reader.u32()?,
+        ..GameStats::default()
+    };
+    for clears in game.stats.clears.iter_mut() { *clears = reader.u32()? }
+    game.stats.attack = reader.u32()?;
+    game.stats.back_to_back = reader.u32()?;
+    game.stats.max_back_to_back = reader.u32()?;
+    for placed in game.stats.placed.iter_mut() { *placed = reader.u32()? }
+
+    // a run resumed with another handling can't be replayed
+    let handling = reader.take(3)?;
+    let resumed_with = (game.preferences.das, game.preferences.arr);
+    if handling[0] == 0 || (handling[1], handling[2]) != resumed_with { game.handling = None }
+
+    // the rows of an interrupted line clear are still on the playfield
+    game.entry_delay = match delay[0] {
+        1 => Some(EntryDelay::LineClear(game.playfield.full_rows(), delay[1])),
+        2 => Some(EntryDelay::Spawn(delay[1])),
+        _ => None,
+    };
+
+    // restore the fall speed and gravity timer, resuming the run from the pause menu
+    game.initialize_routines();
+    game.set_state(RunState::Playing);
+    game.pause();
+    game.intent_y(intent_y);
+    if let Some(routine) = game.get_routine("move_y", "game") { routine.set_timer(timer) }
+
+    Ok(game)
+}
+
+// reads values from a buffer in order, running out of bytes means the save is corrupted
+struct Reader<'a> {
+    buffer: &'a [u8],
+    index: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take (&mut self, count: usize) -> Result<&'a [u8], SaveError> {
+        match self.buffer.get(self.index..self.index + count) {
+            Some(bytes) => { self.index += count; Ok(bytes) },
+            None => Err(SaveError::Corrupt),
+        }
+    }
+    fn u8 (&mut self) -> Result<u8, SaveError> { self.take(1).map(|bytes| bytes[0]) }
+    fn u32 (&mut self) -> Result<u32, SaveError> { self.take(4).map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])) }
+    fn i32 (&mut self) -> Result<i32, SaveError> { self.u32().map(|value| value as i32) }
+    fn u64 (&mut self) -> Result<u64, SaveError> { Ok((self.u32()? as u64) << 32 | self.u32()? as u64) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HasBag;
+
+    #[test]
+    fn long_recordings_are_saved () {
+        let _dir = crate::init_test_data_dir();
+        let mut game = Game::singleplayer(config_tracker::get_rules(0), 0);
+        for _ in 0..3 { game.bag_next(); }
+        game.recording = (0..40000u32).map(|frame| frame as u16).collect();
+        game.stats.frames = 40000;
+        save_games(0, &[&game]).expect("save");
+
+        let (game_mode, games) = load_games().expect("load");
+        delete_save();
+        assert_eq!(game_mode, 0);
+        assert_eq!(games[0].recording, game.recording);
+        assert!(games[0].replayable());
+
+        // the bag deals the same pieces from it's seed
+        let bag = |game: &Game| game.bag_queue.as_ref().map(|bag| (bag.seed(), bag.peek()));
+        assert_eq!(bag(&game).map(|(seed, _)| seed.1), Some(3));
+        assert_eq!(bag(&games[0]), bag(&game));
+
+        // a run which handling changed stays unreplayable once resumed
+        game.handling = None;
+        save_games(0, &[&game]).expect("save");
+        let (_, games) = load_games().expect("load");
+        delete_save();
+        assert!(!games[0].replayable());
+    }
+}
@@ -1,151 +1,313 @@
-use crate::{write_binary, append_binary, load_binary};
+use crate::{write_records, read_records, append_record, binary_exists, migrate_legacy, RecordReader, DataFileError, Game};
 
 // path where scores are saved
-const PATH_HISTORY: &str = "data/scores/history";
-const PATH_BEST: &str = "data/scores/best";
+const PATH_HISTORY: &str = "scores/history";
+const PATH_BEST: &str = "scores/best";
 
-// maximum best scores list length
+// scores of deleted profiles, kept out of the lists
+const PATH_ARCHIVE: &str = "scores/archive";
+
+// version of the layout of the score records, shared by every score file
+const VERSION: u16 = 1;
+
+// maximum best scores list length, of each game mode and profile
 const MAX_BEST_LENGTH: usize = 100;
 
-// formats the given data to binary
-fn to_bytes (player_id: u8, game_mode: u8, score: i32) -> [u8; 4] {
-    assert!(player_id <= 16, "score_tracker.to_bytes() -- Error: Expected player of id <= 16 but got {} instead", player_id);
-    debug_assert!(game_mode <= 16, "score_tracker.to_bytes() -- Error: Expected game_mode of id <= 16 but got {} instead", game_mode);
+// a finished run as stored on the score lists
+#[derive(Debug, Clone, Copy)]
+pub struct ScoreRecord {
+
+    // id of the profile, 0 for the guest
+    pub player: u32,
+    pub game_mode: u8,
+    pub score: i32,
+
+    // seconds since the unix epoch when the run ended
+    pub timestamp: u64,
+
+    // frames played
+    pub duration: u32,
+    pub lines: u32,
+    pub level: u32,
+    pub pieces: u32,
+    pub max_combo: u32,
+
+    // seed of the bag, dealing the same Tetrominoes again
+    pub seed: u64,
+
+    // line clears by the number of rows cleared at once (see GameStats), and the garbage they'd send
+    pub clears: [u32; 5],
+    pub attack: u32,
+}
 
-    // get the score as be_bytes
-    let mut bytes = (score / 10).to_be_bytes();
+impl ScoreRecord {
+
+    // creates the record of the given Game, ending now
+    pub fn new (player: u32, game_mode: u8, game: &Game) -> Self {
+        Self {
+            player,
+            game_mode,
+            score: game.score,
+            timestamp: match std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) { Ok(time) => time.as_secs(), Err(_) => 0 },
+            duration: game.stats.frames,
+            lines: game.stats.lines,
+            level: game.stats.level(),
+            pieces: game.stats.pieces,
+            max_combo: game.stats.max_combo,
+            seed: game.seed,
+            clears: game.stats.clears,
+            attack: game.stats.attack,
+        }
+    }
 
-    // replace the first u8 element with 4 bytes for the player and 4 bytes for the gamemode
-    bytes[0] = (player_id << 4) + game_mode;
+    // pieces placed per second of play, the game runs at 60 frames per second
+    pub fn pps (&self) -> f32 {
+        if self.duration == 0 { return 0.0 }
+        self.pieces as f32 * 60.0 / self.duration as f32
+    }
 
-    // return the result
+    // attack per minute of play
+    pub fn apm (&self) -> f32 {
+        if self.duration == 0 { return 0.0 }
+        self.attack as f32 * 3600.0 / self.duration as f32
+    }
+}
+
+// formats the given record: player id, game mode and the full score, then the timestamp, duration, lines, level,
+// pieces, max combo and seed, then the line clears and attack
+fn to_bytes (record: &ScoreRecord) -> Vec<u8> {
+    let mut bytes = record.player.to_be_bytes().to_vec();
+    bytes.push(record.game_mode);
+    bytes.extend(record.score.to_be_bytes());
+    bytes.extend(record.timestamp.to_be_bytes());
+    for value in [record.duration, record.lines, record.level, record.pieces, record.max_combo] { bytes.extend(value.to_be_bytes()) }
+    bytes.extend(record.seed.to_be_bytes());
+    for value in record.clears { bytes.extend(value.to_be_bytes()) }
+    bytes.extend(record.attack.to_be_bytes());
     bytes
 }
 
+// reads a score record, skipping the ones missing any field
+fn from_bytes (record: &[u8]) -> Option<ScoreRecord> {
+    let mut reader = RecordReader::new(record);
+    let mut score = ScoreRecord {
+        player: reader.u32()?,
+        game_mode: reader.u8()?,
+        score: reader.i32()?,
+        timestamp: reader.u64()?,
+        duration: reader.u32()?,
+        lines: reader.u32()?,
+        level: reader.u32()?,
+        pieces: reader.u32()?,
+        max_combo: reader.u32()?,
+        seed: reader.u64()?,
+        clears: [0; 5],
+        attack: 0,
+    };
+    for clears in score.clears.iter_mut() { *clears = reader.u32()? }
+    score.attack = reader.u32()?;
+    Some(score)
+}
+
+// a record of a score from before the versioned format, which only kept the player, game mode and score
+fn legacy_record (player: u32, game_mode: u8, score: i32) -> ScoreRecord {
+    ScoreRecord { player, game_mode, score, timestamp: 0, duration: 0, lines: 0, level: 0, pieces: 0, max_combo: 0, seed: 0, clears: [0; 5], attack: 0 }
+}
+
 // stores the given score on history and recalculates the best scores considering the new entry
-pub fn track_score (profile: u8, game_mode: u8, score: i32) {
+pub fn track_score (record: ScoreRecord) {
 
     // save score on history
-    match save_score(profile, game_mode, score) {
-        Err(e) => println!("track_score: save_score({}, {}, {}) -- Erro: {}", profile, game_mode, score, e),
-        _=> {}
+    if let Err(e) = save_score(&record) {
+        println!("track_score: save_score({}, {}, {}) -- Erro: {}", record.player, record.game_mode, record.score, e)
     }
 
     // update best scores
-    match update_best(profile, game_mode, score) {
-        Err(e) => println!("track_score: update_best({}, {}, {}) -- Erro: {}", profile, game_mode, score, e),
-        _=> {}
+    if let Err(e) = update_best(&record) {
+        println!("track_score: update_best({}, {}, {}) -- Erro: {}", record.player, record.game_mode, record.score, e)
     }
 }
 
 
-// updates the best scores of given player
-pub fn update_best (player: u8, game_mode: u8, score: i32) -> Result<(), std::io::Error>{
+// updates the best scores of the player and game mode of the given record
+pub fn update_best (record: &ScoreRecord) -> Result<(), std::io::Error>{
 
-    if score == 0 { return Ok(()) }
+    if record.score == 0 { return Ok(()) }
 
-    // loads best scores and match result
-    let scores = match load_scores(PATH_BEST) {
-        
-        // best scores loaded successfully
-        Ok(scores) => scores,
-        
-        // best scores file not initialized
-        _=> vec![]
-    };
-
-    // convert the new entry to bytes
-    let new_score_bytes = to_bytes(player, game_mode, score);
+    // loads best scores, the file may not be initialized yet. A file that can't be read is left as it is
+    let mut scores = load_existing_scores(PATH_BEST)?;
 
-    // initialize a list for the new best scores
-    let mut new_scores = vec![];
+    // the new score goes before the equal ones
+    let index = scores.iter().position(|s| s.score <= record.score).unwrap_or(scores.len());
+    scores.insert(index, *record);
 
-    // initialize a bool to define if the new score was added
-    let mut added: bool = false;
+    // overwrite the file with the new lists
+    write_best(scores)
+}
 
-    // loop through the scores
-    for i in 0..scores.len().min(MAX_BEST_LENGTH) {
+// writes the best scores, sorted and cut to the maximum length of each game mode and profile
+fn write_best (scores: Vec<ScoreRecord>) -> Result<(), std::io::Error> {
+    write_records(PATH_BEST, VERSION, &keep_best(scores).iter().map(to_bytes).collect::<Vec<Vec<u8>>>())
+}
 
-        new_scores.push(to_bytes(scores[i].0, scores[i].1, scores[i].2));
+// sorts the scores from the best to the worst, keeping the best ones of each game mode and profile
+fn keep_best (mut scores: Vec<ScoreRecord>) -> Vec<ScoreRecord> {
+    scores.sort_by_key(|s| std::cmp::Reverse(s.score));
+    let mut counts: Vec<((u32, u8), usize)> = vec![];
+    scores.into_iter().filter(|s| {
+        if s.score <= 0 { return false }
+        let count = match counts.iter_mut().find(|(key, _)| *key == (s.player, s.game_mode)) {
+            Some((_, count)) => count,
+            None => { counts.push(((s.player, s.game_mode), 0)); &mut counts.last_mut().unwrap().1 }
+        };
+        *count += 1;
+        *count <= MAX_BEST_LENGTH
+    }).collect()
+}
 
-        // if the score at the index is greater than the given score, skip
-        if scores[i].2 > score || added { continue; }
+// append the score to the history
+pub fn save_score (record: &ScoreRecord) -> Result<(), std::io::Error> {
 
-        // new score is greater or equal to current score, insert the new score at this index
-        new_scores.insert(i, new_score_bytes);
+    // append the score record to the history
+    append_record(PATH_HISTORY, VERSION, to_bytes(record))
+}
 
-        added = true;
-    }
+// loads all scores on given file
+fn load_scores (path: &str) -> Result<Vec<ScoreRecord>, std::io::Error> {
 
-    if !added && scores.len() < MAX_BEST_LENGTH { new_scores.push(new_score_bytes) }
+    // loads the records and match result
+    match read_records(path) {
 
-    // write nothing to the file to overwrite
-    let _ = write_binary(PATH_BEST, new_scores[0]);
+        // file is loaded successfully, read each record
+        Ok(file) => Ok(file.records.iter().enumerate().filter_map(|(i, record)| match from_bytes(record) {
+            Some(score) => Some(score),
+            None => { println!("score_tracker::load_scores({}) -- Error: record {} is too short to be a score, skipped", path, i); None }
+        }).collect()),
 
-    // append each score 
-    for i in 1..new_scores.len() {
-        let _ = append_binary(PATH_BEST, new_scores[i]);
+        // error loading the file
+        Err(e) => Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}: {}", path, e)))
     }
+}
 
-    // update successfull
-    Ok(())
-
-
+/// returns the highest profile id found on the scores, including the archived ones of deleted profiles
+pub fn highest_player () -> u32 {
+    [PATH_HISTORY, PATH_BEST, PATH_ARCHIVE].iter()
+        .flat_map(|path| load_scores(path).unwrap_or_default())
+        .map(|score| score.player)
+        .max().unwrap_or(0)
 }
 
-// append the score to the history
-pub fn save_score (player: u8, game_mode: u8, score: i32) -> Result<(), std::io::Error> {
+// loads all scores on given file, a missing file holding none. Other errors are returned so the file isn't overwritten
+fn load_existing_scores (path: &str) -> Result<Vec<ScoreRecord>, std::io::Error> {
+    match binary_exists(path) {
+        true => load_scores(path),
+        false => Ok(vec![]),
+    }
+}
 
-    // append the score to the binary at path 
-    append_binary(PATH_HISTORY, to_bytes(player, game_mode, score))
+// records of the scores from a file written before the versioned format
+fn legacy_scores (buffer: &[u8]) -> Vec<Vec<u8>> {
+    buffer.chunks_exact(4).map(|bytes| to_bytes(&legacy_record(
+        (bytes[0] >> 4) as u32,
+        bytes[0] & 0x0F,
+        i32::from_be_bytes([0, bytes[1], bytes[2], bytes[3]]) * 10,
+    ))).collect()
 }
 
-// loads all scores on given file
-fn load_scores (path: &str) -> Result<Vec<(u8, u8, i32)>, std::io::Error> {
+// converts the score files from before the versioned format, where each score took 4 bytes:
+// 4 bits for the player, 4 bits for the game mode and the score divided by 10 on the 3 remaining bytes
+pub fn migrate_scores () {
+    migrate_legacy(PATH_HISTORY, VERSION, legacy_scores);
 
-    // loads the binary and match result
-    match load_binary(path) {
+    // the best scores were a single list, the scores pushed out of it are recovered from the history
+    fn legacy_best (buffer: &[u8]) -> Vec<Vec<u8>> {
+        let mut scores: Vec<ScoreRecord> = legacy_scores(buffer).iter().filter_map(|record| from_bytes(record)).collect();
+        merge(&mut scores, &load_scores(PATH_HISTORY).unwrap_or_default());
+        keep_best(scores).iter().map(to_bytes).collect()
+    }
+    migrate_legacy(PATH_BEST, VERSION, legacy_best);
+}
 
-        // file is loaded successfully
-        Ok(buffer) => {
+// moves the scores of the given player out of the history and best scores, into the archive
+pub fn archive_scores (player: u32) -> Result<(), std::io::Error> {
 
-            // create a vec of tuple for player, gamemode and score
-            let mut list = vec![]; 
+    let history = load_existing_scores(PATH_HISTORY)?;
+    let (archived, kept): (Vec<ScoreRecord>, Vec<ScoreRecord>) = history.into_iter().partition(|s| s.player == player);
+    if archived.is_empty() { return Ok(()) }
 
-            // loop through the loaded buffer with increments of 4 u8
-            for i in 0..buffer.len() / 4 {
+    let mut archive = match read_records(PATH_ARCHIVE) {
+        Ok(file) => file.records,
+        Err(DataFileError::Missing) => vec![],
+        Err(e) => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}: {}", PATH_ARCHIVE, e)))
+    };
+    archive.extend(archived.iter().map(to_bytes));
+    write_records(PATH_ARCHIVE, VERSION, &archive)?;
+    write_records(PATH_HISTORY, VERSION, &kept.iter().map(to_bytes).collect::<Vec<Vec<u8>>>())?;
 
-                // add the following tuple to the list
-                list.push((
+    // the best scores are a subset of the history, the archived ones are simply dropped
+    let best = load_existing_scores(PATH_BEST)?;
+    write_best(best.into_iter().filter(|s| s.player != player).collect())
+}
 
-                    // player: first 4 bytes of first u8
-                    buffer[i * 4] >> 4,
+// merges the given records into the history and best scores, returning how many were added to each. Records already on
+// a list aren't added again, so importing the same scores twice changes nothing
+pub fn merge_scores (history: &[ScoreRecord], best: &[ScoreRecord]) -> Result<(usize, usize), std::io::Error> {
 
-                    // game_mode: last 4 bytes of first u8
-                    (buffer[i * 4] << 4) >> 4,
+    // the history stays in the order the runs ended. Lists that can't be read are left as they are
+    let mut scores = load_existing_scores(PATH_HISTORY)?;
+    let history_added = merge(&mut scores, history);
+    scores.sort_by_key(|s| s.timestamp);
+    write_records(PATH_HISTORY, VERSION, &scores.iter().map(to_bytes).collect::<Vec<Vec<u8>>>())?;
 
-                    // score: the 3 remaining u8
-                    i32::from_be_bytes([0, buffer[(i * 4) + 1], buffer[(i * 4) + 2], buffer[(i * 4) + 3]]) * 10
-                ))
-            }
+    // the best scores are sorted again and cut to the maximum length
+    let mut scores = load_existing_scores(PATH_BEST)?;
+    let best_added = merge(&mut scores, &best.iter().filter(|s| s.score > 0).copied().collect::<Vec<ScoreRecord>>());
+    write_best(scores)?;
 
-            // return the result
-            Ok(list)
-        },
+    Ok((history_added, best_added))
+}
 
-        // error loading binary file
-        Err(e) => Err(e)
+// adds the records missing from the list, a record appearing twice on both sides is only added once
+fn merge (list: &mut Vec<ScoreRecord>, records: &[ScoreRecord]) -> usize {
+    let key = |s: &ScoreRecord| (s.player, s.game_mode, s.score, s.timestamp, s.duration, s.seed);
+    let mut present: Vec<_> = list.iter().map(key).collect();
+    let mut added = 0;
+    for record in records {
+        match present.iter().position(|k| *k == key(record)) {
+            Some(i) => { present.swap_remove(i); },
+            None => { list.push(*record); added += 1 }
+        }
     }
+    added
+}
+
+// formats a unix timestamp as an UTC date ("YYYY-MM-DD HH:MM")
+pub fn format_date (timestamp: u64) -> String {
+    if timestamp == 0 { return "unknown date".to_string() }
+
+    // convert the days since the epoch to a civil date
+    let days = (timestamp / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    let minutes = timestamp % 86400 / 60;
+    format!("{}-{:02}-{:02} {:02}:{:02}", year, month, day, minutes / 60, minutes % 60)
 }
 
 // loads and filters a list of scores at given path
-fn load_filter (path: &str, player: Option<u8>, game_mode: Option<u8>) -> Result<Vec<(u8, u8, i32)>, std::io::Error> {
+fn load_filter (path: &str, player: Option<u32>, game_mode: Option<u8>) -> Result<Vec<ScoreRecord>, std::io::Error> {
     match load_scores(path) {
         Ok(mut scores) => {
             
             // match filter params and filters list if given
-            match player { Some(p) => { scores = scores.drain(..).filter(|s| s.0 == p).collect(); }, _=>{} }
-            match game_mode { Some(gm) => { scores = scores.drain(..).filter(|s| s.1 == gm).collect(); }, _=>{} }
+            if let Some(p) = player { scores.retain(|s| s.player == p) }
+            if let Some(gm) = game_mode { scores.retain(|s| s.game_mode == gm) }
             Ok(scores)
 
         },
@@ -154,17 +316,52 @@ fn load_filter (path: &str, player: Option<u8>, game_mode: Option<u8>) -> Result
 }
 
 // loads history and filters the list if player and/or game_mode params are given
-pub fn load_history (player: Option<u8>, game_mode: Option<u8>) -> Result<Vec<(u8, u8, i32)>, std::io::Error> {
+pub fn load_history (player: Option<u32>, game_mode: Option<u8>) -> Result<Vec<ScoreRecord>, std::io::Error> {
     match load_filter(PATH_HISTORY, player, game_mode) {
         Ok(scores) => Ok(scores),
         _=> Ok(vec![])
     }
 }
 
-// loads best and filters the list if player and/or game_mode params are given
-pub fn load_best (player: Option<u8>, game_mode: Option<u8>) -> Result<Vec<(u8, u8, i32)>, std::io::Error> {
+// loads best and filters the list if player and/or game_mode params are given. The list of a game mode without a
+// player is the overall one, the best of every profile cut to the maximum length
+pub fn load_best (player: Option<u32>, game_mode: Option<u8>) -> Result<Vec<ScoreRecord>, std::io::Error> {
     match load_filter(PATH_BEST, player, game_mode) {
-        Ok(scores) => Ok(scores),
+        Ok(mut scores) => { if game_mode.is_some() { scores.truncate(MAX_BEST_LENGTH) } Ok(scores) },
         _ => Ok(vec![])
     }
 }
+
+/// loads every record of the history and of the best scores, for exporting them. Unlike load_history and load_best,
+/// a file that can't be read is an error instead of holding no scores, only a missing one holds none
+pub fn load_all_scores () -> Result<(Vec<ScoreRecord>, Vec<ScoreRecord>), std::io::Error> {
+    Ok((load_existing_scores(PATH_HISTORY)?, load_existing_scores(PATH_BEST)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_scores_keep_player_game_mode_and_score () {
+
+        // player 2 on versus with 2580 points, then a partial score that's dropped
+        let records = legacy_scores(&[0x21, 0x00, 0x01, 0x02, 0x10, 0x00]);
+        assert_eq!(records.len(), 1);
+        let record = from_bytes(&records[0]).expect("score record");
+        assert_eq!((record.player, record.game_mode, record.score), (2, 1, 2580));
+        assert_eq!((record.timestamp, record.lines, record.attack), (0, 0, 0));
+    }
+
+    #[test]
+    fn merge_adds_only_missing_records () {
+        let score = |player: u32, score: i32| legacy_record(player, 0, score);
+        let mut list = vec![score(1, 100)];
+
+        // the same run imported twice is kept twice, minus the one already on the list
+        let added = merge(&mut list, &[score(1, 100), score(1, 100), score(2, 100)]);
+        assert_eq!(added, 2);
+        assert_eq!(list.len(), 3);
+        assert_eq!(merge(&mut list, &[score(1, 100), score(2, 100)]), 0);
+    }
+}
@@ -1,6 +1,7 @@
 use doryen_rs::{Engine, DoryenApi, UpdateEvent};
-use crate::states:: {MainMenu, Profiles, GameMode, Scores, Settings};
-use crate::{RustyEngine, Controller, RTColor, profile_tracker, clear};
+use crate::states:: {MainMenu, Profiles, GameMode, Scores, Stats, Settings, Summary};
+use std::time::{SystemTime, Instant, Duration};
+use crate::{RustyEngine, Controller, RTColor, profile_tracker, config_tracker, clear};
 
 // wrapper for state
 pub enum GameState {
@@ -8,7 +9,9 @@ pub enum GameState {
     Profiles(Profiles),
     Game(GameMode),
     Scores(Scores),
+    Stats(Stats),
     Settings(Settings),
+    Summary(Summary),
 }
 
 // logic implementation for GameState
@@ -20,15 +23,22 @@ impl GameState {
     pub fn singleplayer () -> Self { GameState::Game(GameMode::singleplayer()) }
     pub fn versus       () -> Self { GameState::Game(GameMode::versus()) }
     pub fn scores       () -> Self { GameState::Scores(Scores::new()) }
+    pub fn stats        () -> Self { GameState::Stats(Stats::new()) }
     pub fn settings     () -> Self { GameState::Settings(Settings::new()) }
+    pub fn summary      (game_mode: GameMode) -> Self { GameState::Summary(Summary::new(game_mode)) }
 }
 
 // defines events to be returned by the GameStates to the StateHandler 
 pub enum GameEvent {
-    SetProfile(usize),
+    SetProfile(u32),
+    SetVersusProfile(u32),
     State(GameState),
+
+    // shows the given state over the current one, which is brought back as it was on PreviousState
+    Suspend(GameState),
     PreviousState,
     GameOver,
+    Retry,
     Exit,
 }
 
@@ -39,6 +49,7 @@ impl GameEvent {
     pub fn new_game         () -> Self { GameEvent::State(GameState::singleplayer()) }
     pub fn new_game_versus  () -> Self { GameEvent::State(GameState::versus()) }
     pub fn scores           () -> Self { GameEvent::State(GameState::scores()) }
+    pub fn stats            () -> Self { GameEvent::State(GameState::stats()) }
     pub fn settings         () -> Self { GameEvent::State(GameState::settings()) }
 }
 
@@ -50,7 +61,9 @@ impl GameState {
             Self::Profiles(state)  => state.init(),
             Self::Game(state)      => state.init(),
             Self::Scores(state)      => state.init(),
+            Self::Stats(state)     => state.init(),
             Self::Settings(state)  => state.init(),
+            Self::Summary(state)   => state.init(),
         }
     }
     fn update(&mut self, api: &mut dyn DoryenApi) -> (Option<GameEvent>, Option<UpdateEvent>) {
@@ -59,7 +72,17 @@ impl GameState {
             Self::Profiles(state)  => state.update(api),
             Self::Game(state)      => state.update(api),
             Self::Scores(state)      => state.update(api),
+            Self::Stats(state)     => state.update(api),
             Self::Settings(state)  => state.update(api),
+            Self::Summary(state)   => state.update(api),
+        }
+    }
+    // applies the changes made to the config files to the state
+    fn reload_config(&mut self) {
+        match self {
+            Self::Game(state)      => state.reload_config(),
+            Self::Settings(state)  => state.reload_config(),
+            _=> {}
         }
     }
     fn render(&mut self, api: &mut dyn DoryenApi) {
@@ -69,7 +92,9 @@ impl GameState {
             Self::Profiles(state)  => state.render(api),
             Self::Game(state)      => state.render(api),
             Self::Scores(state)      => state.render(api),
+            Self::Stats(state)     => state.render(api),
             Self::Settings(state)  => state.render(api),
+            Self::Summary(state)   => state.render(api),
         }
     }
 }
@@ -79,25 +104,70 @@ pub struct StateHandler {
     pub state: GameState,
     pub previous_state: Option<GameState>,
     pub controller: Controller,
-    pub profile: u8,
+    pub profile: u32,
+    pub versus_profile: u32,
+
+    // last modification of the config files, checked every CONFIG_CHECK_FRAMES to apply their changes,
+    // and the errors found on them, shown over every state until they're fixed
+    config_stamp: Vec<Option<SystemTime>>,
+    config_errors: Vec<String>,
+    config_timer: u8,
+
+    // time the last update ended, to tell when the window stopped being updated
+    last_update: Instant,
 }
 
+// frames between each check for changes on the config files
+const CONFIG_CHECK_FRAMES: u8 = 30;
+
+// time without updates after which the window is taken to be minimized or hidden
+const FOCUS_LOST_GAP: Duration = Duration::from_millis(250);
+
 // logic implementation for StateHandler
 impl StateHandler {
 
     // creates the StateHandler
     pub fn new () -> Self {
+        let profile = profile_tracker::load_profile().unwrap_or(0);
+        let versus_profile = profile_tracker::load_versus_profile().unwrap_or(0);
         Self {
             state: GameState::main_menu(), 
             previous_state: None, 
             controller: Controller::default(),
-            profile: match profile_tracker::load_profile() { 
-                Some(profile) => profile as u8, 
-                None => 0 
-            }
+            profile,
+            versus_profile,
+            config_stamp: config_tracker::config_stamp(&[profile, versus_profile]),
+            config_errors: config_tracker::config_errors(&[profile, versus_profile]).iter().map(|err| err.to_string()).collect(),
+            config_timer: 0,
+            last_update: Instant::now(),
         }
     }
 
+    // reloads the config files if they changed since the last check, reporting their errors.
+    // The state keeps it's settings while there's an error, the values that were valid are applied once it's fixed
+    fn check_config(&mut self) {
+        self.config_timer += 1;
+        if self.config_timer < CONFIG_CHECK_FRAMES { return }
+        self.config_timer = 0;
+
+        let profiles = [self.profile, self.versus_profile];
+        let stamp = config_tracker::config_stamp(&profiles);
+        if stamp == self.config_stamp { return }
+        self.config_stamp = stamp;
+
+        self.config_errors = config_tracker::config_errors(&profiles).iter().map(|err| err.to_string()).collect();
+        if self.config_errors.is_empty() { self.state.reload_config() }
+    }
+
+    // doryen-rs doesn't report the focus of the window, so this isn't focus detection: it pauses on a stall between
+    // updates instead, as most platforms stop redrawing a window that's minimized or hidden, or while it's dragged.
+    // Alt-tabbing away from a window that keeps running isn't noticed. Only the time outside of the updates counts,
+    // so the files written by an update don't pause the run. The run being played is paused before it's next update
+    fn check_focus(&mut self) {
+        if self.last_update.elapsed() < FOCUS_LOST_GAP { return }
+        if let GameState::Game(game_mode) = &mut self.state { game_mode.focus_lost() }
+    }
+
     // Sets the state of the StateHandler
     pub fn set_state(&mut self, state: GameState) {
         self.state = state;
@@ -105,6 +175,14 @@ impl StateHandler {
     }
 
     fn previous_state(&mut self) {
+
+        // a run suspended by the pause menu is resumed as it was, with the settings changed meanwhile
+        if let Some(GameState::Game(mut game_mode)) = self.previous_state.take() {
+            game_mode.reload_config();
+            self.state = GameState::Game(game_mode);
+            return
+        }
+
         let state = match &self.previous_state {
             Some(state) => match state {
                 GameState::Game(gamemode) => match gamemode {
@@ -114,19 +192,32 @@ impl StateHandler {
                 GameState::MainMenu(_) => GameState::main_menu(),
                 GameState::Profiles(_) => GameState::profiles(),
                 GameState::Scores(_) => GameState::scores(),
+                GameState::Stats(_) => GameState::stats(),
                 GameState::Settings(_) => GameState::settings(),
+                GameState::Summary(_) => GameState::main_menu(),
             },
             None => GameState::main_menu()
         
         };
+
+        // the profiles of the session may have been deleted by the previous state
+        self.profile = profile_tracker::load_profile().unwrap_or(0);
+        self.versus_profile = profile_tracker::load_versus_profile().unwrap_or(0);
+
         self.set_state(state);
     } 
 
     // Sets the current profile 
-    fn set_profile (&mut self, profile: usize) {
-        self.profile = profile as u8;
+    fn set_profile (&mut self, profile: u32) {
+        self.profile = profile;
         profile_tracker::set_profile(profile)
     }
+
+    // Sets the profile playing the second side of versus
+    fn set_versus_profile (&mut self, profile: u32) {
+        self.versus_profile = profile;
+        profile_tracker::set_versus_profile(profile)
+    }
 }
 
 // doryen-rs engine implementation for StateHandler. Redirects the engine methods to the state
@@ -145,6 +236,12 @@ impl Engine for StateHandler {
     // updates the current state
     fn update(&mut self, api: &mut dyn DoryenApi) -> Option<UpdateEvent> {
 
+        // apply the changes made to the config files
+        self.check_config();
+
+        // pause the run if the window stopped being updated
+        self.check_focus();
+
         // update the state and store the result
         let state_update_result = self.state.update(api);
         
@@ -156,17 +253,34 @@ impl Engine for StateHandler {
 
                 // state requests the profile set
                 GameEvent::SetProfile(profile) => self.set_profile(profile),
+                GameEvent::SetVersusProfile(profile) => self.set_versus_profile(profile),
                 
                 // state returns a redirect to another state
                 GameEvent::State(state) => self.set_state(state),
 
+                // state requests to show another state over it, keeping itself to be brought back
+                GameEvent::Suspend(state) => {
+                    self.previous_state = Some(std::mem::replace(&mut self.state, state));
+                    self.state.init();
+                },
+
                 // state requests to move to the previous state
                 GameEvent::PreviousState => self.previous_state(),
 
-                // state alerts that the player lost the game
-                GameEvent::GameOver => match &self.state {
-                    GameState::Game(game) => { game.game_over(self.profile); self.set_state(GameState::main_menu()) },
-                    _=> {} 
+                // state alerts that the player lost the game: the run is summed up, unless it was quit to be continued later
+                GameEvent::GameOver => if let GameState::Game(_) = &self.state {
+                    if let GameState::Game(game_mode) = std::mem::replace(&mut self.state, GameState::main_menu()) {
+                        match game_mode.quit() {
+                            true => { game_mode.game_over(); self.set_state(GameState::main_menu()) },
+                            false => self.set_state(GameState::summary(game_mode)),
+                        }
+                    }
+                }
+
+                // state requests to start the game mode over: a run that's over counts it's scores, one in progress is dropped
+                GameEvent::Retry => if let GameState::Game(game_mode) = &self.state {
+                    if game_mode.over() { game_mode.game_over() }
+                    self.set_state(GameState::Game(game_mode.restart()))
                 }
 
                 // state returns an UpdateEvent::Exit to quit the application 
@@ -180,6 +294,7 @@ impl Engine for StateHandler {
         }
 
         // return the second value of the state update
+        self.last_update = Instant::now();
         state_update_result.1
     }
 
@@ -187,9 +302,24 @@ impl Engine for StateHandler {
     fn render(&mut self, api: &mut dyn DoryenApi) {
         self.state.render(api);
         match &self.state {
-            GameState::MainMenu(m) => m.render_playing_as(api.con(), self.profile as usize),
+            GameState::MainMenu(m) => m.render_playing_as(api.con(), self.profile, self.versus_profile),
             _=> {}
         }
+
+        // errors on the config files, wrapped at the bottom of the screen
+        if !self.config_errors.is_empty() {
+            use crate::{CONSOLE_WIDTH, CONSOLE_HEIGHT};
+            let con = api.con();
+            let lines: Vec<String> = self.config_errors.iter().flat_map(|err| {
+                let chars: Vec<char> = err.chars().collect();
+                chars.chunks(CONSOLE_WIDTH as usize - 2).map(String::from_iter).collect::<Vec<String>>()
+            }).collect();
+            let top = CONSOLE_HEIGHT as i32 - lines.len() as i32 - 2;
+            con.rectangle(0, top, CONSOLE_WIDTH, lines.len() as u32 + 2, Some(RTColor::Red.u8()), Some(RTColor::Black.u8()), Some(' ' as u16));
+            for (i, line) in lines.iter().enumerate() {
+                con.print(1, top + 1 + i as i32, line, doryen_rs::TextAlign::Left, Some(RTColor::White.u8()), None);
+            }
+        }
     }
     
 }
\ No newline at end of file
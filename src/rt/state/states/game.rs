@@ -1,4 +1,4 @@
-use crate::{Bag, HasBag, data::*, Controller, InputHandler, RoutineHandler, config_tracker};
+use crate::{Bag, HasBag, data::*, Controller, InputID, InputHandler, ReplayFrame, RoutineHandler, config_tracker, achievement_tracker, fit_block_scale, r_playfield_size_x, r_playfield_size_y};
 extern crate doryen_rs; use doryen_rs::Console;
 
 use crate::DEBUG_MOVEMENT;
@@ -8,45 +8,196 @@ const DEFAULT_MOVE_Y_COOLDOWN: u32 = 240;
 pub const RESET_MOVE_INTENT_MANUAL: (i8, i8) = (0, 0);
 pub const RESET_MOVE_INTENT_AUTO: (i8, i8) = (0, 4);
 
-// sizes of the playfield array
-pub const PLAYFIELD_WIDTH: u8 = 10;
-pub const PLAYFIELD_HEIGHT: u8 = 24;
+// default sizes of the visible playfield, each Game picks it's own through BoardSize
+pub const DEFAULT_PLAYFIELD_WIDTH: u8 = 10;
+pub const DEFAULT_PLAYFIELD_HEIGHT: u8 = 20;
 
-// defines the size of each block of a Tetromino
+// defines the biggest size of each block of a Tetromino, bigger boards are rendered with smaller blocks
 pub const BLOCK_SCALE: u8 = 2;
 
+// frames the cleared rows flash before collapsing, and frames between a Tetromino locking down and the next one spawning (ARE)
+pub const LINE_CLEAR_DELAYS: [u8; 4] = [0, 10, 20, 40];
+pub const ENTRY_DELAYS: [u8; 5] = [0, 6, 12, 18, 30];
+pub const DEFAULT_LINE_CLEAR_DELAY: u8 = 20;
+pub const DEFAULT_ENTRY_DELAY: u8 = 6;
+
+// the rules a Game is played with, chosen for each game mode on the Settings
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct GameRules {
+    pub rotation_system: RotationSystemID,
+    pub piece_set: PieceSet,
+    pub board_size: BoardSize,
+    pub line_clear_delay: u8,
+    pub entry_delay: u8,
+
+    // holding a rotate key during the delays rotates the next Tetromino as it spawns (IRS)
+    pub initial_rotation: bool,
+}
+
+impl Default for GameRules {
+    fn default () -> Self {
+        Self {
+            rotation_system: RotationSystemID::Srs,
+            piece_set: PieceSet::Tetrominoes,
+            board_size: BoardSize::Standard,
+            line_clear_delay: DEFAULT_LINE_CLEAR_DELAY,
+            entry_delay: DEFAULT_ENTRY_DELAY,
+            initial_rotation: false,
+        }
+    }
+}
+
+// the delays between a Tetromino locking down and the next one spawning, while there's no Tetromino to control
+pub enum EntryDelay {
+
+    // the full rows (indexes on the playfield) flash for the given number of frames left before being cleared
+    LineClear(Vec<usize>, u8),
+
+    // ARE: number of frames left until the next Tetromino spawns
+    Spawn(u8),
+}
+
+// statistics of a run, stored with it's score
+#[derive(Debug, Default, Clone, Copy)]
+pub struct GameStats {
+
+    // frames played, not counting the ones spent paused or before the run starts
+    pub frames: u32,
+    pub lines: u32,
+    pub pieces: u32,
+
+    // consecutive Tetrominoes that cleared rows, and the longest of those streaks
+    pub combo: u32,
+    pub max_combo: u32,
+
+    // line clears by the number of rows cleared at once: singles, doubles, triples, tetrises and 5 rows (pentominoes)
+    pub clears: [u32; 5],
+
+    // garbage the clears would send on a guideline versus game, versus doesn't send it yet
+    pub attack: u32,
+
+    // consecutive difficult clears (Tetrises or more rows, and T-Spins) without an easier clear between them,
+    // and the longest of those chains
+    pub back_to_back: u32,
+    pub max_back_to_back: u32,
+
+    // Tetrominoes placed of each kind, by their index on TetrominoID::all()
+    pub placed: [u32; 19],
+}
+
+// garbage sent by clearing 1 to 5 rows at once, by clearing 1 to 3 rows with a T-Spin, and the bonus of a back to back clear
+const ATTACK: [u32; 5] = [0, 1, 2, 4, 6];
+const T_SPIN_ATTACK: [u32; 3] = [2, 4, 6];
+const BACK_TO_BACK_ATTACK: u32 = 1;
+
+// frames an unlocked achievement is shown for
+const TOAST_FRAMES: u16 = 180;
+
+// frames of the countdown before a run starts, a second for each number, and frames "Go!" is shown after it
+pub const COUNTDOWN_FRAMES: u8 = 180;
+pub const GO_FRAMES: u32 = 40;
+
+impl GameStats {
+    // the level goes up every 10 cleared lines
+    pub fn level (&self) -> u32 { 1 + self.lines / 10 }
+
+    // counts a line clear of given number of rows, made with a T-Spin or not
+    pub fn count_clear (&mut self, rows: usize, t_spin: bool) {
+        if rows == 0 { return }
+        let index = rows.min(self.clears.len()) - 1;
+        self.clears[index] += 1;
+        self.attack += match t_spin { true => T_SPIN_ATTACK[index.min(2)], false => ATTACK[index] };
+
+        // an easier clear breaks the back to back chain
+        if rows < 4 && !t_spin { self.back_to_back = 0; return }
+        if self.back_to_back > 0 { self.attack += BACK_TO_BACK_ATTACK }
+        self.back_to_back += 1;
+        self.max_back_to_back = self.max_back_to_back.max(self.back_to_back);
+    }
+}
+
 // struct that handles the functionallities of the pause menu
 pub struct PauseMenu {
     pub cursor: usize,
+
+    // who paused the run on versus, shown on the menu
+    pub paused_by: Option<String>,
+
+    // true while the key bindings are listed over the menu
+    pub controls: bool,
+}
+
+#[derive(Clone, Copy)]
+pub enum PauseMenuAction { Continue, Restart, Settings, Controls, Exit }
+impl PauseMenuAction {
+    pub fn all () -> [PauseMenuAction; 5] {
+        [PauseMenuAction::Continue, PauseMenuAction::Restart, PauseMenuAction::Settings, PauseMenuAction::Controls, PauseMenuAction::Exit]
+    }
+    pub fn text (&self) -> &'static str {
+        match self {
+            PauseMenuAction::Continue => "Continue",
+            PauseMenuAction::Restart => "Restart",
+            PauseMenuAction::Settings => "Settings",
+            PauseMenuAction::Controls => "Controls",
+            PauseMenuAction::Exit => "Exit",
+        }
+    }
 }
-pub enum PauseMenuAction { Exit, Continue }
+
 impl PauseMenu {
-    fn new () -> Self { Self { cursor: 1 } }
+    pub fn new (paused_by: Option<String>) -> Self { Self { cursor: 0, paused_by, controls: false } }
     pub fn move_cursor (&mut self, dir: i8) {
-        self.cursor = ((((self.cursor as i8 + dir) % 2) + 2) % 2) as usize;
+        let len = PauseMenuAction::all().len() as i8;
+        self.cursor = ((((self.cursor as i8 + dir) % len) + len) % len) as usize;
     }
     pub fn action (&self) -> PauseMenuAction {
-        match self.cursor {
-            0 => PauseMenuAction::Exit,
-            _ => PauseMenuAction::Continue
+        PauseMenuAction::all()[self.cursor]
+    }
+}
+
+// reasons for a RustyTetris run to end
+pub enum GameOverReason {
+
+    // a new piece overlaps the blocks on the playfield when it spawns
+    BlockOut,
+
+    // a piece locks down completely above the visible playfield
+    LockOut,
+
+    // the player exits the run from the pause menu
+    Quit,
+}
+
+impl GameOverReason {
+    pub fn text (&self) -> &str {
+        match self {
+            GameOverReason::BlockOut => "Block out: no room to spawn",
+            GameOverReason::LockOut => "Lock out: locked above the field",
+            GameOverReason::Quit => "Quit",
         }
     }
 }
 
 // enum that defines the current state of a RustyTetris run
 pub enum RunState {
-    Start,
+
+    // frames left of the 3-2-1 countdown before the first Tetromino drops
+    Countdown(u8),
     Playing,
     Paused(PauseMenu),
-    Over,
+    Over(GameOverReason),
 }
 
 // Rusty Tetris engine definition
 pub struct Game {
-    pub playfield: [[Option<RTColor>; PLAYFIELD_HEIGHT as usize]; PLAYFIELD_WIDTH as usize],
+    pub playfield: Playfield,
     pub playfield_con: Option<Console>,
     pub move_y_cooldown: u32,
     pub bag_queue: Option<Bag>,
+
+    // seed of the bag, the same seed always deals the same Tetrominoes
+    pub seed: u64,
+    pub stats: GameStats,
     pub cur_tetromino: Option<Tetromino>,
     pub cur_con: Option<Console>,
     pub next_con: Option<Console>,
@@ -58,48 +209,76 @@ pub struct Game {
     pub inputmap: Vec::<crate::KeyMap>,
     pub routines: Vec::<crate::Routine>,
     pub controller: Controller,
+    pub rules: GameRules,
+    pub rotation_system: Box<dyn RotationSystem>,
+    pub entry_delay: Option<EntryDelay>,
+
+    // keys held during the entry delay, applied to the next Tetromino as it spawns
+    pub charged_shift: i8,
+    pub charged_rotation: Option<bool>,
+    pub block_scale: u8,
     pub player: usize,
 
+    // id of the profile playing this Game (0 for the guest) and it's preferences
+    pub profile: u32,
+    pub preferences: Preferences,
+
+    // true while the last move of the current Tetromino was a rotation, for T-Spins
+    pub rotated_last: bool,
+
+    // achievements the profile unlocked, and the ones unlocked during this run still shown with the frames left
+    pub achievements: Vec<Achievement>,
+    pub toasts: Vec<(Achievement, u16)>,
+
+    // whether this Game won the versus match, once both Games are over
+    pub won: Option<bool>,
+
+    // true while the other side of versus is paused, the Game isn't updated nor shown meanwhile
+    pub frozen: bool,
+
+    // keys of the controller on every frame played, with the seed they replay the run. A replay plays them back
+    // from the frame it's at instead of reading the keyboard
+    pub recording: Vec<ReplayFrame>,
+    pub resumed: bool,
+    pub replay: Option<usize>,
+
+    // DAS and ARR the keys were recorded with, None once they changed during the run as the replay can't follow them
+    pub handling: Option<(u8, u8)>,
 }
 
 // engine implementation
 impl Game {
 
-    // creates a blank playfield
-    fn create_playfield() -> [[Option<RTColor>; PLAYFIELD_HEIGHT as usize]; PLAYFIELD_WIDTH as usize] {
-        [[Default::default(); PLAYFIELD_HEIGHT as usize]; PLAYFIELD_WIDTH as usize]
-    }
-
-    // replaces the playfield
-    pub fn set_playfield (&mut self, new_playfield: [[Option<RTColor>; PLAYFIELD_HEIGHT as usize]; PLAYFIELD_WIDTH as usize]) {
-        self.playfield = new_playfield;
+    // create a new instance for the given profile
+    pub fn singleplayer (rules: GameRules, profile: u32) -> Self {
+        Self::new(0, profile, Some(config_tracker::get_controller(profile, 0)), rules)
     }
 
-    // create a new instance
-    pub fn singleplayer () -> Self {
-        Self::new(0, match config_tracker::get_controller(0) { Ok(c) => Some(c), Err(_) => panic!("states/game/Game::singleplayer() -- Error: invalid controller")})
-    }
-
-    // create a new instance for Some player
-    pub fn versus (player: usize) -> Self {
+    // create a new instance for Some player, played by the given profile
+    pub fn versus (player: usize, rules: GameRules, profile: u32) -> Self {
         println!("new rusty tetris instance for player {}", player);
-        Self::new(player, match config_tracker::get_controller(player) { Ok(c) => Some(c), Err(_) => panic!("states/game/Game::versus({}) -- Error: invalid controller", player)})
+        Self::new(player, profile, Some(config_tracker::get_controller(profile, player)), rules)
     }
     
-    // create a new instance with defined player
-    pub fn new (player: usize, controller: Option<Controller>) -> Self {
+    // create a new instance with defined player, using the preferences of the profile
+    pub fn new (player: usize, profile: u32, controller: Option<Controller>, rules: GameRules) -> Self {
+        let playfield = { let (width, height) = rules.board_size.dimensions(rules.piece_set); Playfield::new(width, height) };
+        let block_scale = fit_block_scale(playfield.width(), playfield.visible_height(), player != 0);
+        let preferences = config_tracker::get_preferences(profile);
         Self {
-            playfield: Self::create_playfield(),
-            playfield_con: Some(Console::new((PLAYFIELD_WIDTH * BLOCK_SCALE) as u32 + 2, (PLAYFIELD_HEIGHT * BLOCK_SCALE) as u32 + 2)),
+            playfield_con: Some(Console::new(r_playfield_size_x(playfield.width(), block_scale), r_playfield_size_y(playfield.visible_height(), block_scale))),
+            playfield,
             move_y_cooldown: DEFAULT_MOVE_Y_COOLDOWN,
             bag_queue: None,
+            seed: rand::random(),
+            stats: GameStats::default(),
             cur_tetromino: Default::default(),
             cur_con: None,
-            next_con: None,
+            next_con: Some(Console::new(6 * block_scale as u32, 8 * block_scale as u32)),
             cur_pos: (0, 0),
             move_intent: (0, 1),
             score: 0,
-            run_state: RunState::Start,
+            run_state: RunState::Countdown(COUNTDOWN_FRAMES),
             mouse_pos: (0.0,0.0),
             inputmap: vec![],
             routines: vec![],
@@ -107,10 +286,56 @@ impl Game {
                 Some(c) => c,
                 None => match player { 0 => Controller::default(), _=> Controller::default_versus(player - 1) }
             },
+            rotation_system: rules.rotation_system.get(),
+            rules,
+            entry_delay: None,
+            charged_shift: 0,
+            charged_rotation: None,
+            block_scale,
             player,
+            profile,
+            preferences,
+            rotated_last: false,
+            achievements: achievement_tracker::unlocked_achievements(profile),
+            toasts: vec![],
+            won: None,
+            frozen: false,
+            recording: vec![],
+            resumed: false,
+            replay: None,
+            handling: Some((preferences.das, preferences.arr)),
         }
     }
 
+    // true if the keys of the whole run were recorded with the same handling, runs saved before they were recorded
+    // only have the frames after
+    pub fn replayable (&self) -> bool {
+        self.handling.is_some() && !self.recording.is_empty() && self.recording.len() as u32 >= self.stats.frames
+    }
+
+    // creates a replay of the given Game, dealt the same Tetrominoes and played by it's recorded keys.
+    // The run is replayed with the bindings and preferences the Game ended with, and the handling it was recorded with
+    pub fn replay (game: &Game) -> Self {
+        let mut replay = Self::new(game.player, game.profile, Some(game.controller.clone()), game.rules);
+        replay.seed = game.seed;
+        replay.preferences = game.preferences;
+        if let Some((das, arr)) = game.handling { (replay.preferences.das, replay.preferences.arr) = (das, arr) }
+        replay.recording = game.recording.clone();
+        replay.replay = Some(0);
+        replay
+    }
+
+    // reloads the key bindings and preferences of the profile after the config files changed, keeping the rules of the run
+    pub fn reload_config (&mut self) {
+        self.controller = config_tracker::get_controller(self.profile, self.player);
+        self.preferences = config_tracker::get_preferences(self.profile);
+        if self.handling != Some((self.preferences.das, self.preferences.arr)) { self.handling = None }
+
+        // held keys start over, on the frame the replay marks as resumed
+        self.register_inputs();
+        self.resumed = true;
+    }
+
     // sets the state of the run
     pub fn set_state (&mut self, new_state: RunState) {
         self.run_state = new_state;
@@ -119,63 +344,68 @@ impl Game {
     // pauses / resumes the game
     pub fn pause (&mut self) {
         match self.run_state {
-            RunState::Playing => self.set_state(RunState::Paused(PauseMenu::new())),
-            RunState::Paused(_) => self.set_state(RunState::Playing),
+            RunState::Playing => {
+                let paused_by = match (self.player, self.profile) {
+                    (0, _) => None,
+                    (player, 0) => Some(format!("Player {}", player)),
+                    (player, profile) => Some(format!("Player {} ({})", player, crate::profile_tracker::profile_name(profile))),
+                };
+                self.set_state(RunState::Paused(PauseMenu::new(paused_by)))
+            },
+            RunState::Paused(_) => {
+                self.set_state(RunState::Playing);
+
+                // held keys start over after a pause, on the frame the replay marks as resumed
+                self.register_inputs();
+                self.resumed = true;
+            },
             _=> {}
         }
     }
 
-    // resets the game
-    pub fn reset(&mut self) {
-
-        // create an empty playfield
-        self.playfield = Self::create_playfield();
+    // freezes or thaws the Game while the other side of versus is paused. Held keys start over when it's thawed, like after a pause
+    pub fn freeze (&mut self, frozen: bool) {
+        if self.frozen && !frozen {
+            self.register_inputs();
+            self.resumed = true;
+        }
+        self.frozen = frozen;
+    }
 
-        // set default game speed 
-        self.move_y_cooldown = DEFAULT_MOVE_Y_COOLDOWN;
-        
-        // register the input keys
-        // use super::InputHandler;
-        self.register_inputs();
+    // jumps to the next Tetromino on the bag
+    pub fn next (&mut self) {
 
-        // register the routines 
-        self.initialize_routines();
+        // get the next Tetromino on the bag, spawned by the rotation system
+        let id = self.bag_next();
+        let t = self.rotation_system.spawn(id);
 
-        // initialize the score to 0
-        self.score = 0;
+        // set it as the new current Tetromino. Guideline spawn rows place the lowest blocks of the piece on the first hidden row
+        let spawn = self.rotation_system.spawn_pos(id, self.playfield.width());
+        let bottom = t.cells().iter().map(|(_, y)| *y).max().unwrap_or(0);
+        self.set_current(t, (spawn.0, self.playfield.hidden_rows() - 1 - bottom + spawn.1));
 
-        // initialize state back to Start
-        self.run_state = RunState::Start;
+        // block out: the piece overlaps the stack as it spawns
+        if collides(t.cells(), self.cur_pos, &self.playfield) {
+            self.set_state(RunState::Over(GameOverReason::BlockOut));
+            return
+        }
 
-        // call next to start the game 
-        self.next();
+        // the piece drops into the visible playfield right away if nothing is in it's way
+        if !collides(t.cells(), (self.cur_pos.0, self.cur_pos.1 + 1), &self.playfield) { self.cur_pos.1 += 1 }
     }
-    
-    // jumps to the next Tetromino on the bag
-    pub fn next (&mut self) {
 
-        // get the next Tetromino on the bag
-        let t = self.bag_next();
+    // sets the given Tetromino as the current one at the given position
+    pub fn set_current (&mut self, t: Tetromino, pos: (i8, i8)) {
 
-        // get the size of the Tetromino's grid (3x3 or 4x4)
-        let size = (t.grid[0].len() as u32, t.grid.len() as u32);
+        // get the size of the Tetromino's bounding box (2x2 up to 5x5)
+        let size = (t.size as u32, t.size as u32);
 
-        // set it as the new current Tetromino
         self.cur_tetromino = Some(t);
+        self.cur_pos = pos;
+        self.rotated_last = false;
 
         // initialize console for the Tetromino
-        self.cur_con = Some(Console::new(size.0 * BLOCK_SCALE as u32, size.1 * BLOCK_SCALE as u32));
-
-        // reset position
-        self.cur_pos = ((PLAYFIELD_WIDTH as i8 / 2) - (size.0 as i8 / 2), 0);
-        
-        // initialize console for the next Tetromino after this one
-        self.next_con = Some(Console::new(6 * BLOCK_SCALE as u32, 8 * BLOCK_SCALE as u32));
-
-        // check if game over
-        if get_rot_correction(&self.cur_tetromino.clone().unwrap().grid, self.cur_pos, &self.playfield) != 0 {
-            self.set_state(RunState::Over)
-        };
+        self.cur_con = Some(Console::new(size.0 * self.block_scale as u32, size.1 * self.block_scale as u32));
     }
 
     /// returns the number of moves until given Tetromino reaches the bottom
@@ -203,9 +433,6 @@ impl Game {
     // skips to the next tetromino, finishing the trajectory of the current
     pub fn skip (&mut self) {
 
-        // initialize a bool to define if the Game instance should set it's RunState to Playing from RunState::Start 
-        let mut start_run = false;
-
         // match the current Tetromino
         match &self.cur_tetromino {
 
@@ -215,14 +442,6 @@ impl Game {
             // Some current Tetromino 
             Some (t) => {
 
-                // match the current instance RunState
-                match self.run_state {
-
-                    // Start the game
-                    RunState::Start => start_run = true,
-                    _=> {}
-                }
-
                 // get the number of steps to be skipped
                 let steps = self.get_skip_steps(&t);
 
@@ -244,7 +463,6 @@ impl Game {
                 }
             }
         }
-        if start_run { self.set_state(RunState::Playing) }
     }
 
     // rotates the current Tetromino
@@ -256,23 +474,24 @@ impl Game {
             // some tetromino
             Some(t) => {
 
-                // get the rotated grid
-                let rotated = t.get_rotated(clockwise).to_owned();
+                // get the rotation state to rotate to
+                let rotated = t.get_rotated(clockwise);
 
-                // get the result of the srs wall_kick check
-                let srs = srs_correction(t.id, t.rotation, if clockwise { 1 } else { -1 }, &rotated, self.cur_pos, &self.playfield);
+                // get the result of the rotation system's wall kick check
+                let kick = self.rotation_system.kick(t, rotated, self.cur_pos, &self.playfield);
 
                 // match Some / None
-                match srs {
+                match kick {
 
                     // Some position returned, allow rotation and update Tetromino's position
                     Some((x, y)) => {
 
-                        // replace the tetromino's grid
-                        t.set_grid(rotated);
+                        // switch the tetromino's rotation state
+                        t.set_rotation(rotated);
 
                         // move the tetromino
                         self.cur_pos.0 += x; self.cur_pos.1 += y;
+                        self.rotated_last = true;
 
                     },
 
@@ -280,8 +499,8 @@ impl Game {
                     _=> {}
                 }
 
-                // replace the tetromino's grid
-                // t.set_grid(rotated);
+                // replace the tetromino's rotation state
+                // t.set_rotation(rotated);
 
                 // if correction is not none, move the tetromino
                 // if correction != 0 { self._move_x(correction); }
@@ -302,8 +521,10 @@ impl Game {
 
                 let simulated = simulate_move_y(&tetromino, self.cur_pos, dir, &self.playfield);
 
-                // apply the new position
-                self.cur_pos = (simulated.0 + simulated.2, simulated.1 + simulated.3);
+                // apply the new position, moving the Tetromino after a rotation undoes a T-Spin
+                let position = (simulated.0 + simulated.2, simulated.1 + simulated.3);
+                if position != self.cur_pos { self.rotated_last = false }
+                self.cur_pos = position;
 
                 // Tetronimo is still current
                 return simulated.3 < 0;
@@ -315,8 +536,11 @@ impl Game {
         }
     }
 
-    // declare the intent of moving x by 'dir' in the next move_x call
-    pub fn intent_x (&mut self, dir: i8) { self.move_intent.0 = (self.move_intent.0 as i32 + dir as i32).min(127) as i8 /*/.min(1).max(-1) */}
+    // declare the intent of moving x by 'dir' in the next move_x call. Ignored while there's no Tetromino to move
+    pub fn intent_x (&mut self, dir: i8) {
+        if self.cur_tetromino.is_none() { return }
+        self.move_intent.0 = (self.move_intent.0 as i32 + dir as i32).min(127) as i8 /*/.min(1).max(-1) */
+    }
 
     // calls move_cur to move horizontally
     fn _move_x (&mut self, dir: i8) { self.move_cur((dir, 0)); }
@@ -334,10 +558,6 @@ impl Game {
 
     // declare the intent of moving y by 'dir' in the next move_y call
     pub fn intent_y (&mut self, dir: i8) {
-        match self.run_state {
-            RunState::Start => self.set_state(RunState::Playing),
-            _=> {}
-        }
         self.move_intent.1 = dir;
         let speed = self.move_y_cooldown;
         match self.get_routine("move_y", "game") {
@@ -370,34 +590,35 @@ impl Game {
     }
 
     fn end_turn(&mut self) {
-        // add the Tetromino the the playfield
-        self.add_to_playfield();
-
-        // let mut next_playfield = self.check_rows();
-        let mut score_sum = 0;
-
-        // enter dynamic loop
-        loop {
 
-            // check rows and match accordingly
-            match self.check_rows() {
+        // lock out: the Tetromino locks down without any of it's blocks on the visible playfield
+        if let Some(t) = &self.cur_tetromino {
+            if t.cells().iter().all(|(_, y)| self.cur_pos.1 + y < self.playfield.hidden_rows()) {
+                self.set_state(RunState::Over(GameOverReason::LockOut));
+                return
+            }
+        }
 
-                // one line was cleared this iteration, new playfield returned
-                Some(playfield) => {
+        // a T-Spin is told by the blocks around the T before it locks
+        let t_spin = self.t_spin();
+        if let Some(t) = &self.cur_tetromino { self.stats.placed[t.id.index()] += 1 }
 
-                    // TODO: this is where some sort of animation comes into play
+        // add the Tetromino the the playfield and lose control over it
+        self.add_to_playfield();
+        self.cur_tetromino = None;
+        self.move_intent.0 = 0;
 
-                    // replace the playfield
-                    self.set_playfield(playfield);
+        // find the full rows, they are only removed once the line clear animation ends
+        let full_rows = self.playfield.full_rows();
+        let score_sum = full_rows.len() as i32;
 
-                    // increase the score by 1
-                    score_sum += 1;
-                },
-
-                // no lines cleared, exit loop
-                None => break
-            }
-        }
+        // count the piece and the cleared lines, a piece that clears nothing breaks the combo
+        self.stats.pieces += 1;
+        self.stats.lines += full_rows.len() as u32;
+        self.stats.combo = if score_sum != 0 { self.stats.combo + 1 } else { 0 };
+        self.stats.max_combo = self.stats.max_combo.max(self.stats.combo);
+        self.stats.count_clear(full_rows.len(), t_spin);
+        self.check_achievements(full_rows.len(), t_spin);
 
         // if score is not 0
         if score_sum != 0 {
@@ -406,121 +627,146 @@ impl Game {
             self.score += score_sum * score_sum * 10;
             self.move_y_cooldown = (self.move_y_cooldown as i32 - (self.move_y_cooldown as i32 / 30 * score_sum).max(1)).max(0) as u32;
             println!("score: {} (+{}) | new speed: {}", self.score, score_sum * score_sum * 10, self.move_y_cooldown);
-        }                    
+        }
 
-        // lose control over the Tetromino and get the next one
-        self.next();
+        // flash the full rows before clearing them, or skip straight to the entry delay
+        if score_sum != 0 && self.rules.line_clear_delay > 0 {
+            self.entry_delay = Some(EntryDelay::LineClear(full_rows, self.rules.line_clear_delay));
+        } else {
+            self.playfield.clear_full_rows();
+            self.start_spawn_delay();
+        }
 
     }
 
-    // adds the current Tetromino to the playfield as solid blocks
-    pub fn add_to_playfield (&mut self) {
-
-        // unwrap the current Tetromino
-        match &self.cur_tetromino {
-
-            // no bugs
-            Some(t) => {
+    // true if the current Tetromino is a T that got to where it is by rotating, with 3 of the 4 corners around it's
+    // center taken by blocks or walls
+    fn t_spin (&self) -> bool {
+        let t = match &self.cur_tetromino { Some(t) if t.id == TetrominoID::T && self.rotated_last => t, _ => return false };
+        let cells = t.cells();
+        let center = match cells.iter().find(|(x, y)| cells.iter().filter(|(nx, ny)| (nx - x).abs() + (ny - y).abs() == 1).count() == 3) {
+            Some((x, y)) => (self.cur_pos.0 + x, self.cur_pos.1 + y),
+            None => return false,
+        };
+        let corners = [(-1, -1), (1, -1), (-1, 1), (1, 1)].iter().filter(|(x, y)| {
+            let (x, y) = (center.0 + x, center.1 + y);
+            self.playfield.occupied(x, y) || self.playfield.out_of_bounds(x, y)
+        }).count();
+        corners >= 3
+    }
 
-                // loop through the Tetromino's ggrid
-                for y in 0..t.grid.len() as usize {
-                    for x in 0..t.grid[0].len() as usize {
+    // unlocks the achievements reached by the line clear that just happened
+    fn check_achievements (&mut self, rows: usize, t_spin: bool) {
+        if self.replay.is_some() { return }
+        let mut reached = vec![];
+        if rows >= 4 { reached.push((Achievement::FirstTetris, 1)) }
+        if t_spin && rows == 3 { reached.push((Achievement::TSpinTriple, 1)) }
+        if self.stats.back_to_back > 0 { reached.push((Achievement::BackToBack, self.stats.back_to_back)) }
+        if self.player == 0 && self.stats.lines >= SPRINT_LINES && self.stats.frames < SPRINT_FRAMES { reached.push((Achievement::Sprint, 1)) }
+
+        for (achievement, progress) in reached {
+            if self.achievements.contains(&achievement) { continue }
+            if achievement_tracker::reach_progress(self.profile, achievement, progress) { self.unlocked(achievement) }
+        }
+    }
 
-                        // if no block at position, skip
-                        if !t.grid[x][y] { continue; }
+    // shows the given achievement as unlocked
+    pub fn unlocked (&mut self, achievement: Achievement) {
+        self.achievements.push(achievement);
+        self.toasts.push((achievement, TOAST_FRAMES));
+    }
 
-                        // get the target x and y of the block
-                        let target_x = self.cur_pos.0 + x as i8;
-                        let target_y = self.cur_pos.1 + y as i8;
+    // counts down the frames the unlocked achievements are shown for, one at a time
+    pub fn tick_toasts (&mut self) {
+        if let Some((_, frames)) = self.toasts.first_mut() { *frames = frames.saturating_sub(1) }
+        if let Some((_, 0)) = self.toasts.first() { self.toasts.remove(0); }
+    }
 
-                        // check boundaries of playfield and skip if outside
-                        if {
-                            let mut oob = false;
-                            if target_x < 0 || target_y < 0 || target_x >= self.playfield.len() as i8 { oob = true; }
-                            if target_y >= self.playfield[0].len() as i8 { oob = true }
-                            oob
-                        } { continue; }
+    // starts the ARE before the next Tetromino, spawning it right away if there's none
+    fn start_spawn_delay (&mut self) {
+        match self.rules.entry_delay {
+            0 => { self.entry_delay = None; self.spawn_charged() },
+            frames => self.entry_delay = Some(EntryDelay::Spawn(frames)),
+        }
+    }
 
-                        // add the block at the position to the playfield
-                        self.playfield[target_x as usize][target_y as usize] = Some(t.color);
-                    }
-                }
-            }
+    // counts down the current entry delay by one frame
+    pub fn tick_entry_delay (&mut self) {
+        match &mut self.entry_delay {
+            Some(EntryDelay::LineClear(_, frames)) | Some(EntryDelay::Spawn(frames)) if *frames > 1 => *frames -= 1,
 
-            // bugs 
+            // the line clear animation is over, collapse the rows and wait for the next Tetromino
+            Some(EntryDelay::LineClear(_, _)) => {
+                self.playfield.clear_full_rows();
+                self.start_spawn_delay();
+            },
+            Some(EntryDelay::Spawn(_)) => {
+                self.entry_delay = None;
+                self.spawn_charged();
+            },
             None => {}
         }
-
     }
 
-    // checks if there are full rows, destroys them and increases the score if any
-    pub fn check_rows (&mut self) -> Option<[[Option<RTColor>; PLAYFIELD_HEIGHT as usize]; PLAYFIELD_WIDTH as usize]> {
-
-        // println!("\nchecking rows");
+    // spawns the next Tetromino, applying the shift and rotation charged during the entry delay
+    fn spawn_charged (&mut self) {
+        self.next();
+        if let RunState::Over(_) = self.run_state { return }
+
+        // the held direction is charged: it shifts to the wall right away without a repeat delay, and auto repeats from the spawn otherwise
+        if self.charged_shift != 0 {
+            let shift = self.charged_shift;
+            match self.preferences.arr {
+                0 => self._move_x(shift * self.playfield.width()),
+                _ => self._move_x(shift),
+            }
+            let key = self.controller.get(if shift < 0 { InputID::Left } else { InputID::Right }).to_string();
+            for keymap in self.inputmap.iter_mut().filter(|keymap| keymap.key_text == key && keymap.category == "priority") {
+                keymap.charge();
+            }
+        }
+        if self.rules.initial_rotation {
+            if let Some(clockwise) = self.charged_rotation { self.rotate(clockwise) }
+        }
+        self.charged_shift = 0;
+        self.charged_rotation = None;
+    }
 
-        // initialize the new playfield
-        let mut new_playfield = [[None; PLAYFIELD_HEIGHT as usize]; PLAYFIELD_WIDTH as usize];
+    // adds the current Tetromino to the playfield as solid blocks
+    pub fn add_to_playfield (&mut self) {
 
-        // initialize a index offset to remove rows
-        let mut y_offset = 0;
+        // unwrap the current Tetromino
+        match &self.cur_tetromino {
 
-        // loop through the rows
-        for y in (0..PLAYFIELD_HEIGHT as usize).rev() {
-            // println!("{}", y);
+            // no bugs
+            Some(t) => {
 
-            // if the row is full
-            if { let mut row_is_full = true;
-            // let mut row_is_full = true;
+                // loop through the Tetromino's blocks
+                for (x, y) in t.cells().iter() {
 
-                // loop through the blocks on this row
-                for x in 0..PLAYFIELD_WIDTH as usize {
+                    // get the target x and y of the block
+                    let target_x = self.cur_pos.0 + x;
+                    let target_y = self.cur_pos.1 + y;
 
-                    // if any block is empty, row is not full
-                    match self.playfield[x][y] {
-                        None => { row_is_full = false; break; }
-                        Some(_) => {  }
-                    }
+                    // add the block at the position to the playfield. Blocks outside of it are skipped
+                    self.playfield.set(target_x, target_y, t.color);
                 }
-
-                // println!("Row [{}] is {}", y, if row_is_full {"full"} else {"not full"});
-
-                // return the result of the verification
-                row_is_full
-                
-            } && y_offset == 0 {
-            // if row_is_full {
-
-                // println!("Row [{}] is full", y);
-
-                // increase the y_offset to use the same y index on the next iteration
-                y_offset += 1;
-                // continue;
-
-            }
-
-            // else { println!("Row [{}] is empty", y); }
-
-            // copy this row to the new playfield
-            for x in 0..PLAYFIELD_WIDTH as usize {
-                let pick_y = (y as i8 - y_offset as i8) as usize;
-                new_playfield[x][y] = if pick_y < PLAYFIELD_HEIGHT as usize { self.playfield[x][pick_y] } else { None }
             }
 
+            // bugs 
+            None => {}
         }
 
-        // return the new playfield if at least one row is erased
-        if y_offset > 0 { Some(new_playfield) } else { None }
-
     }
 
 }
 
 impl HasBag for Game {
-    fn bag_next(&mut self) -> Tetromino {
+    fn bag_next(&mut self) -> TetrominoID {
         match &mut self.bag_queue {
             Some(bag) => bag.next(),
             None => {
-                self.bag_queue = Some(Bag::new());
+                self.bag_queue = Some(Bag::new(self.rules.piece_set.pieces(), self.seed));
                 self.bag_next()
             }
         }
@@ -532,4 +778,11 @@ impl HasBag for Game {
             None => None
         }
     }
+
+    fn bag_peek(&self, count: usize) -> Vec<TetrominoID> {
+        match &self.bag_queue {
+            Some(bag) => bag.peek_n(count),
+            None => vec![]
+        }
+    }
 }
\ No newline at end of file
@@ -1,5 +1,5 @@
 use doryen_rs::{DoryenApi, UpdateEvent};
-use crate::{Game, GameEvent, RustyEngine};
+use crate::{Game, GameEvent, RustyEngine, RunState, GameOverReason, SaveError, Achievement, config_tracker, profile_tracker, achievement_tracker, save_games, load_games, delete_save};
 
 // wrapper for Game 
 pub enum GameMode {
@@ -10,16 +10,94 @@ pub enum GameMode {
 // logic implementation for GameMode
 impl GameMode {
 
-    // creates a GameMode instance for singleplayer
+    // creates a GameMode instance for singleplayer, played by the profile of the session
     pub fn singleplayer() -> Self {
-        GameMode::SinglePlayer(Game::singleplayer())
+        GameMode::SinglePlayer(Game::singleplayer(config_tracker::get_rules(0), profile_tracker::load_profile().unwrap_or(0)))
     }
 
-    // creates a GameMode instance for versus mode with two Game instances
+    // creates a GameMode instance for versus mode with two Game instances,
+    // played by the profile of the session and the one assigned to the second side
     pub fn versus() -> Self {
-        GameMode::Versus(Game::versus(1), Game::versus(2))
+        let rules = config_tracker::get_rules(1);
+        let profiles = (profile_tracker::load_profile().unwrap_or(0), profile_tracker::load_versus_profile().unwrap_or(0));
+        GameMode::Versus(Game::versus(1, rules, profiles.0), Game::versus(2, rules, profiles.1))
     }
     
+    // resumes the saved run, paused where it was left. The save is consumed once resumed, and discarded if it's corrupted.
+    // A save made with other rules is kept, so it can be continued after switching them back
+    pub fn resume() -> Result<Self, SaveError> {
+        let (game_mode, mut games) = match load_games() {
+            Ok(loaded) => loaded,
+            Err(SaveError::Corrupt) => { delete_save(); return Err(SaveError::Corrupt) },
+            Err(err) => return Err(err),
+        };
+        let game_mode = match (game_mode, games.len()) {
+            (0, 1) => GameMode::SinglePlayer(games.remove(0)),
+            (1, 2) => {
+
+                // a versus run is resumed paused by the first side, with the second one frozen
+                let mut game2 = games.remove(1);
+                game2.set_state(RunState::Playing);
+                game2.freeze(true);
+                GameMode::Versus(games.remove(0), game2)
+            },
+            _ => { delete_save(); return Err(SaveError::Corrupt) },
+        };
+        delete_save();
+        Ok(game_mode)
+    }
+
+    // applies the changes made to the config files to each Game
+    pub fn reload_config (&mut self) {
+        match self {
+            GameMode::SinglePlayer(game) => game.reload_config(),
+            GameMode::Versus(game1, game2) => { game1.reload_config(); game2.reload_config() },
+        }
+    }
+
+    // returns the Game instances of the GameMode
+    pub fn games (&self) -> Vec<&Game> {
+        match self {
+            GameMode::SinglePlayer(game) => vec![game],
+            GameMode::Versus(game1, game2) => vec![game1, game2],
+        }
+    }
+
+    // true if every Game of the GameMode was quit from the pause menu instead of topping out
+    pub fn quit (&self) -> bool {
+        self.games().iter().all(|game| matches!(game.run_state, RunState::Over(GameOverReason::Quit)))
+    }
+
+    // true if every Game of the GameMode is over, and the run wasn't quit to be continued later
+    pub fn over (&self) -> bool {
+        self.games().iter().all(|game| matches!(game.run_state, RunState::Over(_))) && !self.quit()
+    }
+
+    // creates a new GameMode of the same kind, with new seeds
+    pub fn restart (&self) -> Self {
+        match self {
+            GameMode::SinglePlayer(_) => GameMode::singleplayer(),
+            GameMode::Versus(_, _) => GameMode::versus(),
+        }
+    }
+
+    // pauses the run after the window lost focus, if the players want it to. On versus the first side playing with
+    // auto pause on pauses, freezing the other one
+    pub fn focus_lost (&mut self) {
+        match self {
+            GameMode::SinglePlayer(game) => if auto_pause(game) { game.pause() },
+            GameMode::Versus(game1, game2) => {
+                if paused(game1) || paused(game2) { return }
+                match (auto_pause(game1), auto_pause(game2)) {
+                    (true, _) => game1.pause(),
+                    (_, true) => game2.pause(),
+                    _ => return,
+                }
+                self.sync_pause();
+            }
+        }
+    }
+
     // matches GameMode to a unique id for serialization
     pub fn id (&self) -> u8 {
         match self {
@@ -29,21 +107,30 @@ impl GameMode {
     }
 
     // called when game ends to track scores
-    pub fn game_over (&self, profile: u8) {
+    pub fn game_over (&self) {
 
         // import score_tracker for this function only
         use crate::rt::serialization::score_tracker::*;
 
+        // a run quit from the pause menu isn't over yet, save it to be continued instead of tracking it's scores
+        if self.quit() {
+            match save_games(self.id(), &self.games()) {
+                Ok(_) => println!("game saved"),
+                Err(e) => println!("game_over: save_games({}) -- Erro: {}", self.id(), e),
+            }
+            return
+        }
+
         // match GameMode
         match self {
 
             // singleplayer: track the score of the Game on this GameMode
-            GameMode::SinglePlayer(game) => track_score(profile, self.id(), game.score),
+            GameMode::SinglePlayer(game) => track_score(ScoreRecord::new(game.profile, self.id(), game)),
 
-            // versus: track the score of both instances of Game
+            // versus: track the score of both instances of Game, each for it's own profile
             GameMode::Versus(game1, game2) => {
-                track_score(profile, self.id(), game1.score);
-                track_score(profile, self.id(), game2.score);
+                track_score(ScoreRecord::new(game1.profile, self.id(), game1));
+                track_score(ScoreRecord::new(game2.profile, self.id(), game2));
             }
         }
     }
@@ -60,19 +147,80 @@ impl GameMode {
     pub fn update (&mut self, api: &mut dyn DoryenApi) -> (Option<GameEvent>, Option<UpdateEvent>) {
         match self {
             GameMode::SinglePlayer(game) => game.update(api),
-            GameMode::Versus(game1, game2) => match ( game1.update(api).0, game2.update(api).0 ) {
-                (Some(r1), Some(_)) => (Some(r1), None),
-                _=> (None, None)
+            GameMode::Versus(game1, game2) => {
+
+                // while a side is paused only it's menu is updated, the other side is frozen
+                let result = match (paused(game1), paused(game2)) {
+                    (true, _) => game1.update(api),
+                    (_, true) => game2.update(api),
+                    _ => match ( game1.update(api).0, game2.update(api).0 ) {
+                        (Some(GameEvent::Retry), _) | (_, Some(GameEvent::Retry)) => (Some(GameEvent::Retry), None),
+                        (Some(r1), Some(_)) => (Some(r1), None),
+                        _=> (None, None)
+                    }
+                };
+                self.sync_pause();
+                if let GameMode::Versus(game1, _) = self { if game1.won.is_none() { self.decide_match() } }
+                result
             }
         }
     }
 
-    // redirects the render method to the Game of the GameMode
+    // pausing a side of versus freezes the other if it's still playing, and exiting from it's pause menu quits both so
+    // the run is saved. A side that already topped out stays over: exiting then ends the match, which is scored
+    fn sync_pause (&mut self) {
+        let (game1, game2) = match self { GameMode::Versus(game1, game2) => (game1, game2), _ => return };
+        let quit = |game: &Game| matches!(game.run_state, RunState::Over(GameOverReason::Quit));
+        let (quit1, quit2) = (quit(game1), quit(game2));
+        for (game, other_quit) in [(&mut *game1, quit2), (&mut *game2, quit1)] {
+            if game.frozen && other_quit { game.freeze(false); game.set_state(RunState::Over(GameOverReason::Quit)) }
+        }
+        let playing = |game: &Game| matches!(game.run_state, RunState::Playing);
+        let (paused1, paused2) = (paused(game1), paused(game2));
+        let (playing1, playing2) = (playing(game1), playing(game2));
+        game1.freeze(paused2 && playing1);
+        game2.freeze(paused1 && playing2);
+    }
+
+    // once both sides of versus are over, tells who won and counts the win for it's profile. The side that lasted
+    // longer wins: a side can only quit alone after the other topped out, so quitting then still wins the match.
+    // A match quit by both sides is saved instead
+    fn decide_match (&mut self) {
+        if self.quit() { return }
+        let (game1, game2) = match self { GameMode::Versus(game1, game2) => (game1, game2), _ => return };
+        let over = |game: &Game| matches!(game.run_state, RunState::Over(_));
+        if !over(game1) || !over(game2) { return }
+        let winner = match game1.stats.frames.cmp(&game2.stats.frames) {
+            std::cmp::Ordering::Greater => Some(1),
+            std::cmp::Ordering::Less => Some(2),
+            std::cmp::Ordering::Equal => None,
+        };
+        game1.won = Some(winner == Some(1));
+        game2.won = Some(winner == Some(2));
+
+        let game = match winner { Some(1) => game1, Some(_) => game2, None => return };
+        if achievement_tracker::add_progress(game.profile, Achievement::Rival, 1) { game.unlocked(Achievement::Rival) }
+    }
+
+    // redirects the render method to the Game of the GameMode, the side that paused last so it's menu is on top
     pub fn render (&mut self, api: &mut dyn DoryenApi) {
         match self {
             GameMode::SinglePlayer(game) => game.render(api),
-            GameMode::Versus(game1, game2) => { game1.render(api); game2.render(api) }
+            GameMode::Versus(game1, game2) => match paused(game1) {
+                true => { game2.render(api); game1.render(api) },
+                false => { game1.render(api); game2.render(api) },
+            }
         }
     }
 }
 
+// true if the Game is on the pause menu
+fn paused (game: &Game) -> bool {
+    matches!(game.run_state, RunState::Paused(_))
+}
+
+// true if the Game is being played by a profile that wants it paused when the window loses focus
+fn auto_pause (game: &Game) -> bool {
+    matches!(game.run_state, RunState::Playing) && !game.frozen && game.preferences.auto_pause
+}
+
@@ -1,12 +1,14 @@
-use crate::{RustyEngine, GameEvent, InputHandler, RTColor, render_logo, render_button };
+use crate::{RustyEngine, GameEvent, GameState, GameMode, InputHandler, RTColor, render_logo, render_button, has_save, SaveError };
 use crate::{CONSOLE_HEIGHT, CONSOLE_WIDTH};
 
 // for action distinction
 enum Action {
+    Continue,
     Play,
     Versus,
     Profile,
     Scores,
+    Stats,
     Settings,
     Exit,
 }
@@ -15,32 +17,37 @@ enum Action {
 impl Action {
     fn text (&self) -> &str {
         match self {
+            Action::Continue => "Continue",
             Action::Play     => "Play",
             Action::Versus   => "Versus",
             Action::Profile  => "Profiles",
             Action::Scores   => "Scores",
+            Action::Stats    => "Stats",
             Action::Settings => "Settings",
             Action::Exit     => "Exit",        
         }
     }
     fn color (&self) -> RTColor {
         match self {
+            Action::Continue => RTColor::Orange,
             Action::Play     => RTColor::Cyan,
             Action::Versus   => RTColor::Magenta,
             Action::Profile  => RTColor::Yellow,
             Action::Scores   => RTColor::Green,
+            Action::Stats    => RTColor::Orange,
             Action::Settings => RTColor::Blue,
             Action::Exit     => RTColor::Red,        
         }
     }
 }
 
-// lists the possible idenfiable actions of the main_menu
-const ACTIONS: [Action; 6] = [
+// lists the possible idenfiable actions of the main_menu, Continue is added on top of them when there's a saved run
+const ACTIONS: [Action; 7] = [
     Action::Play,
     Action::Versus,
     Action::Profile,
     Action::Scores,
+    Action::Stats,
     Action::Settings,
     Action::Exit,
 ];
@@ -49,17 +56,25 @@ const ACTIONS: [Action; 6] = [
 pub struct MainMenu {
     pub cursor_pos: usize,
     pub inputmap: Vec::<crate::KeyMap>,
+    actions: Vec<Action>,
+
+    // message shown below the menu, like why a saved run couldn't be continued
+    notice: Option<String>,
 }
 
 // logic implementation for the MainMenu
 impl MainMenu {
 
     // initializatio
-    pub fn new () -> Self { Self {cursor_pos: 0, inputmap: vec![] }}
+    pub fn new () -> Self {
+        let mut actions = if has_save() { vec![Action::Continue] } else { vec![] };
+        actions.extend(ACTIONS);
+        Self { cursor_pos: 0, inputmap: vec![], actions, notice: None }
+    }
 
     // sets the position of the cursor 
     fn set_cursor(&mut self, pos: i32) -> Option<GameEvent> {
-        let len = ACTIONS.len() as i32;
+        let len = self.actions.len() as i32;
         self.cursor_pos = (((pos % len) + len) % len) as usize;
         None
     }
@@ -70,14 +85,27 @@ impl MainMenu {
     }
 
     // triggers the currently selected action
-    fn action (&self) -> Option<GameEvent> {
-        match ACTIONS[self.cursor_pos] {
-            
+    fn action (&mut self) -> Option<GameEvent> {
+        match self.actions[self.cursor_pos] {
+
+            // resumes the saved run. Continue goes away if the save is gone, unless it was made with other rules
+            Action::Continue  => match GameMode::resume() {
+                Ok(game_mode) => Some(GameEvent::State(GameState::Game(game_mode))),
+                Err(SaveError::RulesChanged) => { self.notice = Some(SaveError::RulesChanged.to_string()); None },
+                Err(err) => {
+                    self.notice = Some(err.to_string());
+                    self.actions.remove(0);
+                    self.cursor_pos = 0;
+                    None
+                }
+            },
+
             // returns Some GameEvent matching the action
             Action::Play      => return Some(GameEvent::new_game()),
             Action::Versus    => return Some(GameEvent::new_game_versus()),
             Action::Profile   => return Some(GameEvent::profiles()),
             Action::Scores    => return Some(GameEvent::scores()),
+            Action::Stats     => return Some(GameEvent::stats()),
             Action::Settings  => return Some(GameEvent::settings()),
             Action::Exit      => return Some(GameEvent::Exit),
 
@@ -86,13 +114,19 @@ impl MainMenu {
         }
     }
 
-    pub fn render_playing_as (&self, con: &mut doryen_rs::Console, profile: usize) {
-        if profile == 0 { return; }
-        use crate::profile_tracker::profile_name;
+    // renders the profile of the session and the one on the second side of versus, if any
+    pub fn render_playing_as (&self, con: &mut doryen_rs::Console, profile: u32, versus_profile: u32) {
+        if profile == 0 && versus_profile == 0 { return; }
+        use crate::{profile_tracker::profile_name, glyphs};
         let half_con_height = CONSOLE_HEIGHT as i32 / 2;
         let half_con_width  = CONSOLE_WIDTH as i32  / 2;
-        let menu_height = ACTIONS.len() as i32 * 3;
-        con.print(half_con_width, half_con_height - (menu_height / 2) - 4, format!("Playing as {}", profile_name(profile)).as_str(), doryen_rs::TextAlign::Center, Some(RTColor::White.u8()), None);
+        let menu_height = self.actions.len() as i32 * 3;
+        let name = |profile: u32| if profile == 0 { "Guest".to_string() } else { profile_name(profile) };
+        let text = match versus_profile {
+            0 => format!("Playing as {}", name(profile)),
+            _ => format!("Playing as {} | Versus player 2: {}", name(profile), name(versus_profile)),
+        };
+        con.print(half_con_width, half_con_height - (menu_height / 2) - 4, glyphs(&text).as_str(), doryen_rs::TextAlign::Center, Some(RTColor::White.u8()), None);
     }
 
 }
@@ -131,13 +165,13 @@ impl RustyEngine for MainMenu {
         let black_colr = RTColor::Black.u8();
 
         // calulate the visual height of the menu
-        let menu_height = ACTIONS.len() as i32 * 3;
+        let menu_height = self.actions.len() as i32 * 3;
 
         // for each action
-        for i in 0..ACTIONS.len() {
+        for i in 0..self.actions.len() {
 
             // reference the color of the action to define the render_button colors
-            let text_color = ACTIONS[i].color().u8();
+            let text_color = self.actions[i].color().u8();
 
             // render the button with the text
             render_button(
@@ -145,11 +179,11 @@ impl RustyEngine for MainMenu {
                 half_con_width,
                 half_con_height - (menu_height / 2) + (i as i32 * 5),
                 12, 5,
-                ACTIONS[i].text(),
+                self.actions[i].text(),
 
                 // active:      black text,     white bg details,       custom bg
                 // inactive:    custom text,    grey bg details,        black bg
-                if i == self.cursor_pos {RTColor::Black}   else {ACTIONS[i].color()},
+                if i == self.cursor_pos {RTColor::Black}   else {self.actions[i].color()},
                 if  i == self.cursor_pos {Some(white_colr)} else {Some(fore_color)},
                 if  i == self.cursor_pos {Some(text_color)} else {Some(black_colr)},
 
@@ -157,6 +191,11 @@ impl RustyEngine for MainMenu {
             );
         }
 
+        // render the notice below the menu
+        if let Some(notice) = &self.notice {
+            con.print(half_con_width, half_con_height - (menu_height / 2) + (self.actions.len() as i32 * 5) + 1, notice, doryen_rs::TextAlign::Center, Some(RTColor::Red.u8()), None);
+        }
+
     }
 }
 
@@ -3,4 +3,6 @@ pub mod game; pub use game::*;
 pub mod main_menu; pub use main_menu::*;
 pub mod profiles; pub use profiles::*;
 pub mod scores; pub use scores::*;
+pub mod stats; pub use stats::*;
+pub mod summary; pub use summary::*;
 pub mod settings; pub use settings::*;
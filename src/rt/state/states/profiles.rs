@@ -1,10 +1,13 @@
-use crate::{RustyEngine, GameEvent, InputHandler, profile_tracker::*};
+use crate::{RustyEngine, GameEvent, InputHandler, profile_tracker::*, archive_scores, delete_profile_config, export_data, import_data, export_folder, import_folder, CONSOLE_HEIGHT};
+use crate::{AchievementProgress, load_achievements, delete_achievements};
 
 // defines an Action enum to represent the available actions on the State
 enum Action {
     Play,
+    Versus,
     Rename,
     Delete,
+    Trophies,
 }
 
 // matches Action with string
@@ -12,22 +15,39 @@ impl Action {
     fn text (&self) -> &str {
         match self {
             Action::Play => "Play",
+            Action::Versus => "Player 2",
             Action::Rename => "Rename",
             Action::Delete => "Delete",
+            Action::Trophies => "Trophies",
         }
     }
 }
 
 // const actions for len() and reference through index
-const ACTIONS: [Action; 3] = [
+const ACTIONS: [Action; 5] = [
     Action::Play,
+    Action::Versus,
     Action::Rename,
     Action::Delete,
+    Action::Trophies,
 ];
 
+// the list starts below the search field, each profile taking 5 rows
+const LIST_Y: i32 = 8;
+const ROW_HEIGHT: i32 = 5;
+const VISIBLE_ROWS: usize = ((CONSOLE_HEIGHT as i32 - 3 - LIST_Y) / ROW_HEIGHT) as usize;
+
+// the action buttons of each profile start after the name, one column each
+const BUTTON_X: i32 = 22;
+const BUTTON_WIDTH: i32 = 11;
+
+// the "Export" and "Import" buttons share the row of the "new" button, under the Rename and Delete actions
+const EXPORT_COLUMN: usize = 2;
+const IMPORT_COLUMN: usize = 3;
+
 // defines the Profiles state
 pub struct Profiles {
-    profiles: Vec<String>,
+    profiles: Vec<Profile>,
     pub inputmap: Vec::<crate::KeyMap>,
     pub cursor_pos: (usize, usize),
     pub renaming: bool,
@@ -36,6 +56,15 @@ pub struct Profiles {
     cursor_anim: u8,
     scroll_pos: i32,
     exit: bool,
+
+    // text typed outside of a rename filters the list by name, the cursor moves through the profiles that match it
+    search: String,
+
+    // result of the last export or import, shown under the title
+    status: Option<String>,
+
+    // name and achievements of the profile whose trophies are open, listed over the profiles
+    trophies: Option<(String, Vec<AchievementProgress>)>,
 }
 
 // defines the functions of the state
@@ -53,14 +82,33 @@ impl Profiles {
             cursor_anim: 0,
             scroll_pos: 0, 
             exit: false,
+            search: String::new(),
+            status: None,
+            trophies: None,
         }
     }
 
+    // indexes of the profiles matching the search, in order
+    fn visible (&self) -> Vec<usize> {
+        let search = self.search.to_lowercase();
+        (0..self.profiles.len()).filter(|i| self.profiles[*i].name.to_lowercase().contains(&search)).collect()
+    }
+
+    // index of the profile under the cursor, None when hovering the "new" button
+    fn selected (&self) -> Option<usize> {
+        self.visible().get(self.cursor_pos.0).copied()
+    }
+
     // redirects the generic action command to a corresponding action
     fn action (&mut self) -> Option<GameEvent> {
         
-        // if cursor is outside the range of the profiles vec (hovering the "new" button)
-        if self.cursor_pos.0 >= self.profiles.len() {
+        // if cursor is outside the range of the listed profiles (hovering the "new", "Export" or "Import" buttons)
+        if self.selected().is_none() {
+            match self.cursor_pos.1 {
+                EXPORT_COLUMN => return self.export(),
+                IMPORT_COLUMN => return self.import(),
+                _=> {}
+            }
 
             // create a new profile
             self.create();
@@ -78,15 +126,23 @@ impl Profiles {
 
         // match the index of the cursor to the corresponding action
         match ACTIONS[self.cursor_pos.1] {
-            Action::Play   => return self.play(),
-            Action::Rename => return self.rename_start(),
-            Action::Delete => return self.delete(),
-        };
+            Action::Play   => self.play(),
+            Action::Versus => self.play_versus(),
+            Action::Rename => self.rename_start(),
+            Action::Delete => self.delete(),
+            Action::Trophies => self.trophies(),
+        }
     }
 
     // Escape command to back track
     fn escape (&mut self) -> Option<GameEvent> {
 
+        // if the trophies are open, go back to the profiles
+        if self.trophies.is_some() {
+            self.trophies = None;
+            return None
+        }
+
         // if currently renaming, stop renaming
         if self.renaming {
             self.renaming = false;
@@ -95,27 +151,45 @@ impl Profiles {
             return None
         }
 
-        // otherwise, apply the changes and exit the state
+        // clear the search before leaving
+        if !self.search.is_empty() { return self.set_search(String::new()) }
+
+        // otherwise, apply the changes and exit the state. The profiles of the session are reloaded, they may have been deleted
         self.apply_changes();
         Some(GameEvent::PreviousState)
     }
 
-    // syncs the changes made inside the state to the binary files 
+    // syncs the changes made inside the state to the binary files, archiving the scores of deleted profiles
     fn apply_changes (&self) {
+        let previous = get_profiles().unwrap_or_default();
         if let Err(err) = save_profiles(&self.profiles) { panic!("Profiles.apply_changes() -- Error applying changes: {}", err) }
+
+        for profile in previous.iter().filter(|p| !self.profiles.iter().any(|kept| kept.id == p.id)) {
+            if let Err(err) = archive_scores(profile.id) { println!("Profiles.apply_changes() -- Error archiving the scores of {}: {}", profile.name, err) }
+            if let Err(err) = delete_profile_config(profile.id) { println!("Profiles.apply_changes() -- Error deleting the settings of {}: {}", profile.name, err) }
+            if let Err(err) = delete_achievements(profile.id) { println!("Profiles.apply_changes() -- Error deleting the achievements of {}: {}", profile.name, err) }
+        }
     }
 
     // play as the profile 
     fn play (&mut self) -> Option<GameEvent> {
         self.exit = true;
         self.apply_changes();
-        Some(GameEvent::SetProfile(self.cursor_pos.0 + 1))
+        self.selected().map(|i| GameEvent::SetProfile(self.profiles[i].id))
+    }
+
+    // play as the profile on the second side of versus
+    fn play_versus (&mut self) -> Option<GameEvent> {
+        self.exit = true;
+        self.apply_changes();
+        self.selected().map(|i| GameEvent::SetVersusProfile(self.profiles[i].id))
     }
 
     // starts renaming a profile, locking the input to the text entry field
     fn rename_start (&mut self) -> Option<GameEvent> {
+        let selected = self.selected()?;
         self.renaming = true;
-        self.renaming_curr_text = self.profiles[self.cursor_pos.0].to_string();
+        self.renaming_curr_text = self.profiles[selected].name.to_string();
         self.renaming_prev_text = self.renaming_curr_text.to_owned();
         None
     }
@@ -123,45 +197,80 @@ impl Profiles {
     // conclude the rename, restoring the default inputs and chaning the name of the profile locally
     fn rename_conclude (&mut self) -> Option<GameEvent> {
         self.renaming = false;
-        if self.renaming_curr_text.len() < 3 { 
-            self.renaming_curr_text = self.renaming_prev_text.to_owned()
-        }
-        self.profiles[self.cursor_pos.0] = self.renaming_curr_text.to_string();
+        let name = valid_name(&self.renaming_curr_text).unwrap_or_else(|| self.renaming_prev_text.to_owned());
+        if let Some(selected) = self.selected() { self.profiles[selected].name = name }
         self.renaming_curr_text = String::new();
         None
     }
     
-    // create a new profile
+    // create a new profile, clearing the search so it's listed, and move the cursor to it
     fn create (&mut self) -> Option<GameEvent> {
-        if self.profiles.len() < MAX_PROFILES - 1 { self.profiles.push("new profile".to_string()); }
+        let id = next_profile_id(&self.profiles);
+        self.profiles.push(Profile { id, name: "new profile".to_string() });
+        self.search = String::new();
+        self.set_cursor(self.profiles.len() as i32 - 1, self.cursor_pos.1 as i32)
+    }
+
+    // writes the profiles and scores to the exports folder of the data directory, with the changes made so far
+    fn export (&mut self) -> Option<GameEvent> {
+        self.apply_changes();
+        let folder = export_folder();
+        self.status = Some(match export_data(&folder) {
+            Ok(()) => format!("Exported to {}", folder.display()),
+            Err(err) => { println!("Profiles.export() -- Error: {}", err); "Export failed, see the log".to_string() }
+        });
         None
     }
 
+    // merges the files on the imports folder of the data directory, listing the imported profiles
+    fn import (&mut self) -> Option<GameEvent> {
+        self.apply_changes();
+        let folder = import_folder();
+        self.status = Some(match import_data(&folder) {
+            Ok(summary) => {
+                println!("Profiles.import() -- {}", summary);
+                self.profiles = get_profiles().unwrap_or_default();
+                format!("Imported {} new profiles, {} merged by name, {} renamed", summary.profiles_added, summary.profiles_merged, summary.profiles_renamed)
+            },
+            Err(err) => { println!("Profiles.import() -- Error: {}", err); format!("Nothing to import on {}", folder.display()) }
+        });
+        self.set_search(String::new())
+    }
+
     // delete a profile
     fn delete(&mut self) -> Option<GameEvent> {
-        self.profiles.remove(self.cursor_pos.0);
+        if let Some(selected) = self.selected() { self.profiles.remove(selected); }
+        self.set_cursor(self.cursor_pos.0 as i32, self.cursor_pos.1 as i32)
+    }
+
+    // opens the achievements of the profile
+    fn trophies (&mut self) -> Option<GameEvent> {
+        let selected = self.selected()?;
+        let profile = &self.profiles[selected];
+        self.trophies = Some((profile.name.to_owned(), load_achievements(profile.id)));
         None
     }
 
-    // sets the position of the cursor 
+    // sets the position of the cursor, scrolling the list to keep it visible
     fn set_cursor(&mut self, x: i32, y: i32) -> Option<GameEvent> {
-        let lenx = self.profiles.len() as i32 + 1;
+        let lenx = self.visible().len() as i32 + 1;
         let leny = ACTIONS.len() as i32;
         self.cursor_pos = ((((x % lenx) + lenx) % lenx) as usize, (((y % leny) + leny) % leny) as usize);
+        let cursor = self.cursor_pos.0 as i32;
+        self.scroll_pos = self.scroll_pos.min(cursor).max(cursor - VISIBLE_ROWS as i32 + 1).max(0);
         None
     }
 
     // moves the cursor towards target direction
     fn move_cursor(&mut self, x: i32, y: i32) -> Option<GameEvent> {
-        if self.cursor_pos.0 >= 6 + self.scroll_pos as usize { self.scroll(x); };
         self.set_cursor(self.cursor_pos.0 as i32 + x, self.cursor_pos.1 as i32 + y)
     }
 
-    // scrolls the contents of the list 
-    fn scroll (&mut self, dir: i32) -> Option<GameEvent> {
-        let len = (self.profiles.len() as i32 + 1).max(1);
-        self.scroll_pos = (self.scroll_pos as i32 + dir - 14).max(0).min(len);
-        None
+    // replaces the search text, moving the cursor back to the top of the filtered list
+    fn set_search (&mut self, search: String) -> Option<GameEvent> {
+        self.search = search;
+        self.scroll_pos = 0;
+        self.set_cursor(0, self.cursor_pos.1 as i32)
     }
 
     // blink animation for the text entry field's cursor during rename
@@ -184,7 +293,7 @@ impl RustyEngine for Profiles {
     }
 
     fn render(&mut self, api: &mut dyn doryen_rs::DoryenApi) {
-        use crate::{RTColor, Align, render_rect, render_button, CONSOLE_HEIGHT, CONSOLE_WIDTH};
+        use crate::{RTColor, Align, render_rect, render_button, glyphs, CONSOLE_WIDTH};
         
         // get the console
         let con = api.con();
@@ -195,34 +304,80 @@ impl RustyEngine for Profiles {
         let darker_gray = RTColor::DarkerGray.u8();
         let black = RTColor::Black;
 
-        // scrollbar
+        let visible = self.visible();
+
+        // scrollbar, the thumb covers the visible part of the list (profiles and the "new" button)
         render_rect(con, CONSOLE_WIDTH as i32, 8, 3, CONSOLE_HEIGHT - 11, Some(('|', darker_gray)), Some(black.u8()), (Align::End, Align::Start));
-        if self.profiles.len() > 0 {
-            let max_list_len = self.profiles.len() as i32;
-            let scrollbar_height = (CONSOLE_HEIGHT as i32 - 11 - (max_list_len - 14)).max(1) as u32;
-                render_rect(con, CONSOLE_WIDTH as i32, 8 + self.scroll_pos as i32, 3, scrollbar_height, Some((' ', darker_gray)), Some(dark_gray), (Align::End, Align::Start));
-        }
+        let track_height = CONSOLE_HEIGHT as i32 - 11;
+        let list_len = visible.len() as i32 + 1;
+        let scrollbar_height = (track_height * VISIBLE_ROWS as i32 / list_len).max(1).min(track_height) as u32;
+        render_rect(con, CONSOLE_WIDTH as i32, 8 + track_height * self.scroll_pos / list_len, 3, scrollbar_height, Some((' ', darker_gray)), Some(dark_gray), (Align::End, Align::Start));
 
         // profiles
-        for i in 0..self.profiles.len() {
-            render_rect(con, 0, i as i32 * 5 + 5, CONSOLE_WIDTH - 3, 5, None, Some(darker_gray), Align::start2());
+        for (pos, i) in visible.iter().enumerate().skip(self.scroll_pos as usize).take(VISIBLE_ROWS) {
+            let y = (pos as i32 - self.scroll_pos) * ROW_HEIGHT + LIST_Y;
+            render_rect(con, 0, y, CONSOLE_WIDTH - 3, 5, None, Some(darker_gray), Align::start2());
             let mut text = &format!("{}{}", self.renaming_curr_text, if self.cursor_anim < 12 { '|' } else { ' ' });
-            if !self.renaming || self.cursor_pos.0 != i { text = &self.profiles[i] };
-            con.print(11, i as i32 * 5 + 7, text.as_str(), doryen_rs::TextAlign::Center, Some(white.u8()), None);
+            if !self.renaming || self.cursor_pos.0 != pos { text = &self.profiles[*i].name };
+            con.print(11, y + 2, glyphs(text).as_str(), doryen_rs::TextAlign::Center, Some(white.u8()), None);
             for j in 0..ACTIONS.len() {
-                let selected = self.cursor_pos == (i, j) && !self.renaming;
+                let selected = self.cursor_pos == (pos, j) && !self.renaming;
                 let color = if selected { red } else { white };
                 let fore = if selected { Some(white.u8()) } else { None };
                 let back = Some(if selected { white.u8() } else { darker_gray });    
-                render_button(con, j as i32 * 18 + 22, i as i32 * 5 + 6, 18, 3, ACTIONS[j].text(), color, fore, back, Align::start2());
+                render_button(con, j as i32 * BUTTON_WIDTH + BUTTON_X, y + 1, BUTTON_WIDTH as u32, 3, ACTIONS[j].text(), color, fore, back, Align::start2());
             }
         }
 
-        let selected = self.profiles.len() == self.cursor_pos.0 && !self.renaming;
-        let color = if selected { red } else { white };
-        let fore = if selected { Some(white.u8()) } else { None };
-        let back = Some(if selected { white.u8() } else { darker_gray });
-        render_button(con, 5, self.profiles.len() as i32 * 5 + 7, CONSOLE_WIDTH - 13, 3, "new", color, fore, back, (Align::Start, Align::center()));
+        // the "new", "Export" and "Import" buttons after the last profile, if they're scrolled into view
+        let new_pos = visible.len() as i32 - self.scroll_pos;
+        if new_pos < VISIBLE_ROWS as i32 {
+            let y = new_pos * ROW_HEIGHT + LIST_Y + 2;
+            let buttons = [
+                (5, BUTTON_X + BUTTON_WIDTH * EXPORT_COLUMN as i32 - 5, "new", self.cursor_pos.1 != EXPORT_COLUMN && self.cursor_pos.1 != IMPORT_COLUMN),
+                (EXPORT_COLUMN as i32 * BUTTON_WIDTH + BUTTON_X, BUTTON_WIDTH, "Export", self.cursor_pos.1 == EXPORT_COLUMN),
+                (IMPORT_COLUMN as i32 * BUTTON_WIDTH + BUTTON_X, BUTTON_WIDTH, "Import", self.cursor_pos.1 == IMPORT_COLUMN),
+            ];
+            for (x, width, text, column) in buttons {
+                let selected = visible.len() == self.cursor_pos.0 && column && !self.renaming;
+                let color = if selected { red } else { white };
+                let fore = if selected { Some(white.u8()) } else { None };
+                let back = Some(if selected { white.u8() } else { darker_gray });
+                render_button(con, x, y, width as u32, 3, text, color, fore, back, (Align::Start, Align::center()));
+            }
+        }
+
+        // the trophies of a profile cover the list and the search field
+        if let Some((name, achievements)) = &self.trophies {
+            render_rect(con, 0, 5, CONSOLE_WIDTH, CONSOLE_HEIGHT - 5, None, Some(darker_gray), Align::start2());
+            for (i, progress) in achievements.iter().enumerate() {
+                let y = LIST_Y + i as i32 * ROW_HEIGHT;
+                let achievement = progress.achievement;
+                let unlocked = progress.unlocked != 0;
+                let color = if unlocked { RTColor::Yellow } else { RTColor::Gray };
+                render_rect(con, 0, y, CONSOLE_WIDTH, 4, None, Some(black.u8()), Align::start2());
+                con.print(2, y + 1, achievement.text(), doryen_rs::TextAlign::Left, Some(color.u8()), None);
+                con.print(2, y + 2, achievement.description(), doryen_rs::TextAlign::Left, Some(RTColor::Gray.u8()), None);
+                match unlocked {
+                    true => con.print(CONSOLE_WIDTH as i32 - 3, y + 1, format!("unlocked {}", crate::format_date(progress.unlocked)).as_str(), doryen_rs::TextAlign::Right, Some(white.u8()), None),
+                    false => {
+                        let goal = achievement.goal();
+                        con.print(CONSOLE_WIDTH as i32 - 3, y + 1, format!("{}/{}", progress.progress.min(goal), goal).as_str(), doryen_rs::TextAlign::Right, Some(white.u8()), None);
+                        if goal > 1 { crate::render_bar(con, CONSOLE_WIDTH as i32 - 23, y + 2, 20, progress.progress as f32, goal as f32, RTColor::Yellow.u8()) }
+                    }
+                };
+            }
+            render_button(con, 0, 0, CONSOLE_WIDTH, 5, glyphs(&format!("Trophies: {}", name)).as_str(), RTColor::Yellow, Some(darker_gray), None, (Align::Start, Align::Start));
+            render_button(con, 0, 0, 7, 5, "Esc", red, Some(darker_gray), None, (Align::Start, Align::Start));
+            return
+        }
+
+        // search field
+        render_rect(con, 0, 5, CONSOLE_WIDTH - 3, 3, None, Some(black.u8()), Align::start2());
+        match self.search.is_empty() {
+            true => con.print(2, 6, "Type to search", doryen_rs::TextAlign::Left, Some(RTColor::Gray.u8()), None),
+            false => con.print(2, 6, glyphs(&format!("Search: {}", self.search)).as_str(), doryen_rs::TextAlign::Left, Some(white.u8()), None),
+        };
         
         render_rect(con, CONSOLE_WIDTH as i32, 5, 3, 3, Some(('-', darker_gray)), Some(dark_gray), (Align::End, Align::Start));
         con.ascii(CONSOLE_WIDTH as i32 - 2, 6, 30);
@@ -237,6 +392,10 @@ impl RustyEngine for Profiles {
         // renders the Esc button 
         render_button(con, 0, 0, 7, 5, "Esc", red, Some(darker_gray), None, (Align::Start, Align::Start));
 
+        // result of the last export or import
+        if let Some(status) = &self.status {
+            con.print(CONSOLE_WIDTH as i32 / 2, 4, glyphs(status).as_str(), doryen_rs::TextAlign::Center, Some(RTColor::Gray.u8()), None);
+        }
     }
 }
 
@@ -258,13 +417,20 @@ impl InputHandler for Profiles {
     // handle per frame inputs
     fn handle_input(&mut self, input: &mut dyn doryen_rs::InputApi, _: &str) -> Option<GameEvent> {
 
+        // the trophies only close
+        if self.trophies.is_some() {
+            if input.key_pressed("Escape") || input.key_pressed("Enter") { return self.escape() }
+            return None
+        }
+
         if self.renaming {
             if input.key_pressed("Enter") { return self.action() }
             if input.key_pressed("Escape") { return self.escape() }
 
-            let txt = input.text();
-            if !txt.is_empty() && self.renaming_curr_text.len() < 16 {
-                self.renaming_curr_text.push_str(&txt);
+            // names are limited in characters, not bytes
+            for c in input.text().chars().filter(|c| !c.is_control()) {
+                if self.renaming_curr_text.chars().count() >= MAX_NAME_LENGTH { break }
+                self.renaming_curr_text.push(c);
             }
             // handle backspace
             if input.key_pressed("Backspace") && !self.renaming_curr_text.is_empty() {
@@ -273,6 +439,15 @@ impl InputHandler for Profiles {
             return None
         }
 
+        // typing filters the list
+        let typed: String = input.text().chars().filter(|c| !c.is_control()).collect();
+        if !typed.is_empty() { return self.set_search(format!("{}{}", self.search, typed)) }
+        if input.key_pressed("Backspace") && !self.search.is_empty() {
+            let mut search = self.search.to_owned();
+            search.pop();
+            return self.set_search(search)
+        }
+
         // loop through all registered inputs
         for index in 0..self.inputmap.len() {
 
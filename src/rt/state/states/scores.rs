@@ -1,34 +1,51 @@
 use crate::{InputHandler, GameEvent, RustyEngine};
 use crate::{CONSOLE_HEIGHT, CONSOLE_WIDTH};
-use crate::{Align, RTColor, render_rect, render_button};
+use crate::{Align, RTColor, ScoreRecord, Profile, render_rect, render_button, glyphs, format_date};
+use doryen_rs::{Console, TextAlign};
 
 // for action distinction
 enum Action {
     Tab(i8),
+    Mode(i8),
     Scroll(i8),
+    Export,
+    Import,
     Exit,
-    None,
 }
 
 impl Action {
     fn text(&self) -> String {
         match self {
             Action::Tab(x) => format!("Tab({})", x),
+            Action::Mode(x) => format!("Mode({})", x),
             Action::Scroll(y) => format!("Scroll({})", y),
+            Action::Export => "Export".to_string(),
+            Action::Import => "Import".to_string(),
             Action::Exit => "Exit".to_string(),
-            Action::None => "None".to_string(),
         }
     }
 }
 
+// a tab of the lists: the name, the history and the best scores
+type ScoreTab = (String, Vec<ScoreRecord>, Vec<ScoreRecord>);
+
+// the game modes listed, by id, and their colors
+pub const MODES: [(&str, RTColor); 2] = [("Singleplayer", RTColor::Cyan), ("Versus", RTColor::Magenta)];
+
 // defines the state "Scores"
 pub struct Scores {
-    scores: Vec<(String, Vec<(u8, u8, i32)>, Vec<(u8, u8, i32)>)>,
-    profiles: Vec<String>,
+    scores: Vec<ScoreTab>,
+    profiles: Vec<Profile>,
     cursor: usize,
     tab: usize,
+
+    // game mode of the lists, each mode is ranked apart
+    mode: u8,
     inputmap: Vec::<crate::KeyMap>,
-    actions: [[Action; 3]; 3]
+    actions: [[Action; 3]; 3],
+
+    // result of the last export or import, shown under the title
+    status: Option<String>,
 }
 
 // implements initialization for the state
@@ -36,60 +53,70 @@ impl Scores {
 
     // initialize the state, loading the score data of all profiles
     pub fn new () -> Self {
+        let (scores, profiles) = Self::load(0);
+        Self {
+            scores,
+            profiles,
+            cursor: 0,
+            tab: 0,
+            mode: 0,
+            inputmap: vec![],
+            actions: [
+                [Action::Export,  Action::Scroll(-1), Action::Import ],
+                [Action::Tab(-1), Action::Exit,       Action::Tab( 1)],
+                [Action::Mode(-1), Action::Scroll( 1), Action::Mode( 1)],
+            ],
+            status: None,
+        }
+    }
+
+    // loads the scores of the game mode: of everyone, then of each profile with any
+    fn load (mode: u8) -> (Vec<ScoreTab>, Vec<Profile>) {
         use crate::rt::serialization::score_tracker::*;
         use crate::rt::serialization::profile_tracker::get_profiles;
 
         // initialize player index and scores vec
         let mut scores = vec![(
             "All".to_owned(),
-            load_history(None, None).unwrap(),
-            load_best(None, None).unwrap()
+            load_history(None, Some(mode)).unwrap(),
+            load_best(None, Some(mode)).unwrap()
         )];
 
         let profiles = get_profiles().expect("error loading profiles");
 
-        for i in 0..profiles.len() {
-            let player = Some(i as u8 + 1);
+        for profile in profiles.iter() {
+            let player = Some(profile.id);
 
-            match (load_history(player, None), load_best(player, None)) {
+            match (load_history(player, Some(mode)), load_best(player, Some(mode))) {
 
                 (Ok(hist), Ok(best)) if hist.len() > 0 || best.len() > 0 => {
-                    scores.push((profiles[i].to_string(), hist, best));
+                    scores.push((profile.name.to_string(), hist, best));
                 },
                 _=> {}
             }
 
         }
-
-
-        Self {
-            scores,
-            profiles,
-            cursor: 0,
-            tab: 0,
-            inputmap: vec![],
-            actions: [
-                [Action::None,    Action::Scroll(-1), Action::None   ],
-                [Action::Tab(-1), Action::Exit,       Action::Tab( 1)],
-                [Action::None,    Action::Scroll( 1), Action::None   ],
-            ]
-        }
+        (scores, profiles)
     }
 
     // idenfifies the action triggered by the input and call the corresponding functionallity
     fn action (&mut self, x: usize, y: usize) -> Option<GameEvent> {
         println!("action: {}", self.actions[y][x].text());
         match self.actions[y][x] {
-            Action::Tab(dir)    => self.tab(dir),
+            Action::Tab(dir)    => self.tab(dir as i32),
+            Action::Mode(dir)   => self.switch_mode(dir),
             Action::Scroll(dir) => self.scroll(dir as i32),
+            Action::Export => self.export(),
+            Action::Import => self.import(),
             Action::Exit => Some(GameEvent::main_menu()),
-            Action::None => None,
         }
     }
 
     // switches between players
-    fn tab (&mut self, dir: i8) -> Option<GameEvent> {
-        let new = ((self.tab as i8 + dir) + self.scores.len() as i8) as usize % self.scores.len();
+    fn tab (&mut self, dir: i32) -> Option<GameEvent> {
+        let len = self.scores.len() as i32;
+        if len == 0 { return None }
+        let new = (self.tab as i32 + dir).rem_euclid(len) as usize;
         if new != self.tab {
             self.tab = new;
             self.cursor = 0;
@@ -97,14 +124,48 @@ impl Scores {
         None
     }
 
+    // switches between game modes, staying on the same player if they have scores on the other mode
+    fn switch_mode (&mut self, dir: i8) -> Option<GameEvent> {
+        self.mode = ((self.mode as i8 + dir).rem_euclid(MODES.len() as i8)) as u8;
+        let name = self.scores[self.tab].0.to_owned();
+        (self.scores, self.profiles) = Self::load(self.mode);
+        self.tab = self.scores.iter().position(|tab| tab.0 == name).unwrap_or(0);
+        self.cursor = 0;
+        None
+    }
+
     // scrolls the contents of the list 
     fn scroll (&mut self, dir: i32) -> Option<GameEvent> {
-        let len = (self.scores[self.tab].0.len().max(self.scores[self.tab].1.len()) as i32).max(1);
+        let len = (self.scores[self.tab].1.len().max(self.scores[self.tab].2.len()) as i32).max(1);
         self.cursor = (self.cursor as i32 + dir).max(0).min(len - 14) as usize;
         None
     }
 
+    // writes the profiles and scores to the exports folder of the data directory
+    fn export (&mut self) -> Option<GameEvent> {
+        let folder = crate::export_folder();
+        self.status = Some(match crate::export_data(&folder) {
+            Ok(()) => format!("Exported to {}", folder.display()),
+            Err(err) => { println!("Scores.export() -- Error: {}", err); "Export failed, see the log".to_string() }
+        });
+        None
+    }
 
+    // merges the files on the imports folder of the data directory, reloading the lists
+    fn import (&mut self) -> Option<GameEvent> {
+        let folder = crate::import_folder();
+        self.status = Some(match crate::import_data(&folder) {
+            Ok(summary) => {
+                println!("Scores.import() -- {}", summary);
+                (self.scores, self.profiles) = Self::load(self.mode);
+                self.tab = 0;
+                self.cursor = 0;
+                format!("Imported {} scores and {} new profiles", summary.history_added, summary.profiles_added)
+            },
+            Err(err) => { println!("Scores.import() -- Error: {}", err); format!("Nothing to import on {}", folder.display()) }
+        });
+        None
+    }
 }
 
 // implements the doryen-rs engine for the state
@@ -112,7 +173,7 @@ impl RustyEngine for Scores {
 
     // engine initialization
     fn init(&mut self) {
-        println!("Scores.init() -- history lenth: {}", self.scores[0].0.len());
+        println!("Scores.init() -- history lenth: {}", self.scores[0].1.len());
         self.register_inputs()
     }
 
@@ -132,7 +193,6 @@ impl RustyEngine for Scores {
         // let half_con_width  = CONSOLE_WIDTH as i32  / 2;
 
         // reference the following colors 
-        let white = RTColor::White;
         let blue = RTColor::Blue;
         let red = RTColor::Red;
         // let gray = RTColor::Grey.u8();
@@ -147,7 +207,7 @@ impl RustyEngine for Scores {
         // render scrollbar
         render_rect(con, CONSOLE_WIDTH as i32, 8, 3, CONSOLE_HEIGHT - 11, Some(('|', darker_gray)), Some(black), (Align::End, Align::Start));
         if self.scores.len() > 0 {
-            let max_list_len = self.scores[self.tab].0.len().max(self.scores[self.tab].1.len()) as i32;
+            let max_list_len = self.scores[self.tab].1.len().max(self.scores[self.tab].2.len()) as i32;
             let scrollbar_height = (CONSOLE_HEIGHT as i32 - 11 - (max_list_len - 14)).max(1) as u32;
                 render_rect(con, CONSOLE_WIDTH as i32, 8 + self.cursor as i32, 3, scrollbar_height, Some((' ', darker_gray)), Some(dark_gray), (Align::End, Align::Start));
         }
@@ -163,42 +223,35 @@ impl RustyEngine for Scores {
 
         // render best
         for i in 0..self.scores[self.tab].2.len() {
-            
-            // get the score record
-            let record = self.scores[self.tab].2[i];
-
-            // render 
-            // render_button(con, 0, 10 + (i as i32 - self.cursor as i32) * 5, 40, 5, format!("{}º Player: #[red]{}#[white] | GM: #[blue]{}#[white] | Score: #[green]{}", i+1, record.0, record.1, record.2).as_str(), white, None, None, Align::start2());
-            con.area(0, 10 + (i as i32 - self.cursor as i32) * 5, 40, 1, None, Some(darker_gray), Some(0));
-            con.area(0, 14 + (i as i32 - self.cursor as i32) * 5, 40, 1, None, Some(darker_gray), Some(0));
-            con.rectangle(0, 10 + (i as i32 - self.cursor as i32) * 5, 40, 5, Some(white.u8()), None, Some(0));
-            con.print(4, 10 + (i as i32 - self.cursor as i32) * 5, format!("{}º", i+1).as_str(), doryen_rs::TextAlign::Right, Some(white.u8()), None);
-            con.print(38, 10 + (i as i32 - self.cursor as i32) * 5, ["Singleplayer", "Versus"][record.1 as usize], doryen_rs::TextAlign::Right, Some([RTColor::Cyan, RTColor::Magenta][record.1 as usize].u8()), None);
-            con.print(1, 10 + (i as i32 - self.cursor as i32) * 5 + 2, if record.0 == 0 { "Guest" } else { self.profiles[record.0 as usize - 1].as_str() }, doryen_rs::TextAlign::Left, Some(blue.u8()), None);
-            con.print_color(37, 10 + (i as i32 - self.cursor as i32) * 5 + 2, format!("#[green]{}", record.2).as_str(), doryen_rs::TextAlign::Right, None);
+            let y = 10 + (i as i32 - self.cursor as i32) * 5;
+            render_record(con, 0, y, 40, Some(i + 1), &self.scores[self.tab].2[i], &self.profiles);
         }
 
         // render history
         for i in 0..self.scores[self.tab].1.len() {
-            
-            // get the score record
-            let record = self.scores[self.tab].1[i];
-
-            // render 
-            con.area(40, 10 + (i as i32 - self.cursor as i32) * 5, 37, 1, None, Some(darker_gray), Some(0));
-            con.area(40, 14 + (i as i32 - self.cursor as i32) * 5, 37, 1, None, Some(darker_gray), Some(0));
-            con.rectangle(40, 10 + (i as i32 - self.cursor as i32) * 5, 37, 5, Some(white.u8()), None, Some(0));
-            con.print(75, 10 + (i as i32 - self.cursor as i32) * 5, ["Singleplayer", "Versus"][record.1 as usize], doryen_rs::TextAlign::Right, Some([RTColor::Cyan, RTColor::Magenta][record.1 as usize].u8()), None);
-            con.print(41, 10 + (i as i32 - self.cursor as i32) * 5 + 2, if record.0 == 0 { "Guest" } else { self.profiles[record.0 as usize - 1].as_str() }, doryen_rs::TextAlign::Left, Some(blue.u8()), None);
-            con.print_color(74, 10 + (i as i32 - self.cursor as i32) * 5 + 2, format!("#[green]{}", record.2).as_str(), doryen_rs::TextAlign::Right, None);
+            let y = 10 + (i as i32 - self.cursor as i32) * 5;
+            render_record(con, 40, y, 37, None, &self.scores[self.tab].1[i], &self.profiles);
         }
         
-        // render title
-        render_button(con, 0, 0, CONSOLE_WIDTH, 5, format!("Scores: {}", self.scores[self.tab].0).as_str(), blue, Some(darker_gray), None, (Align::Start, Align::Start));
+        // render title, the player on the first row and the game modes on the last
+        render_button(con, 0, 0, CONSOLE_WIDTH, 5, "", blue, Some(darker_gray), None, (Align::Start, Align::Start));
+        con.print_color(CONSOLE_WIDTH as i32 / 2, 1, format!("#[{}]{}", blue.text(), glyphs(&format!("Scores: {}", self.scores[self.tab].0))).as_str(), TextAlign::Center, None);
+        let tabs = MODES.iter().enumerate().map(|(i, (name, color))| match i == self.mode as usize {
+            true => format!("#[{}][{}]", color.text(), name),
+            false => format!("#[dark_gray] {} ", name),
+        }).collect::<Vec<String>>().join(" ");
+        con.print_color(CONSOLE_WIDTH as i32 / 2, 3, format!("{} #[gray]Tab", tabs).as_str(), TextAlign::Center, None);
 
         // renders the Esc button 
         render_button(con, 0, 0, 7, 5, "Esc", red, Some(darker_gray), None, (Align::Start, Align::Start));
 
+        // renders the export and import buttons, and the result of the last one
+        render_button(con, CONSOLE_WIDTH as i32 - 24, 0, 12, 5, "E Export", RTColor::Gray, Some(darker_gray), None, (Align::Start, Align::Start));
+        render_button(con, CONSOLE_WIDTH as i32 - 12, 0, 12, 5, "I Import", RTColor::Gray, Some(darker_gray), None, (Align::Start, Align::Start));
+        if let Some(status) = &self.status {
+            con.print(CONSOLE_WIDTH as i32 / 2, 4, glyphs(status).as_str(), TextAlign::Center, Some(RTColor::Gray.u8()), None);
+        }
+
         // render labels
         render_button(con, 0,  5, 40, 5, "#[cyan]Best Scores", RTColor::Gray, Some(darker_gray), None, (Align::Start, Align::Start));
         render_button(con, 40, 5, 37, 5, "#[magenta]History", RTColor::Gray, Some(darker_gray), None, (Align::Start, Align::Start));
@@ -206,6 +259,46 @@ impl RustyEngine for Scores {
     }
 }
 
+// renders a score record in a box 5 rows tall: the date and game mode on the top border, the player and score,
+// the statistics of the run, and the seed of it's bag on the bottom border
+fn render_record (con: &mut Console, x: i32, y: i32, width: i32, rank: Option<usize>, record: &ScoreRecord, profiles: &[Profile]) {
+
+    let white = RTColor::White.u8();
+    let gray = RTColor::Gray.u8();
+    let darker_gray = RTColor::DarkerGray.u8();
+    let right = x + width - 2;
+
+    con.area(x, y, width as u32, 1, None, Some(darker_gray), Some(0));
+    con.area(x, y + 4, width as u32, 1, None, Some(darker_gray), Some(0));
+    con.rectangle(x, y, width as u32, 5, Some(white), None, Some(0));
+
+    // top border: rank (best scores only), date and game mode
+    let date_x = match rank {
+        Some(rank) => { con.print(x + 4, y, format!("{}º", rank).as_str(), TextAlign::Right, Some(white), None); x + 6 },
+        None => x + 2,
+    };
+    con.print(date_x, y, format_date(record.timestamp).as_str(), TextAlign::Left, Some(gray), None);
+    let (mode, color) = MODES[record.game_mode as usize];
+    con.print(right, y, mode, TextAlign::Right, Some(color.u8()), None);
+
+    // player and score
+    let name = match record.player { 0 => "Guest", player => profiles.iter().find(|p| p.id == player).map(|p| p.name.as_str()).unwrap_or("?") };
+    con.print(x + 1, y + 1, glyphs(name).as_str(), TextAlign::Left, Some(RTColor::Blue.u8()), None);
+    con.print_color(right - 1, y + 1, format!("#[green]{}", record.score).as_str(), TextAlign::Right, None);
+
+    // statistics, scores tracked before they were recorded only have the score
+    if record.duration == 0 {
+        con.print(x + 1, y + 2, "no statistics", TextAlign::Left, Some(gray), None);
+        return
+    }
+    let seconds = record.duration / 60;
+    con.print_color(x + 1, y + 2, format!("#[white]{}:{:02}  #[gray]lines #[white]{}  #[gray]lv #[white]{}", seconds / 60, seconds % 60, record.lines, record.level).as_str(), TextAlign::Left, None);
+    con.print_color(x + 1, y + 3, format!("#[gray]pieces #[white]{}  #[white]{:.2}#[gray]pps  combo #[white]{}", record.pieces, record.pps(), record.max_combo).as_str(), TextAlign::Left, None);
+
+    // bottom border: seed of the bag
+    con.print(right, y + 4, format!("seed {:016x}", record.seed).as_str(), TextAlign::Right, Some(gray), None);
+}
+
 impl InputHandler for Scores {
 
     fn register_inputs (&mut self) {
@@ -215,6 +308,9 @@ impl InputHandler for Scores {
             crate::KeyMap::new("ArrowDown",     "", Some(4) ),
             crate::KeyMap::new("ArrowLeft",     "", Some(6) ),
             crate::KeyMap::new("ArrowRight",    "", Some(6) ),
+            crate::KeyMap::new("KeyE",          "", None ),
+            crate::KeyMap::new("KeyI",          "", None ),
+            crate::KeyMap::new("Tab",           "", None ),
         ];
     }
 
@@ -232,6 +328,9 @@ impl InputHandler for Scores {
                 "ArrowDown"     => return self.action(1, 2),
                 "ArrowLeft"     => return self.action(0, 1),
                 "ArrowRight"    => return self.action(2, 1),
+                "KeyE"          => return self.action(0, 0),
+                "KeyI"          => return self.action(2, 0),
+                "Tab"           => return self.action(2, 2),
 
                 // no key ? probably a overlook
                 _=> { println!("{}.handle_input: Key '{}' is registered but not mapped!", std::any::type_name::<Self>(), self.inputmap[index].key_text); return None }
@@ -1,4 +1,4 @@
-use crate::{RustyEngine, GameEvent, InputHandler, Controller, config_tracker::*, InputID, rt::render::render_popup_window};
+use crate::{RustyEngine, GameEvent, InputHandler, Controller, VALID_KEYS, config_tracker::*, profile_tracker, load_ruleset, Preferences, Theme, DAS_OPTIONS, ARR_OPTIONS, PREVIEW_OPTIONS, InputID, RotationSystemID, PieceSet, BoardSize, GameRules, LINE_CLEAR_DELAYS, ENTRY_DELAYS, rt::render::render_popup_window};
 
 enum Action {
     Set,
@@ -16,16 +16,54 @@ impl Action {
 
 const ACTIONS: [Action; 2] = [Action::Set, Action::Reset];
 
+// number of keys of a controller, the rows after them select the rules of the game mode
+const KEY_ROWS: usize = 9;
+const ROTATION_ROW: usize = KEY_ROWS;
+const PIECES_ROW: usize = KEY_ROWS + 1;
+const BOARD_ROW: usize = KEY_ROWS + 2;
+const LINE_CLEAR_ROW: usize = KEY_ROWS + 3;
+const ENTRY_DELAY_ROW: usize = KEY_ROWS + 4;
+const IRS_ROW: usize = KEY_ROWS + 5;
+
+// the last rows select the gameplay preferences of the profile, shared by all tabs
+const DAS_ROW: usize = KEY_ROWS + 6;
+const ARR_ROW: usize = KEY_ROWS + 7;
+const GHOST_ROW: usize = KEY_ROWS + 8;
+const PREVIEW_ROW: usize = KEY_ROWS + 9;
+const THEME_ROW: usize = KEY_ROWS + 10;
+const AUTO_PAUSE_ROW: usize = KEY_ROWS + 11;
+const ROWS: usize = KEY_ROWS + 12;
+
+// number of rows fitting below the title, the list scrolls to keep the cursor on them
+const VISIBLE_ROWS: usize = 15;
+
+// returns the option after the current one, wrapping around
+fn next_option<T: PartialEq + Copy> (all: &[T], current: T) -> T {
+    let index = all.iter().position(|option| *option == current).unwrap_or(0);
+    all[(index + 1) % all.len()]
+}
+
 enum SubState {
     Main,
     KeySelect,
+    RulesetError(String),
+
+    // the error, and whether the settings can't be saved to the file until it's fixed
+    ConfigError(String, bool),
 }
 
 pub struct Settings {
+    profile: u32,
     defaults: [Controller; 3],
     pub controllers: [Controller; 3],
+    pub rules: [GameRules; 2],
+    pub preferences: Preferences,
+
+    // name of the custom ruleset, shown instead of the rotation system when it's selected
+    ruleset: Option<String>,
     state: SubState,
     cursor: usize,
+    scroll_pos: usize,
     button: usize,
     tab: usize,
     inputmap: Vec::<crate::KeyMap>,
@@ -33,31 +71,108 @@ pub struct Settings {
 
 impl Settings {
     pub fn new () -> Self {
+
+        // key bindings and preferences are the ones of the profile of the session, the guest (0) uses the global ones
+        let profile = profile_tracker::load_profile().unwrap_or(0);
         Self {
+            profile,
             defaults: [Controller::default(), Controller::default_versus1(), Controller::default_versus2()],
-            controllers: match get_controllers() { Ok(controllers) => controllers, Err(err) => panic!("state/states/settings::new() -- Error loading controllers: {}", err)},
-            state: SubState::Main,
+            controllers: get_controllers(profile),
+            rules: [get_rules(0), get_rules(1)],
+            preferences: get_preferences(profile),
+            ruleset: load_ruleset().ok().map(|ruleset| ruleset.name),
+
+            // the config files can't be read entirely, saving from here only edits the values that changed
+            state: match config_errors(&[profile]).first() {
+                Some(err) => SubState::ConfigError(err.to_string(), err.blocks_saving()),
+                None => SubState::Main,
+            },
             cursor: 0,
+            scroll_pos: 0,
             button: 0,
             tab: 0,
             inputmap: vec![],
         }
     }
 
+    // reloads the settings after the config files changed, unless a key is being bound
+    pub fn reload_config (&mut self) {
+        if let SubState::KeySelect = self.state { return }
+        self.controllers = get_controllers(self.profile);
+        self.rules = [get_rules(0), get_rules(1)];
+        self.preferences = get_preferences(self.profile);
+    }
+
     // resets the input at tab + cursor to it's default value
     fn reset_at (&mut self) {
-        self.controllers[self.tab].set_at(self.cursor, self.defaults[self.tab].get_at(self.cursor))
+        let default = GameRules::default();
+        let preferences = Preferences::default();
+        let rules = &mut self.rules[self.tab.min(1)];
+        match self.cursor {
+            ROTATION_ROW => rules.rotation_system = default.rotation_system,
+            PIECES_ROW => rules.piece_set = default.piece_set,
+            BOARD_ROW => rules.board_size = default.board_size,
+            LINE_CLEAR_ROW => rules.line_clear_delay = default.line_clear_delay,
+            ENTRY_DELAY_ROW => rules.entry_delay = default.entry_delay,
+            IRS_ROW => rules.initial_rotation = default.initial_rotation,
+            DAS_ROW => self.preferences.das = preferences.das,
+            ARR_ROW => self.preferences.arr = preferences.arr,
+            GHOST_ROW => self.preferences.ghost = preferences.ghost,
+            PREVIEW_ROW => self.preferences.preview = preferences.preview,
+            THEME_ROW => self.preferences.theme = preferences.theme,
+            AUTO_PAUSE_ROW => self.preferences.auto_pause = preferences.auto_pause,
+            _ => self.controllers[self.tab].set_at(self.cursor, self.defaults[self.tab].get_at(self.cursor)),
+        }
     }
 
     // triggers the action at tab + cursor + button
     fn action (&mut self) -> Option<GameEvent> {
+        let game_mode = self.game_mode();
+        let rules = &mut self.rules[game_mode];
         match ACTIONS[self.button] {
-            Action::Set => self.state = SubState::KeySelect,
+            Action::Set => match self.cursor {
+                ROTATION_ROW => self.cycle_rotation_system(),
+                PIECES_ROW => rules.piece_set = next_option(&PieceSet::all(), rules.piece_set),
+                BOARD_ROW => rules.board_size = next_option(&BoardSize::all(), rules.board_size),
+                LINE_CLEAR_ROW => rules.line_clear_delay = next_option(&LINE_CLEAR_DELAYS, rules.line_clear_delay),
+                ENTRY_DELAY_ROW => rules.entry_delay = next_option(&ENTRY_DELAYS, rules.entry_delay),
+                IRS_ROW => rules.initial_rotation = !rules.initial_rotation,
+                DAS_ROW => self.preferences.das = next_option(&DAS_OPTIONS, self.preferences.das),
+                ARR_ROW => self.preferences.arr = next_option(&ARR_OPTIONS, self.preferences.arr),
+                GHOST_ROW => self.preferences.ghost = !self.preferences.ghost,
+                PREVIEW_ROW => self.preferences.preview = next_option(&PREVIEW_OPTIONS, self.preferences.preview),
+                THEME_ROW => self.preferences.theme = next_option(&Theme::all(), self.preferences.theme),
+                AUTO_PAUSE_ROW => self.preferences.auto_pause = !self.preferences.auto_pause,
+                _ => self.state = SubState::KeySelect,
+            },
             Action::Reset => self.reset_at()
         }
         None
     }
 
+    // index of the game mode configured by the current tab (0: singleplayer, 1: versus)
+    fn game_mode (&self) -> usize {
+        self.tab.min(1)
+    }
+
+    // switches the rotation system of the current tab's game mode to the next one,
+    // skipping the custom ruleset with an error popup if it's file isn't valid
+    fn cycle_rotation_system (&mut self) {
+        let all = RotationSystemID::all();
+        let game_mode = self.game_mode();
+        let mut next = next_option(&all, self.rules[game_mode].rotation_system);
+        if next == RotationSystemID::Custom {
+            match load_ruleset() {
+                Ok(ruleset) => self.ruleset = Some(ruleset.name),
+                Err(err) => {
+                    self.state = SubState::RulesetError(err.to_string());
+                    next = next_option(&all, next);
+                }
+            }
+        }
+        self.rules[game_mode].rotation_system = next;
+    }
+
     // logic redirect to toggle_action or tab acordingly
     fn horizontal_input(&mut self, right: bool) -> Option<GameEvent> {
         match right {
@@ -82,7 +197,11 @@ impl Settings {
 
     // scrolls through the content 
     fn scroll (&mut self, dir: i8) -> Option<GameEvent> {
-        self.cursor = ((self.cursor as i8 + dir) + 8) as usize % 8;
+        self.cursor = ((self.cursor as i8 + dir) + ROWS as i8) as usize % ROWS;
+
+        // keep the cursor on screen
+        if self.cursor < self.scroll_pos { self.scroll_pos = self.cursor }
+        if self.cursor >= self.scroll_pos + VISIBLE_ROWS { self.scroll_pos = self.cursor + 1 - VISIBLE_ROWS }
         None
     }
 
@@ -91,13 +210,13 @@ impl Settings {
 
         // if currently waiting for key select, back to settings
         match self.state {
-            SubState::KeySelect => {
+            SubState::KeySelect | SubState::RulesetError(_) | SubState::ConfigError(..) => {
                 self.state = SubState::Main;
                 None
             },
             SubState::Main => {
                 println!("saving config");
-                let _ = save_controllers(&mut self.controllers);
+                if let Err(err) = save_settings(self.profile, &self.controllers, &self.rules, &self.preferences) { println!("Error saving settings: {}", err) }
                 Some(GameEvent::PreviousState)
             }
         }        
@@ -118,6 +237,7 @@ impl RustyEngine for Settings {
         (self.handle_input(input, match self.state {
             SubState::Main => "main",
             SubState::KeySelect => "selk",
+            SubState::RulesetError(_) | SubState::ConfigError(..) => "err",
         }), None)
     }
 
@@ -137,34 +257,58 @@ impl RustyEngine for Settings {
         let controller = &self.controllers[self.tab];
 
         // scrollbar
-        // render_rect(con, CONSOLE_WIDTH as i32, 8, 3, CONSOLE_HEIGHT - 11, Some(('|', darker_gray)), Some(black.u8()), (Align::End, Align::Start));
-        //     let max_list_len = 8 as i32;
-        //     let scrollbar_height = (CONSOLE_HEIGHT as i32 - 11 - (max_list_len - 14)).max(1) as u32;
-        //         render_rect(con, CONSOLE_WIDTH as i32, 8 + self.scroll_pos as i32, 3, scrollbar_height, Some((' ', darker_gray)), Some(dark_gray), (Align::End, Align::Start));
-
-        // keys
-        for i in 0..8 {
-            render_rect(con, 0, i as i32 * 5 + 5, CONSOLE_WIDTH - 3, 5, None, Some(darker_gray), Align::start2());
-            con.print(11, i as i32 * 5 + 7, InputID::from_index(i).as_str(), doryen_rs::TextAlign::Center, Some(white.u8()), None);
-            con.print(30, i as i32 * 5 + 7, controller.get_at(i), doryen_rs::TextAlign::Center, Some(white.u8()), None);
+        let track_height = CONSOLE_HEIGHT - 5;
+        let thumb_height = (track_height as usize * VISIBLE_ROWS / ROWS) as u32;
+        let thumb_pos = (track_height - thumb_height) as usize * self.scroll_pos / (ROWS - VISIBLE_ROWS);
+        render_rect(con, CONSOLE_WIDTH as i32, 5, 3, track_height, Some(('|', darker_gray)), Some(black.u8()), (Align::End, Align::Start));
+        render_rect(con, CONSOLE_WIDTH as i32, 5 + thumb_pos as i32, 3, thumb_height, Some((' ', darker_gray)), Some(dark_gray), (Align::End, Align::Start));
+
+        // keys and rules of the game mode
+        let rules = &self.rules[self.game_mode()];
+        let preferences = &self.preferences;
+        let on_off = |on: bool| (if on { "On" } else { "Off" }).to_string();
+        for i in self.scroll_pos..self.scroll_pos + VISIBLE_ROWS {
+            let y = (i - self.scroll_pos) as i32 * 5 + 5;
+            render_rect(con, 0, y, CONSOLE_WIDTH - 3, 5, None, Some(darker_gray), Align::start2());
+            let (label, value) = match i {
+                ROTATION_ROW => ("Rotation", match (rules.rotation_system, &self.ruleset) {
+                    (RotationSystemID::Custom, Some(name)) if !name.is_empty() => name.chars().take(16).collect(),
+                    (system, _) => system.text().to_string(),
+                }),
+                PIECES_ROW => ("Pieces", rules.piece_set.text().to_string()),
+                BOARD_ROW => ("Board", rules.board_size.text().to_string()),
+                LINE_CLEAR_ROW => ("Line clear", format!("{} frames", rules.line_clear_delay)),
+                ENTRY_DELAY_ROW => ("Entry delay", format!("{} frames", rules.entry_delay)),
+                IRS_ROW => ("Initial rot.", on_off(rules.initial_rotation)),
+                DAS_ROW => ("Auto shift", format!("{} frames", preferences.das)),
+                ARR_ROW => ("Shift repeat", format!("{} frames", preferences.arr)),
+                GHOST_ROW => ("Ghost piece", on_off(preferences.ghost)),
+                PREVIEW_ROW => ("Next pieces", preferences.preview.to_string()),
+                THEME_ROW => ("Theme", preferences.theme.text().to_string()),
+                AUTO_PAUSE_ROW => ("Auto pause", (if preferences.auto_pause { "If minimized" } else { "Off" }).to_string()),
+                _ => (InputID::from_index(i).as_str(), controller.get_at(i).to_string()),
+            };
+            con.print(11, y + 2, label, doryen_rs::TextAlign::Center, Some(white.u8()), None);
+            con.print(30, y + 2, &value, doryen_rs::TextAlign::Center, Some(white.u8()), None);
             for j in 0..ACTIONS.len() {
                 let selected = self.cursor == i && self.button == j;
                 let color = if selected { red } else { white };
                 let fore = if selected { Some(white.u8()) } else { None };
                 let back = Some(if selected { white.u8() } else { darker_gray });    
-                render_button(con, j as i32 * 18 + 40, i as i32 * 5 + 6, 18, 3, ACTIONS[j].text(), color, fore, back, Align::start2());
+                render_button(con, j as i32 * 18 + 40, y + 1, 18, 3, ACTIONS[j].text(), color, fore, back, Align::start2());
             }
         }
 
         
         // render title
-        let title = format!("Controller Settings: {}", ["Singleplayer", "Versus: Player 1", "Versus: Player 2"][self.tab]);
+        let owner = match self.profile { 0 => "Guest".to_string(), profile => crate::glyphs(&profile_tracker::profile_name(profile)) };
+        let title = format!("Settings of {}: {}", owner, ["Singleplayer", "Versus: Player 1", "Versus: Player 2"][self.tab]);
         render_button(con, 0, 0, CONSOLE_WIDTH, 5, title.as_str(), [RTColor::Cyan, RTColor::Magenta, RTColor::Green][self.tab], Some(darker_gray), None, (Align::Start, Align::Start));
 
         // renders the Esc button 
         render_button(con, 0, 0, 7, 5, "Esc", red, Some(darker_gray), None, (Align::Start, Align::Start));
 
-        match self.state { 
+        match &self.state { 
             SubState::KeySelect => {
                 let half_con_width = CONSOLE_WIDTH as i32 / 2;
                 let half_con_height = CONSOLE_HEIGHT as i32 / 2;
@@ -175,6 +319,26 @@ impl RustyEngine for Settings {
                 con.print(half_con_width, half_con_height, "Press (almost) any key to rebind", doryen_rs::TextAlign::Center, Some(white.u8()), None);
                 con.print(half_con_width, half_con_height + 1, "Press \"Esc\" to cancel", doryen_rs::TextAlign::Center, Some(dark_gray), None);
             },
+            SubState::RulesetError(err) | SubState::ConfigError(err, _) => {
+                let half_con_width = CONSOLE_WIDTH as i32 / 2;
+                let half_con_height = CONSOLE_HEIGHT as i32 / 2;
+
+                render_popup_window(con, half_con_width, half_con_height, 48, 24, Align::center2(), Some(dark_gray), Some(black.u8()), Some(0));
+
+                let title = match &self.state { SubState::ConfigError(..) => "Invalid config file", _ => "Invalid custom ruleset" };
+                con.print(half_con_width, half_con_height - 6, title, doryen_rs::TextAlign::Center, Some(red.u8()), None);
+                if let SubState::ConfigError(_, blocks_saving) = &self.state {
+                    let hint = if *blocks_saving { "Settings aren't saved to it until it's fixed" } else { "Leaving the Settings only edits what changed" };
+                    con.print(half_con_width, half_con_height + 6, hint, doryen_rs::TextAlign::Center, Some(dark_gray), None);
+                }
+
+                // wrap the error message inside the popup
+                let chars: Vec<char> = err.chars().collect();
+                for (i, line) in chars.chunks(42).enumerate() {
+                    con.print(half_con_width, half_con_height - 4 + i as i32, &String::from_iter(line), doryen_rs::TextAlign::Center, Some(white.u8()), None);
+                }
+                con.print(half_con_width, half_con_height + 8, "Press \"Esc\" to close", doryen_rs::TextAlign::Center, Some(dark_gray), None);
+            },
             _=> {}
         }
 
@@ -191,6 +355,8 @@ impl InputHandler for Settings {
             crate::KeyMap::new("ArrowDown",     "main", Some(4) ),
             crate::KeyMap::new("ArrowLeft",     "main", Some(6) ),
             crate::KeyMap::new("ArrowRight",    "main", Some(6) ),
+            crate::KeyMap::new("Enter",         "err",  None ),
+            crate::KeyMap::new("Escape",        "err",  None ),
         ];
     }
 
@@ -200,89 +366,10 @@ impl InputHandler for Settings {
 
             if input.key_pressed("Escape") { return self.escape() }
 
-            let valid_keys = [
-                "Digit1",
-                "Digit2",
-                "Digit3",
-                "Digit4",
-                "Digit5",
-                "Digit6",
-                "Digit7",
-                "Digit8",
-                "Digit9",
-                "Digit0",
-                "KeyA",
-                "KeyB",
-                "KeyC",
-                "KeyD",
-                "KeyE",
-                "KeyF",
-                "KeyG",
-                "KeyH",
-                "KeyI",
-                "KeyJ",
-                "KeyK",
-                "KeyL",
-                "KeyM",
-                "KeyN",
-                "KeyO",
-                "KeyP",
-                "KeyQ",
-                "KeyR",
-                "KeyS",
-                "KeyT",
-                "KeyU",
-                "KeyV",
-                "KeyW",
-                "KeyX",
-                "KeyY",
-                "KeyZ",
-                "F1",
-                "F2",
-                "F3",
-                "F4",
-                "F5",
-                "F6",
-                "F7",
-                "F8",
-                "F9",
-                "F10",
-                "F11",
-                "F12",
-                "Snapshot",
-                "Insert",
-                "Home",
-                "Delete",
-                "End",
-                "PageDown",
-                "PageUp",
-                "ArrowLeft",
-                "ArrowUp",
-                "ArrowRight",
-                "ArrowDown",
-                "Backspace",
-                "Enter",
-                "Space",
-                "Numpad5",
-                "NumpadAdd",
-                "Backslash",
-                "Colon",
-                "Comma",
-                "NumpadDivide",
-                "Equal",
-                "Backquote",
-                "BracketLeft",
-                "Minus",
-                "Period",
-                "BracketRight",
-                "NumpadSubtract",
-                "Tab",                
-            ];
-
-            for i in 0..valid_keys.len() {
-                if input.key_pressed(valid_keys[i]) {
-
-                    self.controllers[self.tab].set_at(self.cursor, valid_keys[i]);
+            for key in VALID_KEYS {
+                if input.key_pressed(key) {
+
+                    self.controllers[self.tab].set_at(self.cursor, key);
                     self.state = SubState::Main;
                     return None
                 }
@@ -307,6 +394,8 @@ impl InputHandler for Settings {
                     ("ArrowDown", "main")     => return self.scroll(1),
                     ("ArrowLeft", "main")     => return self.horizontal_input(false),
                     ("ArrowRight", "main")    => return self.horizontal_input(true),
+                    ("Enter", "err")          => return self.escape(),
+                    ("Escape", "err")         => return self.escape(),
 
                     // no key ? probably a overlook
                     _=> { println!("{}.handle_input: Key '{}' is registered but not mapped!", std::any::type_name::<Self>(), self.inputmap[index].key_text); return None }
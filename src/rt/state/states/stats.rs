@@ -0,0 +1,220 @@
+use crate::{InputHandler, GameEvent, RustyEngine, MODES};
+use crate::{CONSOLE_HEIGHT, CONSOLE_WIDTH};
+use crate::{Align, RTColor, ScoreRecord, render_rect, render_button, render_sparkline, render_bar, glyphs};
+use doryen_rs::TextAlign;
+
+// names of the line clears, by the number of rows cleared at once
+const CLEARS: [&str; 5] = ["Single", "Double", "Triple", "Tetris", "Pentris"];
+
+// width of the trend charts, the last games played that fit on it are shown
+const CHART_WIDTH: usize = 60;
+
+// totals of the history of a player
+struct Summary {
+    name: String,
+    games: usize,
+    lines: u32,
+    pieces: u32,
+    frames: u64,
+
+    // games played, sum of the scores and best score of each game mode
+    modes: Vec<(usize, i64, i32)>,
+
+    // runs tracked with the line clears, their clears and the lines cleared on them
+    clears: [u32; 5],
+    clear_lines: u32,
+
+    // pieces per second, attack per minute and score of each game with statistics, oldest first
+    pps: Vec<f32>,
+    apm: Vec<f32>,
+    scores: Vec<f32>,
+}
+
+impl Summary {
+
+    // adds up the given history
+    fn new (name: &str, history: &[ScoreRecord]) -> Self {
+        let mut summary = Self {
+            name: name.to_string(),
+            games: history.len(),
+            lines: 0, pieces: 0, frames: 0,
+            modes: vec![(0, 0, 0); MODES.len()],
+            clears: [0; 5], clear_lines: 0,
+            pps: vec![], apm: vec![], scores: vec![],
+        };
+        for record in history {
+            if let Some(mode) = summary.modes.get_mut(record.game_mode as usize) {
+                mode.0 += 1;
+                mode.1 += record.score as i64;
+                mode.2 = mode.2.max(record.score);
+            }
+            summary.lines += record.lines;
+            summary.pieces += record.pieces;
+            summary.frames += record.duration as u64;
+
+            // scores tracked before the statistics were recorded only have the score
+            if record.duration == 0 { continue }
+            summary.pps.push(record.pps());
+            summary.scores.push(record.score as f32);
+
+            // and the ones before the line clears were recorded have lines without clears
+            if record.lines > 0 && record.clears.iter().all(|clears| *clears == 0) { continue }
+            summary.apm.push(record.apm());
+            for i in 0..record.clears.len() { summary.clears[i] += record.clears[i] }
+            summary.clear_lines += record.lines;
+        }
+        summary
+    }
+
+    // lines cleared by tetrises (or more rows at once) over the lines of the runs with line clears
+    fn tetris_rate (&self) -> f32 {
+        if self.clear_lines == 0 { return 0.0 }
+        (self.clears[3] * 4 + self.clears[4] * 5) as f32 / self.clear_lines as f32
+    }
+}
+
+// defines the state "Stats"
+pub struct Stats {
+    summaries: Vec<Summary>,
+    tab: usize,
+    inputmap: Vec::<crate::KeyMap>,
+}
+
+impl Stats {
+
+    // initialize the state, adding up the history of everyone, then of each profile with any
+    pub fn new () -> Self {
+        use crate::{load_history, get_profiles};
+
+        let mut summaries = vec![Summary::new("All", &load_history(None, None).unwrap_or_default())];
+        for profile in get_profiles().unwrap_or_default() {
+            let history = load_history(Some(profile.id), None).unwrap_or_default();
+            if !history.is_empty() { summaries.push(Summary::new(&profile.name, &history)) }
+        }
+        Self { summaries, tab: 0, inputmap: vec![] }
+    }
+
+    // switches between players
+    fn tab (&mut self, dir: i32) -> Option<GameEvent> {
+        let len = self.summaries.len() as i32;
+        self.tab = (((self.tab as i32 + dir) % len + len) % len) as usize;
+        None
+    }
+}
+
+impl RustyEngine for Stats {
+
+    fn init(&mut self) {
+        self.register_inputs()
+    }
+
+    fn update(&mut self, api: &mut dyn doryen_rs::DoryenApi) -> (Option<GameEvent>, Option<doryen_rs::UpdateEvent>) {
+        let input = api.input();
+        (self.handle_input(input, ""), None)
+    }
+
+    fn render(&mut self, api: &mut dyn doryen_rs::DoryenApi) {
+
+        // get the console
+        let con = api.con();
+
+        let white = RTColor::White.u8();
+        let gray = RTColor::Gray.u8();
+        let darker_gray = RTColor::DarkerGray.u8();
+        let summary = &self.summaries[self.tab];
+
+        render_rect(con, 0, 5, CONSOLE_WIDTH, CONSOLE_HEIGHT - 5, None, Some(darker_gray), Align::start2());
+
+        // totals
+        let seconds = summary.frames / 60;
+        con.print_color(2, 7, format!("#[gray]games #[white]{}  #[gray]lines #[white]{}  #[gray]pieces #[white]{}  #[gray]time played #[white]{}:{:02}:{:02}",
+            summary.games, summary.lines, summary.pieces, seconds / 3600, seconds / 60 % 60, seconds % 60).as_str(), TextAlign::Left, None);
+
+        // average and best score of each game mode
+        con.print(2, 10, "Mode              Games     Average        Best", TextAlign::Left, Some(gray), None);
+        for (i, (games, total, best)) in summary.modes.iter().enumerate() {
+            let (name, color) = MODES[i];
+            let average = if *games > 0 { *total / *games as i64 } else { 0 };
+            con.print(2, 11 + i as i32, name, TextAlign::Left, Some(color.u8()), None);
+            con.print(25, 11 + i as i32, format!("{}", games).as_str(), TextAlign::Right, Some(white), None);
+            con.print(37, 11 + i as i32, format!("{}", average).as_str(), TextAlign::Right, Some(white), None);
+            con.print(49, 11 + i as i32, format!("{}", best).as_str(), TextAlign::Right, Some(RTColor::Green.u8()), None);
+        }
+
+        // trends of the last games, each chart 4 rows tall with it's label, latest and average value above it
+        let trends: [(&str, &Vec<f32>, RTColor, i32); 3] = [
+            ("Pieces per second", &summary.pps, RTColor::Cyan, 16),
+            ("Attack per minute", &summary.apm, RTColor::Orange, 24),
+            ("Score", &summary.scores, RTColor::Green, 32),
+        ];
+        for (label, values, color, y) in trends {
+            con.print(2, y, label, TextAlign::Left, Some(gray), None);
+            if values.is_empty() {
+                con.print(2, y + 2, "no statistics", TextAlign::Left, Some(gray), None);
+                continue
+            }
+            let average = values.iter().sum::<f32>() / values.len() as f32;
+            con.print_color(CONSOLE_WIDTH as i32 - 3, y, format!("#[gray]last #[white]{:.2}  #[gray]average #[white]{:.2}", values[values.len() - 1], average).as_str(), TextAlign::Right, None);
+            let recent = &values[values.len().saturating_sub(CHART_WIDTH)..];
+            render_rect(con, 2, y + 1, CHART_WIDTH as u32, 4, None, Some(RTColor::Black.u8()), Align::start2());
+            render_sparkline(con, 2, y + 1, 4, recent, color.u8());
+            con.print(CHART_WIDTH as i32 + 3, y + 4, format!("last {}", recent.len()).as_str(), TextAlign::Left, Some(gray), None);
+        }
+
+        // distribution of the line clears
+        con.print(2, 40, "Line clears", TextAlign::Left, Some(gray), None);
+        let total: u32 = summary.clears.iter().sum();
+        let most = summary.clears.iter().copied().max().unwrap_or(0);
+        for (i, name) in CLEARS.iter().enumerate() {
+            let y = 42 + i as i32 * 2;
+            let share = if total > 0 { summary.clears[i] as f32 * 100.0 / total as f32 } else { 0.0 };
+            con.print(2, y, name, TextAlign::Left, Some(white), None);
+            render_bar(con, 12, y, 40, summary.clears[i] as f32, most as f32, RTColor::Magenta.u8());
+            con.print_color(54, y, format!("#[white]{}  #[gray]{:.0}%", summary.clears[i], share).as_str(), TextAlign::Left, None);
+        }
+
+        // share of the lines cleared by tetrises
+        let rate = summary.tetris_rate();
+        con.print(2, 53, "Tetris rate", TextAlign::Left, Some(gray), None);
+        render_bar(con, 12, 53, 40, rate, 1.0, RTColor::Cyan.u8());
+        con.print(54, 53, format!("{:.0}%", rate * 100.0).as_str(), TextAlign::Left, Some(white), None);
+
+        // render title
+        render_button(con, 0, 0, CONSOLE_WIDTH, 5, glyphs(&format!("Stats: {}", summary.name)).as_str(), RTColor::Orange, Some(darker_gray), None, Align::start2());
+
+        // renders the Esc button
+        render_button(con, 0, 0, 7, 5, "Esc", RTColor::Red, Some(darker_gray), None, Align::start2());
+    }
+}
+
+impl InputHandler for Stats {
+
+    fn register_inputs (&mut self) {
+        self.inputmap = vec![
+            crate::KeyMap::new("Escape",        "", None ),
+            crate::KeyMap::new("ArrowLeft",     "", Some(6) ),
+            crate::KeyMap::new("ArrowRight",    "", Some(6) ),
+        ];
+    }
+
+    fn handle_input(&mut self, input: &mut dyn doryen_rs::InputApi, _: &str) -> Option<GameEvent> {
+
+        // loop through all registered inputs
+        for index in 0..self.inputmap.len() {
+
+            // if trigger returns true, match the key to call the function
+            if self.inputmap[index].trigger(input).to_owned() { match self.inputmap[index].key_text.as_str() {
+
+                "Escape"        => return Some(GameEvent::main_menu()),
+                "ArrowLeft"     => return self.tab(-1),
+                "ArrowRight"    => return self.tab(1),
+
+                // no key ? probably a overlook
+                _=> { println!("{}.handle_input: Key '{}' is registered but not mapped!", std::any::type_name::<Self>(), self.inputmap[index].key_text); return None }
+            }}
+        }
+
+        // no result
+        None
+    }
+}
@@ -0,0 +1,305 @@
+use crate::{InputHandler, GameEvent, GameState, GameMode, InputID, Game, GameStats, RunState, RustyEngine, TetrominoID, load_best, profile_tracker::profile_name};
+use crate::{CONSOLE_HEIGHT, CONSOLE_WIDTH};
+use crate::{Align, RTColor, render_rect, render_button, render_bar, glyphs};
+use doryen_rs::TextAlign;
+
+// names of the line clears, by the number of rows cleared at once
+const CLEARS: [&str; 5] = ["Single", "Double", "Triple", "Tetris", "Pentris"];
+
+// width of each player's column
+const COLUMN_WIDTH: i32 = 36;
+
+// defines an Action enum to represent the buttons below the summary
+enum Action {
+    Retry,
+    Replay,
+    MainMenu,
+}
+
+impl Action {
+    fn text (&self) -> &str {
+        match self {
+            Action::Retry => "Retry",
+            Action::Replay => "View replay",
+            Action::MainMenu => "Main menu",
+        }
+    }
+    fn color (&self) -> RTColor {
+        match self {
+            Action::Retry => RTColor::Cyan,
+            Action::Replay => RTColor::Orange,
+            Action::MainMenu => RTColor::Red,
+        }
+    }
+}
+
+// const actions for len() and reference through index
+const ACTIONS: [Action; 3] = [
+    Action::Retry,
+    Action::Replay,
+    Action::MainMenu,
+];
+
+// what a Game of the run did
+struct Player {
+    name: String,
+    reason: String,
+    score: i32,
+    stats: GameStats,
+    pieces: &'static [TetrominoID],
+
+    // best score of the profile on the game mode before this run, if any
+    previous_best: Option<i32>,
+    won: Option<bool>,
+}
+
+impl Player {
+    fn new (game: &Game, game_mode: u8) -> Self {
+        let best = load_best(Some(game.profile), Some(game_mode)).unwrap_or_default();
+        Self {
+            name: match game.profile { 0 => "Guest".to_string(), profile => profile_name(profile) },
+            reason: match &game.run_state { RunState::Over(reason) => reason.text().to_string(), _ => String::new() },
+            score: game.score,
+            stats: game.stats,
+            pieces: game.rules.piece_set.pieces(),
+            previous_best: best.iter().map(|record| record.score).max(),
+            won: game.won,
+        }
+    }
+
+    // true if the run beat the best score of the profile on the game mode
+    fn personal_best (&self) -> bool {
+        self.score > 0 && self.previous_best.is_none_or(|best| self.score > best)
+    }
+}
+
+// defines the state "Summary", shown when a run is over
+pub struct Summary {
+    game_mode: GameMode,
+    players: Vec<Player>,
+    cursor_pos: usize,
+    inputmap: Vec::<crate::KeyMap>,
+
+    // the Games replaying the run while it's replay is watched
+    replay: Option<Vec<Game>>,
+    notice: Option<String>,
+}
+
+impl Summary {
+
+    // sums up the finished run and tracks it's scores, reading the best scores before they include it
+    pub fn new (game_mode: GameMode) -> Self {
+        let players = game_mode.games().iter().map(|game| Player::new(game, game_mode.id())).collect();
+        game_mode.game_over();
+        Self { game_mode, players, cursor_pos: 0, inputmap: vec![], replay: None, notice: None }
+    }
+
+    // redirects the Enter command to the action under the cursor
+    fn action (&mut self) -> Option<GameEvent> {
+        match ACTIONS[self.cursor_pos] {
+            Action::Retry => self.retry(),
+            Action::Replay => self.replay(),
+            Action::MainMenu => Some(GameEvent::main_menu()),
+        }
+    }
+
+    // starts the game mode over, with new seeds
+    fn retry (&self) -> Option<GameEvent> {
+        Some(GameEvent::State(GameState::Game(self.game_mode.restart())))
+    }
+
+    // starts the replay of the run, dealing the same Tetrominoes and playing the recorded keys
+    fn replay (&mut self) -> Option<GameEvent> {
+        if let Some(game) = self.game_mode.games().into_iter().find(|game| !game.replayable()) {
+            self.notice = Some(match game.handling {
+                None => "This run can't be replayed, the auto shift changed during it",
+                Some(_) => "This run can't be replayed, it was saved by an older version",
+            }.to_string());
+            return None
+        }
+        let mut games: Vec<Game> = self.game_mode.games().iter().map(|game| Game::replay(game)).collect();
+        for game in games.iter_mut() { game.init() }
+        self.replay = Some(games);
+        None
+    }
+
+    // moves the cursor through the actions
+    fn move_cursor (&mut self, dir: i32) -> Option<GameEvent> {
+        let len = ACTIONS.len() as i32;
+        self.cursor_pos = (((self.cursor_pos as i32 + dir) % len + len) % len) as usize;
+        None
+    }
+
+    // renders the summary of a player on a column starting at x
+    fn render_player (con: &mut doryen_rs::Console, x: i32, player: &Player) {
+        let white = RTColor::White.u8();
+        let gray = RTColor::Gray.u8();
+        let right = x + COLUMN_WIDTH;
+        let center = x + COLUMN_WIDTH / 2;
+
+        con.print(center, 7, glyphs(&player.name).as_str(), TextAlign::Center, Some(white), None);
+        match player.won {
+            Some(true) => con.print(center, 8, "Winner", TextAlign::Center, Some(RTColor::Green.u8()), None),
+            Some(false) => con.print(center, 8, player.reason.as_str(), TextAlign::Center, Some(RTColor::Red.u8()), None),
+            None => con.print(center, 8, player.reason.as_str(), TextAlign::Center, Some(gray), None),
+        };
+
+        // score and personal best
+        con.print(x, 10, "Score", TextAlign::Left, Some(gray), None);
+        con.print(right, 10, format!("{}", player.score).as_str(), TextAlign::Right, Some(white), None);
+        match (player.personal_best(), player.previous_best) {
+            (true, _) => con.print(center, 11, "New personal best!", TextAlign::Center, Some(RTColor::Yellow.u8()), None),
+            (false, Some(best)) => con.print(right, 11, format!("best {}", best).as_str(), TextAlign::Right, Some(gray), None),
+            _ => {}
+        };
+
+        // statistics of the run
+        let stats = &player.stats;
+        let seconds = stats.frames as f32 / 60.0;
+        let rows = [
+            ("Lines", format!("{}", stats.lines)),
+            ("Level", format!("{}", stats.level())),
+            ("Time", format!("{}:{:05.2}", stats.frames / 3600, seconds % 60.0)),
+            ("Pieces", format!("{}", stats.pieces)),
+            ("Pieces per second", format!("{:.2}", if seconds > 0.0 { stats.pieces as f32 / seconds } else { 0.0 })),
+            ("Attack", format!("{}", stats.attack)),
+            ("Max combo", format!("{}", stats.max_combo)),
+            ("Max back to back", format!("{}", stats.max_back_to_back)),
+        ];
+        for (i, (label, value)) in rows.iter().enumerate() {
+            con.print(x, 13 + i as i32, label, TextAlign::Left, Some(gray), None);
+            con.print(right, 13 + i as i32, value.as_str(), TextAlign::Right, Some(white), None);
+        }
+
+        // line clears
+        con.print(x, 22, "Line clears", TextAlign::Left, Some(gray), None);
+        let most = stats.clears.iter().copied().max().unwrap_or(0);
+        for (i, name) in CLEARS.iter().enumerate() {
+            let y = 23 + i as i32;
+            con.print(x, y, name, TextAlign::Left, Some(white), None);
+            render_bar(con, x + 9, y, COLUMN_WIDTH - 14, stats.clears[i] as f32, most as f32, RTColor::Magenta.u8());
+            con.print(right, y, format!("{}", stats.clears[i]).as_str(), TextAlign::Right, Some(white), None);
+        }
+
+        // Tetrominoes placed of each kind on the piece set of the run
+        con.print(x, 29, "Pieces", TextAlign::Left, Some(gray), None);
+        let most = player.pieces.iter().map(|id| stats.placed[id.index()]).max().unwrap_or(0);
+        for (i, id) in player.pieces.iter().enumerate() {
+            let y = 30 + i as i32;
+            let placed = stats.placed[id.index()];
+            con.print(x, y, id.text(), TextAlign::Left, Some(white), None);
+            render_bar(con, x + 9, y, COLUMN_WIDTH - 14, placed as f32, most as f32, RTColor::Cyan.u8());
+            con.print(right, y, format!("{}", placed).as_str(), TextAlign::Right, Some(white), None);
+        }
+    }
+}
+
+impl RustyEngine for Summary {
+
+    fn init(&mut self) {
+        self.register_inputs()
+    }
+
+    fn update(&mut self, api: &mut dyn doryen_rs::DoryenApi) -> (Option<GameEvent>, Option<doryen_rs::UpdateEvent>) {
+
+        // the replay plays until Escape or Enter goes back to the summary
+        if let Some(games) = self.replay.as_mut() {
+            let input = api.input();
+            if input.key_pressed("Escape") || input.key_pressed("Enter") {
+                self.replay = None;
+                return (None, None)
+            }
+            for game in games.iter_mut() { game.update(api); }
+            return (None, None)
+        }
+
+        let input = api.input();
+        (self.handle_input(input, ""), None)
+    }
+
+    fn render(&mut self, api: &mut dyn doryen_rs::DoryenApi) {
+
+        // replaying: the Games render themselves, with a notice on how to leave
+        if let Some(games) = self.replay.as_mut() {
+            for game in games.iter_mut() { game.render(api) }
+            api.con().print(CONSOLE_WIDTH as i32 / 2, 1, "Replay - Esc to return to the summary", TextAlign::Center, Some(RTColor::Orange.u8()), None);
+            return
+        }
+
+        let con = api.con();
+        let darker_gray = RTColor::DarkerGray.u8();
+        let half_con_width = CONSOLE_WIDTH as i32 / 2;
+
+        render_rect(con, 0, 5, CONSOLE_WIDTH, CONSOLE_HEIGHT - 5, None, Some(darker_gray), Align::start2());
+
+        // a column for each player, side by side on versus
+        let columns = self.players.len() as i32;
+        for (i, player) in self.players.iter().enumerate() {
+            let x = half_con_width - (COLUMN_WIDTH + 4) * columns / 2 + 2 + (COLUMN_WIDTH + 4) * i as i32;
+            Self::render_player(con, x, player);
+        }
+
+        // the actions below the summary
+        let button_y = CONSOLE_HEIGHT as i32 - 8;
+        for (i, action) in ACTIONS.iter().enumerate() {
+            let selected = i == self.cursor_pos;
+            render_button(
+                con,
+                half_con_width + (i as i32 - 1) * 20,
+                button_y,
+                18, 5,
+                action.text(),
+                if selected { RTColor::Black } else { action.color() },
+                if selected { Some(RTColor::White.u8()) } else { Some(darker_gray) },
+                if selected { Some(action.color().u8()) } else { Some(RTColor::Black.u8()) },
+                Align::center2(),
+            );
+        }
+        if let Some(notice) = &self.notice {
+            con.print(half_con_width, button_y + 4, notice, TextAlign::Center, Some(RTColor::Red.u8()), None);
+        }
+
+        // render title
+        let title = match self.game_mode { GameMode::SinglePlayer(_) => "Game over", GameMode::Versus(_, _) => "Versus over" };
+        render_button(con, 0, 0, CONSOLE_WIDTH, 5, title, RTColor::White, Some(darker_gray), None, Align::start2());
+    }
+}
+
+impl InputHandler for Summary {
+
+    fn register_inputs (&mut self) {
+
+        // the Retry key of the first player retries from here too
+        let retry = self.game_mode.games()[0].controller.get(InputID::Retry).to_string();
+        self.inputmap = vec![
+            crate::KeyMap::new(&retry,          "retry", None ),
+            crate::KeyMap::new("Escape",        "", None ),
+            crate::KeyMap::new("Enter",         "", None ),
+            crate::KeyMap::new("ArrowLeft",     "", Some(6) ),
+            crate::KeyMap::new("ArrowRight",    "", Some(6) ),
+        ];
+    }
+
+    fn handle_input(&mut self, input: &mut dyn doryen_rs::InputApi, _: &str) -> Option<GameEvent> {
+
+        // loop through all registered inputs
+        for index in 0..self.inputmap.len() {
+
+            // if trigger returns true, match the key to call the function
+            if self.inputmap[index].trigger(input).to_owned() { match self.inputmap[index].key_text.as_str() {
+
+                _ if self.inputmap[index].category == "retry" => return self.retry(),
+                "Escape"        => return Some(GameEvent::main_menu()),
+                "Enter"         => return self.action(),
+                "ArrowLeft"     => return self.move_cursor(-1),
+                "ArrowRight"    => return self.move_cursor(1),
+
+                // no key ? probably a overlook
+                _=> { println!("{}.handle_input: Key '{}' is registered but not mapped!", std::any::type_name::<Self>(), self.inputmap[index].key_text); return None }
+            }}
+        }
+
+        // no result
+        None
+    }
+}